@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::constants::version;
+use crate::filter::Filter;
+use crate::formatter::Formatter;
+use crate::parser::RdbParser;
+use crate::push::PushParser;
+use crate::types::{Limit, RdbError, RdbOk};
+
+/// Upper bounds for everything a corrupted or malicious file could blow up
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Limits {
+    pub max_string_size: u64,
+    pub max_collection_length: u64,
+    pub max_decompressed_size: u64,
+    pub max_keys: u64,
+    pub min_version: u32,
+    pub max_version: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_string_size: u64::MAX,
+            max_collection_length: u64::MAX,
+            max_decompressed_size: u64::MAX,
+            max_keys: u64::MAX,
+            min_version: version::SUPPORTED_MINIMUM,
+            max_version: version::SUPPORTED_MAXIMUM,
+        }
+    }
+}
+
+fn check(limit: Limit, value: u64, max: u64) -> RdbOk {
+    if value > max {
+        return Err(RdbError::LimitExceeded { limit, value, max });
+    }
+
+    Ok(())
+}
+
+impl Limits {
+    pub fn check_string_size(&self, size: u64) -> RdbOk {
+        check(Limit::StringSize, size, self.max_string_size)
+    }
+
+    pub fn check_collection_length(&self, length: u64) -> RdbOk {
+        check(Limit::CollectionLength, length, self.max_collection_length)
+    }
+
+    pub fn check_decompressed_size(&self, size: u64) -> RdbOk {
+        check(Limit::DecompressedSize, size, self.max_decompressed_size)
+    }
+
+    pub fn check_keys(&self, keys: u64) -> RdbOk {
+        check(Limit::Keys, keys, self.max_keys)
+    }
+
+    pub fn check_version(&self, version: u32) -> RdbOk {
+        let min = self.min_version.max(version::SUPPORTED_MINIMUM);
+        let max = self.max_version.min(version::SUPPORTED_MAXIMUM);
+
+        if version < min || version > max {
+            return Err(RdbError::UnsupportedVersion { version, min, max });
+        }
+
+        Ok(())
+    }
+}
+
+/// Configures a parser before it is created
+///
+/// By default nothing is limited. Parsing untrusted input should set
+/// limits, so that a single corrupted length can't make the parser allocate
+/// gigabytes of memory. Violations are reported as `RdbError::LimitExceeded`.
+///
+/// ```rust,no_run
+/// # use std::io::BufReader;
+/// # use std::fs::File;
+/// let reader = BufReader::new(File::open("dump.rdb").unwrap());
+/// let mut parser = rdb::RdbParser::builder()
+///     .max_string_size(512 * 1024 * 1024)
+///     .max_decompressed_size(512 * 1024 * 1024)
+///     .max_collection_length(1_000_000)
+///     .versions(6, 8)
+///     .build(reader, rdb::formatter::JSON::new(), rdb::filter::Simple::new());
+/// parser.parse().unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RdbParserBuilder {
+    limits: Limits,
+    databases: HashMap<u32, u32>,
+}
+
+impl RdbParserBuilder {
+    pub fn new() -> RdbParserBuilder {
+        RdbParserBuilder::default()
+    }
+
+    /// Maximum length of a single (compressed) string, including ziplists and other encoded blobs
+    pub fn max_string_size(mut self, size: u64) -> RdbParserBuilder {
+        self.limits.max_string_size = size;
+        self
+    }
+
+    /// Maximum number of elements of a single list, set, sorted set or hash
+    pub fn max_collection_length(mut self, length: u64) -> RdbParserBuilder {
+        self.limits.max_collection_length = length;
+        self
+    }
+
+    /// Maximum length of a single LZF-compressed string after decompression
+    pub fn max_decompressed_size(mut self, size: u64) -> RdbParserBuilder {
+        self.limits.max_decompressed_size = size;
+        self
+    }
+
+    /// Maximum number of keys in the whole file, across all databases
+    pub fn max_keys(mut self, keys: u64) -> RdbParserBuilder {
+        self.limits.max_keys = keys;
+        self
+    }
+
+    /// Only accept RDB versions from `min` to `max`
+    ///
+    /// This can only narrow down the versions supported by this library.
+    pub fn versions(mut self, min: u32, max: u32) -> RdbParserBuilder {
+        self.limits.min_version = min;
+        self.limits.max_version = max;
+        self
+    }
+
+    /// Reports the keys of database `from` as keys of database `to`
+    ///
+    /// The filter and the formatter only see `to`. Parsing fails if two databases of the
+    /// input end up as the same database, e.g. when mapping 3 to 0 and the input has both.
+    pub fn map_database(mut self, from: u32, to: u32) -> RdbParserBuilder {
+        self.databases.insert(from, to);
+        self
+    }
+
+    pub fn build<R: Read, F: Formatter, L: Filter>(
+        self,
+        input: R,
+        formatter: F,
+        filter: L,
+    ) -> RdbParser<R, F, L> {
+        let mut parser = RdbParser::with_limits(input, formatter, filter, self.limits);
+        parser.map_databases(self.databases);
+        parser
+    }
+
+    pub fn build_push<F: Formatter, L: Filter>(self, formatter: F, filter: L) -> PushParser<F, L> {
+        let mut parser = PushParser::with_limits(formatter, filter, self.limits);
+        parser.map_databases(self.databases);
+        parser
+    }
+}
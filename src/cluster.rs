@@ -0,0 +1,166 @@
+//! Redis Cluster hash slots and splitting a RDB file by them
+//!
+//! Redis Cluster maps every key to one of 16384 hash slots. Only the part of the key
+//! between the first `{` and the following `}` is hashed, if it is not empty,
+//! so that related keys like `{user:1}:name` and `{user:1}:email` share a slot.
+//!
+//! `Split` uses the slots to turn a standalone snapshot into one RDB file per cluster node:
+//!
+//! ```rust,no_run
+//! # use std::io::{BufReader, BufWriter};
+//! # use std::fs::File;
+//! use rdb::cluster::Split;
+//!
+//! let input = BufReader::new(File::open("dump.rdb").unwrap());
+//! let outputs = vec![
+//!     BufWriter::new(File::create("node-1.rdb").unwrap()),
+//!     BufWriter::new(File::create("node-2.rdb").unwrap()),
+//! ];
+//!
+//! let mut split = Split::new(outputs);
+//! split.assign(0..=8191, 0);
+//! split.assign(8192..=16383, 1);
+//! split.split(input).unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::ops::RangeInclusive;
+
+use crate::copy::{RawEntry, RawReader};
+use crate::crc16::crc16;
+use crate::types::{RdbError, RdbResult};
+use crate::writer::RdbWriter;
+
+/// Number of hash slots of a Redis Cluster
+pub const SLOTS: u16 = 16384;
+
+/// Returns the part of `key` that is hashed to find its slot.
+pub fn hash_tag(key: &[u8]) -> &[u8] {
+    if let Some(start) = key.iter().position(|&b| b == b'{') {
+        if let Some(length) = key[start + 1..].iter().position(|&b| b == b'}') {
+            if length > 0 {
+                return &key[start + 1..start + 1 + length];
+            }
+        }
+    }
+
+    key
+}
+
+/// Returns the hash slot of `key`, between 0 and 16383.
+pub fn key_slot(key: &[u8]) -> u16 {
+    crc16(hash_tag(key)) & (SLOTS - 1)
+}
+
+/// Writes the keys of a RDB file to one output per cluster node, chosen by hash slot
+///
+/// Values are copied without being re-encoded, so the outputs have the version of the input.
+/// As Redis Cluster only supports database 0, only the keys of one database are kept,
+/// and written to database 0. Aux fields, functions and module aux data are copied to every
+/// output. RESIZEDB hints are left out.
+pub struct Split<W: Write> {
+    outputs: Vec<W>,
+    /// The output of every slot
+    slots: Vec<Option<usize>>,
+    database: u32,
+    databases: HashMap<u32, u32>,
+}
+
+impl<W: Write> Split<W> {
+    pub fn new(outputs: Vec<W>) -> Split<W> {
+        Split {
+            outputs,
+            slots: vec![None; SLOTS as usize],
+            database: 0,
+            databases: HashMap::new(),
+        }
+    }
+
+    /// Writes the keys in `slots` to the output with the index `output`.
+    ///
+    /// Panics if there is no such output or the range contains slots beyond 16383.
+    pub fn assign(&mut self, slots: RangeInclusive<u16>, output: usize) {
+        assert!(output < self.outputs.len(), "No output {}", output);
+        assert!(*slots.end() < SLOTS, "No slot {}", slots.end());
+
+        for slot in slots {
+            self.slots[slot as usize] = Some(output);
+        }
+    }
+
+    /// Keeps the keys of database `db` instead of database 0.
+    pub fn set_database(&mut self, db: u32) {
+        self.database = db;
+    }
+
+    /// Treats the keys of database `from` as keys of database `to`, before the database
+    /// to keep is chosen.
+    pub fn map_database(&mut self, from: u32, to: u32) {
+        self.databases.insert(from, to);
+    }
+
+    /// Splits `input` and returns the outputs.
+    ///
+    /// Fails if a key belongs to a slot that is not assigned to any output.
+    pub fn split<R: Read>(self, input: R) -> RdbResult<Vec<W>> {
+        let mut reader = RawReader::new(input)?;
+        let mut writers = Vec::with_capacity(self.outputs.len());
+        for output in self.outputs {
+            let mut writer = RdbWriter::new(output, reader.version())?;
+            writer.write_header()?;
+            writers.push(writer);
+        }
+        let mut selected = vec![false; writers.len()];
+        let mut database = 0;
+
+        loop {
+            let entry = reader.next_entry()?;
+
+            match entry {
+                RawEntry::Eof => break,
+                RawEntry::SelectDb(db) => database = self.databases.get(&db).copied().unwrap_or(db),
+                RawEntry::Global { op_code, ref raw } => {
+                    for writer in &mut writers {
+                        writer.write_u8(op_code)?;
+                        writer.write_raw(raw)?;
+                    }
+                }
+                RawEntry::Key {
+                    value_type,
+                    ref key,
+                    ..
+                } => {
+                    if database != self.database {
+                        reader.skip_value(value_type)?;
+                        continue;
+                    }
+
+                    let slot = key_slot(key);
+                    let output = self.slots[slot as usize].ok_or_else(|| {
+                        RdbError::Other(format!(
+                            "Slot {} of key {} is not assigned to any output",
+                            slot,
+                            String::from_utf8_lossy(key)
+                        ))
+                    })?;
+
+                    let writer = &mut writers[output];
+                    if !selected[output] {
+                        writer.write_select_db(0)?;
+                        selected[output] = true;
+                    }
+                    reader.copy_value(&entry, writer)?;
+                }
+            }
+        }
+
+        let mut outputs = Vec::with_capacity(writers.len());
+        for mut writer in writers {
+            writer.write_eof()?;
+            outputs.push(writer.into_inner());
+        }
+
+        Ok(outputs)
+    }
+}
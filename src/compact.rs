@@ -0,0 +1,338 @@
+//! Building the compact encodings Redis uses for small values:
+//! intsets, ziplists, listpacks and zipmaps
+
+/// Largest ziplist entry Redis still tries to store as an integer
+const ZIPLIST_MAX_INTEGER_LENGTH: usize = 32;
+/// zlbytes, zltail and zllen
+const ZIPLIST_HEADER_SIZE: usize = 10;
+/// The largest overhead of a ziplist entry: the previous length and the string header
+const ZIPLIST_MAX_ENTRY_OVERHEAD: usize = 10;
+/// Largest listpack entry Redis still tries to store as an integer
+const LISTPACK_MAX_INTEGER_LENGTH: usize = 20;
+/// Total bytes and number of entries
+const LISTPACK_HEADER_SIZE: usize = 6;
+/// The largest overhead of a listpack entry: the string header and the back length
+const LISTPACK_MAX_ENTRY_OVERHEAD: usize = 10;
+
+/// Parses `data` as an integer, if it is the canonical representation of one.
+///
+/// Like Redis, "+1", "01" or " 1" are not treated as integers, as they would not
+/// read back the same.
+pub fn parse_integer(data: &[u8]) -> Option<i64> {
+    let value: i64 = std::str::from_utf8(data).ok()?.parse().ok()?;
+
+    if value.to_string().as_bytes() == data {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Formats a score so that it parses back to the same value, preferring the shortest form.
+pub fn format_score(score: f64) -> String {
+    let plain = score.to_string();
+    let exponent = format!("{:e}", score);
+
+    if plain.len() <= exponent.len() {
+        plain
+    } else {
+        exponent
+    }
+}
+
+/// Builds an intset from the members, or returns `None` if any member is not an integer.
+pub fn intset(members: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let mut values = members
+        .iter()
+        .map(|member| parse_integer(member))
+        .collect::<Option<Vec<i64>>>()?;
+    values.sort_unstable();
+    values.dedup();
+
+    let fits = |min: i64, max: i64| values.iter().all(|value| (min..=max).contains(value));
+    let width: u32 = if fits(i16::MIN as i64, i16::MAX as i64) {
+        2
+    } else if fits(i32::MIN as i64, i32::MAX as i64) {
+        4
+    } else {
+        8
+    };
+
+    let mut intset = Vec::with_capacity(8 + values.len() * width as usize);
+    intset.extend_from_slice(&width.to_le_bytes());
+    intset.extend_from_slice(&(values.len() as u32).to_le_bytes());
+    for value in values {
+        intset.extend_from_slice(&value.to_le_bytes()[..width as usize]);
+    }
+
+    Some(intset)
+}
+
+/// A sequence of strings packed into one blob, i.e. a ziplist or a listpack
+pub trait Packed {
+    /// An empty blob in the format of RDB `version`
+    fn new(version: u32) -> Self;
+
+    /// The number of entries
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The size of the finished blob if an entry of `length` bytes was added
+    fn size_with(&self, length: usize) -> usize;
+
+    fn push(&mut self, data: &[u8]);
+
+    fn finish(self) -> Vec<u8>;
+}
+
+/// Builds a ziplist entry by entry
+#[derive(Default)]
+pub struct Ziplist {
+    entries: Vec<u8>,
+    count: usize,
+    last_entry: usize,
+    last_length: usize,
+    /// 8 and 24 bit integers and immediates, added in RDB version 6
+    small_integers: bool,
+}
+
+impl Ziplist {
+    fn size(&self) -> usize {
+        ZIPLIST_HEADER_SIZE + self.entries.len() + 1
+    }
+
+    fn push_integer(&mut self, value: i64) {
+        let bytes = value.to_le_bytes();
+
+        if self.small_integers && (0..=12).contains(&value) {
+            self.entries.push(0xf1 + value as u8);
+        } else if self.small_integers && (i8::MIN as i64..=i8::MAX as i64).contains(&value) {
+            self.entries.push(0xfe);
+            self.entries.extend_from_slice(&bytes[..1]);
+        } else if (i16::MIN as i64..=i16::MAX as i64).contains(&value) {
+            self.entries.push(0xc0);
+            self.entries.extend_from_slice(&bytes[..2]);
+        } else if self.small_integers && (-(1 << 23)..(1 << 23)).contains(&value) {
+            self.entries.push(0xf0);
+            self.entries.extend_from_slice(&bytes[..3]);
+        } else if (i32::MIN as i64..=i32::MAX as i64).contains(&value) {
+            self.entries.push(0xd0);
+            self.entries.extend_from_slice(&bytes[..4]);
+        } else {
+            self.entries.push(0xe0);
+            self.entries.extend_from_slice(&bytes);
+        }
+    }
+}
+
+impl Packed for Ziplist {
+    fn new(version: u32) -> Ziplist {
+        Ziplist {
+            small_integers: version >= 6,
+            ..Ziplist::default()
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+
+    fn size_with(&self, length: usize) -> usize {
+        self.size() + length + ZIPLIST_MAX_ENTRY_OVERHEAD
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        let start = self.entries.len();
+
+        if self.last_length < 254 {
+            self.entries.push(self.last_length as u8);
+        } else {
+            self.entries.push(254);
+            self.entries
+                .extend_from_slice(&(self.last_length as u32).to_le_bytes());
+        }
+
+        let integer = if data.len() <= ZIPLIST_MAX_INTEGER_LENGTH {
+            parse_integer(data)
+        } else {
+            None
+        };
+
+        match integer {
+            Some(value) => self.push_integer(value),
+            None => {
+                let length = data.len();
+                if length <= 0x3f {
+                    self.entries.push(length as u8);
+                } else if length <= 0x3fff {
+                    self.entries.push(0x40 | (length >> 8) as u8);
+                    self.entries.push(length as u8);
+                } else {
+                    self.entries.push(0x80);
+                    self.entries
+                        .extend_from_slice(&(length as u32).to_be_bytes());
+                }
+                self.entries.extend_from_slice(data);
+            }
+        }
+
+        self.last_entry = start;
+        self.last_length = self.entries.len() - start;
+        self.count += 1;
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let size = self.size();
+        let mut ziplist = Vec::with_capacity(size);
+        ziplist.extend_from_slice(&(size as u32).to_le_bytes());
+        ziplist.extend_from_slice(&((ZIPLIST_HEADER_SIZE + self.last_entry) as u32).to_le_bytes());
+        ziplist.extend_from_slice(&(self.count.min(u16::MAX as usize) as u16).to_le_bytes());
+        ziplist.extend_from_slice(&self.entries);
+        ziplist.push(0xff);
+
+        ziplist
+    }
+}
+
+/// Builds a listpack entry by entry
+#[derive(Default)]
+pub struct Listpack {
+    entries: Vec<u8>,
+    count: usize,
+}
+
+impl Listpack {
+    fn push_integer(&mut self, value: i64) {
+        let bytes = value.to_le_bytes();
+
+        if (0..=127).contains(&value) {
+            self.entries.push(value as u8);
+        } else if (-4096..=4095).contains(&value) {
+            let value = if value < 0 { (1 << 13) + value } else { value };
+            self.entries.push(0xc0 | (value >> 8) as u8);
+            self.entries.push(value as u8);
+        } else if (i16::MIN as i64..=i16::MAX as i64).contains(&value) {
+            self.entries.push(0xf1);
+            self.entries.extend_from_slice(&bytes[..2]);
+        } else if (-(1 << 23)..(1 << 23)).contains(&value) {
+            self.entries.push(0xf2);
+            self.entries.extend_from_slice(&bytes[..3]);
+        } else if (i32::MIN as i64..=i32::MAX as i64).contains(&value) {
+            self.entries.push(0xf3);
+            self.entries.extend_from_slice(&bytes[..4]);
+        } else {
+            self.entries.push(0xf4);
+            self.entries.extend_from_slice(&bytes);
+        }
+    }
+
+    /// Appends the length of the entry, which is stored so that it can be read backwards.
+    fn push_backlen(&mut self, length: usize) {
+        let bytes = if length <= 127 {
+            1
+        } else if length < 16383 {
+            2
+        } else if length < 2097151 {
+            3
+        } else if length < 268435455 {
+            4
+        } else {
+            5
+        };
+
+        for i in (0..bytes).rev() {
+            let byte = ((length >> (7 * i)) & 127) as u8;
+            if i == bytes - 1 {
+                self.entries.push(byte);
+            } else {
+                self.entries.push(byte | 128);
+            }
+        }
+    }
+}
+
+impl Packed for Listpack {
+    fn new(_version: u32) -> Listpack {
+        Listpack::default()
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+
+    fn size_with(&self, length: usize) -> usize {
+        LISTPACK_HEADER_SIZE + self.entries.len() + length + LISTPACK_MAX_ENTRY_OVERHEAD + 1
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        let start = self.entries.len();
+
+        let integer = if data.len() <= LISTPACK_MAX_INTEGER_LENGTH {
+            parse_integer(data)
+        } else {
+            None
+        };
+
+        match integer {
+            Some(value) => self.push_integer(value),
+            None => {
+                let length = data.len();
+                if length < 64 {
+                    self.entries.push(0x80 | length as u8);
+                } else if length < 4096 {
+                    self.entries.push(0xe0 | (length >> 8) as u8);
+                    self.entries.push(length as u8);
+                } else {
+                    self.entries.push(0xf0);
+                    self.entries
+                        .extend_from_slice(&(length as u32).to_le_bytes());
+                }
+                self.entries.extend_from_slice(data);
+            }
+        }
+
+        self.push_backlen(self.entries.len() - start);
+        self.count += 1;
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let size = LISTPACK_HEADER_SIZE + self.entries.len() + 1;
+        let mut listpack = Vec::with_capacity(size);
+        listpack.extend_from_slice(&(size as u32).to_le_bytes());
+        listpack.extend_from_slice(&(self.count.min(u16::MAX as usize) as u16).to_le_bytes());
+        listpack.extend_from_slice(&self.entries);
+        listpack.push(0xff);
+
+        listpack
+    }
+}
+
+fn push_zipmap_length(zipmap: &mut Vec<u8>, length: usize) {
+    if length < 253 {
+        zipmap.push(length as u8);
+    } else {
+        zipmap.push(253);
+        zipmap.extend_from_slice(&(length as u32).to_le_bytes());
+    }
+}
+
+/// Builds a zipmap, the encoding of small hashes before ziplists were used.
+/// Must not contain more than 253 pairs.
+pub fn zipmap(pairs: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let mut zipmap = vec![pairs.len() as u8];
+
+    for (field, value) in pairs {
+        push_zipmap_length(&mut zipmap, field.len());
+        zipmap.extend_from_slice(field);
+        push_zipmap_length(&mut zipmap, value.len());
+        // No free bytes after the value
+        zipmap.push(0);
+        zipmap.extend_from_slice(value);
+    }
+    zipmap.push(0xff);
+
+    zipmap
+}
@@ -1,16 +1,23 @@
 pub mod version {
     pub const SUPPORTED_MINIMUM: u32 = 1;
-    pub const SUPPORTED_MAXIMUM: u32 = 8;
+    pub const SUPPORTED_MAXIMUM: u32 = 11;
 }
 
 pub mod constant {
     pub const RDB_6BITLEN: u8 = 0;
     pub const RDB_14BITLEN: u8 = 1;
     pub const RDB_ENCVAL: u8 = 3;
-    pub const RDB_MAGIC: &'static str = "REDIS";
+    pub const RDB_32BITLEN: u8 = 0x80;
+    pub const RDB_64BITLEN: u8 = 0x81;
+    pub const RDB_MAGIC: &str = "REDIS";
 }
 
 pub mod op_code {
+    pub const FUNCTION2: u8 = 245;
+    pub const FUNCTION_PRE_GA: u8 = 246;
+    pub const MODULE_AUX: u8 = 247;
+    pub const IDLE: u8 = 248;
+    pub const FREQ: u8 = 249;
     pub const AUX: u8 = 250;
     pub const RESIZEDB: u8 = 251;
     pub const EXPIRETIME_MS: u8 = 252;
@@ -26,12 +33,35 @@ pub mod encoding_type {
     pub const ZSET: u8 = 3;
     pub const HASH: u8 = 4;
     pub const ZSET_2: u8 = 5;
+    pub const MODULE: u8 = 6;
+    pub const MODULE_2: u8 = 7;
     pub const HASH_ZIPMAP: u8 = 9;
     pub const LIST_ZIPLIST: u8 = 10;
     pub const SET_INTSET: u8 = 11;
     pub const ZSET_ZIPLIST: u8 = 12;
     pub const HASH_ZIPLIST: u8 = 13;
     pub const LIST_QUICKLIST: u8 = 14;
+    pub const STREAM_LISTPACKS: u8 = 15;
+    pub const HASH_LISTPACK: u8 = 16;
+    pub const ZSET_LISTPACK: u8 = 17;
+    pub const LIST_QUICKLIST_2: u8 = 18;
+    pub const STREAM_LISTPACKS_2: u8 = 19;
+    pub const SET_LISTPACK: u8 = 20;
+    pub const STREAM_LISTPACKS_3: u8 = 21;
+}
+
+pub mod quicklist_container {
+    pub const PLAIN: u32 = 1;
+    pub const PACKED: u32 = 2;
+}
+
+pub mod module_op_code {
+    pub const EOF: u64 = 0;
+    pub const SINT: u64 = 1;
+    pub const UINT: u64 = 2;
+    pub const FLOAT: u64 = 3;
+    pub const DOUBLE: u64 = 4;
+    pub const STRING: u64 = 5;
 }
 
 pub mod encoding {
@@ -0,0 +1,228 @@
+//! Copying the keys of a RDB file that match a filter into a new RDB file
+//!
+//! Unlike parsing with `formatter::Rdb`, the values are not decoded and re-encoded:
+//! the bytes of every kept key are copied unchanged, so the output keeps the version
+//! and the encodings of the input.
+//!
+//! ```rust,no_run
+//! # use std::io::{BufReader, BufWriter};
+//! # use std::fs::File;
+//! # use regex::bytes::Regex;
+//! let input = BufReader::new(File::open("dump.rdb").unwrap());
+//! let output = BufWriter::new(File::create("users.rdb").unwrap());
+//!
+//! let mut filter = rdb::filter::Simple::new();
+//! filter.add_keys(Regex::new("^user:").unwrap());
+//! rdb::copy(input, output, filter).unwrap();
+//! ```
+
+use byteorder::ReadBytesExt;
+use std::io::{Read, Write};
+
+use crate::builder::Limits;
+use crate::constants::op_code;
+use crate::filter::Filter;
+use crate::helper::{self, Capture};
+use crate::parser::{
+    read_blob, read_length, read_length_u64, read_version, skip_blob, skip_nested, skip_object,
+    verify_magic,
+};
+use crate::types::{RdbError, RdbOk, RdbResult};
+use crate::writer::RdbWriter;
+
+/// An entry of a RDB file, read without decoding the value
+pub(crate) enum RawEntry {
+    SelectDb(u32),
+    /// Data that doesn't belong to a key: aux fields, functions and module aux data.
+    /// `raw` contains everything after the opcode.
+    Global {
+        op_code: u8,
+        raw: Vec<u8>,
+    },
+    /// A key, whose value has to be copied or skipped next
+    Key {
+        value_type: u8,
+        key: Vec<u8>,
+        raw_key: Vec<u8>,
+        /// The expiry, idle time and frequency opcodes preceding the key
+        metadata: Vec<u8>,
+        has_expiry: bool,
+    },
+    Eof,
+}
+
+/// Reads a RDB file entry by entry, keeping the raw bytes around for copying
+pub(crate) struct RawReader<R: Read> {
+    input: R,
+    version: u32,
+}
+
+impl<R: Read> RawReader<R> {
+    pub fn new(mut input: R) -> RdbResult<RawReader<R>> {
+        verify_magic(&mut input)?;
+        let version = read_version(&mut input)?;
+
+        Ok(RawReader { input, version })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+
+    /// Reads the next entry. RESIZEDB hints are skipped.
+    pub fn next_entry(&mut self) -> RdbResult<RawEntry> {
+        let mut metadata = Vec::new();
+        let mut has_expiry = false;
+
+        loop {
+            let next_op = self.input.read_u8()?;
+
+            match next_op {
+                op_code::EOF => return Ok(RawEntry::Eof),
+                op_code::SELECTDB => return Ok(RawEntry::SelectDb(read_length(&mut self.input)?)),
+                op_code::RESIZEDB => {
+                    read_length(&mut self.input)?;
+                    read_length(&mut self.input)?;
+                }
+                op_code::EXPIRETIME_MS | op_code::EXPIRETIME | op_code::IDLE | op_code::FREQ => {
+                    let mut capture = Capture::new(&mut self.input);
+                    match next_op {
+                        op_code::EXPIRETIME_MS => helper::skip(&mut capture, 8)?,
+                        op_code::EXPIRETIME => helper::skip(&mut capture, 4)?,
+                        op_code::IDLE => {
+                            read_length_u64(&mut capture)?;
+                        }
+                        _ => helper::skip(&mut capture, 1)?,
+                    }
+
+                    has_expiry |=
+                        next_op == op_code::EXPIRETIME_MS || next_op == op_code::EXPIRETIME;
+                    metadata.push(next_op);
+                    metadata.extend_from_slice(&capture.data);
+                }
+                op_code::AUX | op_code::FUNCTION2 | op_code::MODULE_AUX => {
+                    let mut capture = Capture::new(&mut self.input);
+                    match next_op {
+                        op_code::AUX => {
+                            skip_blob(&mut capture)?;
+                            skip_blob(&mut capture)?;
+                        }
+                        op_code::FUNCTION2 => skip_blob(&mut capture)?,
+                        _ => skip_nested(&mut capture, next_op, &Limits::default())?,
+                    }
+
+                    return Ok(RawEntry::Global {
+                        op_code: next_op,
+                        raw: capture.data,
+                    });
+                }
+                op_code::FUNCTION_PRE_GA => {
+                    return Err(RdbError::Other(
+                        "Functions in the format of Redis 7.0 release candidates are not supported"
+                            .into(),
+                    ));
+                }
+                _ => {
+                    let mut capture = Capture::new(&mut self.input);
+                    skip_blob(&mut capture)?;
+                    let raw_key = capture.data;
+
+                    return Ok(RawEntry::Key {
+                        value_type: next_op,
+                        key: read_blob(&mut &raw_key[..])?,
+                        raw_key,
+                        metadata,
+                        has_expiry,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Returns the raw bytes of the value of the key just read.
+    pub fn read_value(&mut self, value_type: u8) -> RdbResult<Vec<u8>> {
+        let mut capture = Capture::new(&mut self.input);
+        skip_object(&mut capture, value_type)?;
+
+        Ok(capture.data)
+    }
+
+    /// Skips the value of the key just read.
+    pub fn skip_value(&mut self, value_type: u8) -> RdbOk {
+        skip_object(&mut self.input, value_type)
+    }
+
+    /// Copies the value of the key just read to `writer`, together with the key.
+    pub fn copy_value<W: Write>(&mut self, entry: &RawEntry, writer: &mut RdbWriter<W>) -> RdbOk {
+        if let RawEntry::Key {
+            value_type,
+            raw_key,
+            metadata,
+            ..
+        } = entry
+        {
+            let value = self.read_value(*value_type)?;
+
+            writer.write_raw(metadata)?;
+            writer.write_u8(*value_type)?;
+            writer.write_raw(raw_key)?;
+            writer.write_raw(&value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Copies the keys of `input` that match `filter` to `output`.
+///
+/// Aux fields, functions and module aux data are always copied.
+/// RESIZEDB hints are left out, as they would overstate the size of filtered databases.
+/// A new checksum is computed for the output.
+pub fn copy<R: Read, W: Write, L: Filter>(input: R, output: W, filter: L) -> RdbOk {
+    let mut reader = RawReader::new(input)?;
+    let mut writer = RdbWriter::new(output, reader.version())?;
+    writer.write_header()?;
+
+    let mut database = 0;
+
+    loop {
+        let entry = reader.next_entry()?;
+
+        match entry {
+            RawEntry::Eof => break,
+            RawEntry::SelectDb(index) => {
+                database = index;
+                if filter.matches_db(database) {
+                    writer.write_select_db(database)?;
+                }
+            }
+            RawEntry::Global {
+                op_code: op,
+                ref raw,
+            } => {
+                writer.write_u8(op)?;
+                writer.write_raw(raw)?;
+            }
+            RawEntry::Key {
+                value_type,
+                ref key,
+                ..
+            } => {
+                if filter.matches_db(database)
+                    && filter.matches_type(value_type)
+                    && filter.matches_key(key)
+                {
+                    reader.copy_value(&entry, &mut writer)?;
+                } else {
+                    reader.skip_value(value_type)?;
+                }
+            }
+        }
+    }
+
+    writer.write_eof()
+}
@@ -0,0 +1,33 @@
+//! CRC-16/XMODEM, as used by Redis Cluster to map keys to hash slots
+
+const POLY: u16 = 0x1021;
+
+const fn make_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ POLY
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static TABLE: [u16; 256] = make_table();
+
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        crc = (crc << 8) ^ TABLE[((crc >> 8) as u8 ^ byte) as usize];
+    }
+    crc
+}
@@ -43,7 +43,7 @@ impl Filter for Simple {
         if self.databases.is_empty() {
             true
         } else {
-            self.databases.iter().any(|&x| x == db)
+            self.databases.contains(&db)
         }
     }
 
@@ -52,8 +52,10 @@ impl Filter for Simple {
             return true;
         }
 
-        let typ = Type::from_encoding(enc_type);
-        self.types.iter().any(|x| *x == typ)
+        match Type::try_from_encoding(enc_type) {
+            Ok(typ) => self.types.contains(&typ),
+            Err(_) => false,
+        }
     }
 
     fn matches_key(&self, key: &[u8]) -> bool {
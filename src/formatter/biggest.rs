@@ -0,0 +1,589 @@
+use super::typed_json::{encoding_name, escape, json_string, type_name, Binary};
+use super::write_str;
+use crate::formatter::{Control, Formatter};
+use crate::memory::{Allocator, Collection, Model};
+use crate::types::{AuxInfo, EncodingType, RdbResult, Type};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap};
+use std::io;
+use std::io::{BufWriter, Write};
+use std::rc::Rc;
+
+/// How the report is written
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Report {
+    /// A table per database, type and metric
+    #[default]
+    Table,
+    /// An array with an object per database, type and metric
+    Json,
+}
+
+/// Reports the biggest keys of every database and type, once the whole file is read
+///
+/// Keys are ranked by three metrics: their serialized size in the RDB file, without
+/// compression except for the nodes of quicklists, their estimated memory in Redis,
+/// as by `Memory`, and their number of elements. Every key comes with its largest
+/// element, the first 64 bytes of a list element, a member or a field, and its size,
+/// including the value of a hash field.
+///
+/// ```text
+/// Database 0, hash, by memory
+/// key     encoding  serialized  memory  elements  largest_size  largest_element
+/// user:1  listpack          61     112         2             9  email
+/// ```
+///
+/// Only the top N keys of each ranking are kept while reading, so the memory used
+/// doesn't grow with the size of the file.
+pub struct Biggest<W: Write = io::Stdout> {
+    out: BufWriter<W>,
+    report: Report,
+    top: usize,
+    model: Model,
+    /// Whether the model follows the `redis-ver` aux field
+    detect_version: bool,
+    db: u32,
+    keys: u64,
+    current: Option<(Stats, Collection)>,
+    /// The quicklist nodes of the current key
+    nodes: u64,
+    rankings: BTreeMap<(u32, usize), Rankings>,
+}
+
+/// Types in the order they are reported
+const TYPES: [Type; 7] = [
+    Type::String,
+    Type::List,
+    Type::Set,
+    Type::SortedSet,
+    Type::Hash,
+    Type::Stream,
+    Type::Module,
+];
+
+const METRICS: [&str; 3] = ["serialized", "memory", "elements"];
+
+/// Bytes of the largest element kept
+const ELEMENT_PREFIX: usize = 64;
+
+/// What the report shows of a key
+struct Stats {
+    key: Vec<u8>,
+    typ: Type,
+    encoding: EncodingType,
+    serialized: u64,
+    memory: u64,
+    elements: u64,
+    /// The start of the largest element and the size of the whole element
+    largest: Option<(Vec<u8>, u64)>,
+}
+
+impl Stats {
+    fn metric(&self, metric: usize) -> u64 {
+        match metric {
+            0 => self.serialized,
+            1 => self.memory,
+            _ => self.elements,
+        }
+    }
+}
+
+/// A key in a ranking, the earlier of two keys of the same size ranks higher
+struct Ranked {
+    value: u64,
+    index: u64,
+    stats: Rc<Stats>,
+}
+
+impl Ranked {
+    fn rank(&self) -> (u64, Reverse<u64>) {
+        (self.value, Reverse(self.index))
+    }
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Ranked) -> bool {
+        self.rank() == other.rank()
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Ranked) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Ranked) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+/// The top keys of a database and type by each metric, the smallest on top of the heap
+#[derive(Default)]
+struct Rankings([BinaryHeap<Reverse<Ranked>>; 3]);
+
+impl Biggest {
+    /// Writes to stdout.
+    pub fn new() -> Biggest {
+        Biggest::with_writer(io::stdout())
+    }
+}
+
+impl Default for Biggest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> Biggest<W> {
+    /// Writes to `out`, buffered.
+    pub fn with_writer(out: W) -> Biggest<W> {
+        Biggest {
+            out: BufWriter::new(out),
+            report: Report::default(),
+            top: 10,
+            model: Model::new(),
+            detect_version: true,
+            db: 0,
+            keys: 0,
+            current: None,
+            nodes: 0,
+            rankings: BTreeMap::new(),
+        }
+    }
+
+    pub fn set_report(&mut self, report: Report) {
+        self.report = report;
+    }
+
+    /// Sets the number of keys in each ranking. Defaults to 10.
+    pub fn set_top(&mut self, top: usize) {
+        self.top = top.max(1);
+    }
+
+    /// Sets the model of the memory estimates, instead of the one of the Redis version
+    /// that wrote the file.
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+        self.detect_version = false;
+    }
+
+    /// Sets the allocator, keeping the Redis version of the model.
+    pub fn set_allocator(&mut self, allocator: Allocator) {
+        self.model.set_allocator(allocator);
+    }
+
+    /// Flushes the buffer and returns the writer.
+    pub fn into_inner(self) -> RdbResult<W> {
+        Ok(self.out.into_inner().map_err(|e| e.into_error())?)
+    }
+
+    fn start(
+        &mut self,
+        key: &[u8],
+        typ: Type,
+        length: u32,
+        expiry: Option<u64>,
+        encoding: EncodingType,
+    ) -> RdbResult<Control> {
+        let value = match encoding {
+            EncodingType::Ziplist(size)
+            | EncodingType::Listpack(size)
+            | EncodingType::Intset(size)
+            | EncodingType::Zipmap(size) => blob_size(size),
+            // Added by the nodes and once the number of nodes is known
+            EncodingType::Quicklist => 0,
+            _ => length_size(length as u64),
+        };
+        let stats = Stats {
+            key: key.to_vec(),
+            typ,
+            encoding,
+            serialized: header_size(key, expiry) + value,
+            memory: self.model.key(key, expiry.is_some()),
+            elements: 0,
+            largest: None,
+        };
+        self.current = Some((stats, self.model.collection(typ, encoding)));
+        self.nodes = 0;
+
+        Ok(Control::Continue)
+    }
+
+    /// Adds an element made of `parts` and, outside of compact encodings and
+    /// quicklists, stored in `serialized` bytes.
+    fn element(&mut self, parts: &[&[u8]], serialized: u64) -> RdbResult<Control> {
+        if let Some((ref mut stats, ref mut collection)) = self.current {
+            stats.elements += 1;
+            if !is_compact(stats.encoding) && stats.encoding != EncodingType::Quicklist {
+                stats.serialized += serialized;
+            }
+            let size = parts.iter().map(|part| part.len() as u64).sum();
+            if stats
+                .largest
+                .as_ref()
+                .is_none_or(|largest| size > largest.1)
+            {
+                let prefix = &parts[0][..parts[0].len().min(ELEMENT_PREFIX)];
+                stats.largest = Some((prefix.to_vec(), size));
+            }
+            collection.add(parts);
+        }
+
+        Ok(Control::Continue)
+    }
+
+    fn end(&mut self) {
+        if let Some((mut stats, collection)) = self.current.take() {
+            if stats.encoding == EncodingType::Quicklist {
+                stats.serialized += length_size(self.nodes);
+            }
+            stats.memory += collection.size();
+            self.rank(stats);
+        }
+    }
+
+    /// Adds the key to the rankings of its database and type, dropping the smallest
+    /// keys beyond the top N.
+    fn rank(&mut self, stats: Stats) {
+        let typ = TYPES.iter().position(|typ| *typ == stats.typ).unwrap_or(0);
+        let rankings = self.rankings.entry((self.db, typ)).or_default();
+        let stats = Rc::new(stats);
+        for (metric, heap) in rankings.0.iter_mut().enumerate() {
+            heap.push(Reverse(Ranked {
+                value: stats.metric(metric),
+                index: self.keys,
+                stats: stats.clone(),
+            }));
+            if heap.len() > self.top {
+                heap.pop();
+            }
+        }
+        self.keys += 1;
+    }
+
+    fn write_table(
+        &mut self,
+        db: u32,
+        typ: Type,
+        metric: &str,
+        keys: &[Rc<Stats>],
+    ) -> RdbResult<()> {
+        let mut rows = vec![[
+            "key",
+            "encoding",
+            "serialized",
+            "memory",
+            "elements",
+            "largest_size",
+            "largest_element",
+        ]
+        .map(String::from)];
+        for stats in keys {
+            let (element, size) = match stats.largest {
+                Some((ref element, size)) => (escape(element), size.to_string()),
+                None => (String::new(), String::new()),
+            };
+            rows.push([
+                escape(&stats.key),
+                encoding(stats).to_string(),
+                stats.serialized.to_string(),
+                stats.memory.to_string(),
+                stats.elements.to_string(),
+                size,
+                element,
+            ]);
+        }
+
+        let mut widths = [0; 7];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let mut table = format!("Database {}, {}, by {}\n", db, type_name(typ), metric);
+        for row in &rows {
+            let mut line = String::new();
+            for (column, cell) in row.iter().enumerate() {
+                let width = widths[column];
+                let cell = match column {
+                    // Text left and numbers right aligned
+                    0 | 1 => format!("{:<width$}  ", cell),
+                    6 => cell.to_string(),
+                    _ => format!("{:>width$}  ", cell),
+                };
+                line.push_str(&cell);
+            }
+            table.push_str(line.trim_end());
+            table.push('\n');
+        }
+        table.push('\n');
+
+        write_str(&mut self.out, &table)
+    }
+
+    fn write_json(
+        &mut self,
+        first: bool,
+        db: u32,
+        typ: Type,
+        metric: &str,
+        keys: &[Rc<Stats>],
+    ) -> RdbResult<()> {
+        let keys: Vec<String> = keys
+            .iter()
+            .map(|stats| {
+                let (element, size) = match stats.largest {
+                    Some((ref element, size)) => {
+                        (json_string(element, Binary::Escape), size.to_string())
+                    }
+                    None => ("null".into(), "null".into()),
+                };
+                format!(
+                    "{{\"key\":{},\"encoding\":\"{}\",\"serialized\":{},\"memory\":{},\"elements\":{},\"largest_element\":{},\"largest_size\":{}}}",
+                    json_string(&stats.key, Binary::Escape),
+                    encoding(stats),
+                    stats.serialized,
+                    stats.memory,
+                    stats.elements,
+                    element,
+                    size
+                )
+            })
+            .collect();
+        let ranking = format!(
+            "{}\n{{\"db\":{},\"type\":\"{}\",\"by\":\"{}\",\"keys\":[{}]}}",
+            if first { "" } else { "," },
+            db,
+            type_name(typ),
+            metric,
+            keys.join(",")
+        );
+
+        write_str(&mut self.out, &ranking)
+    }
+}
+
+fn encoding(stats: &Stats) -> &'static str {
+    match stats.typ {
+        Type::Stream | Type::Module => "raw",
+        typ => encoding_name(typ, stats.encoding),
+    }
+}
+
+fn is_compact(encoding: EncodingType) -> bool {
+    matches!(
+        encoding,
+        EncodingType::Ziplist(_)
+            | EncodingType::Listpack(_)
+            | EncodingType::Intset(_)
+            | EncodingType::Zipmap(_)
+    )
+}
+
+/// A length as encoded in a RDB file
+fn length_size(length: u64) -> u64 {
+    match length {
+        0..=63 => 1,
+        64..=16383 => 2,
+        16384..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// A string with its length, without compression
+fn blob_size(length: u64) -> u64 {
+    length_size(length) + length
+}
+
+/// The type, the expiry and the key
+fn header_size(key: &[u8], expiry: Option<u64>) -> u64 {
+    let expiry = if expiry.is_some() { 9 } else { 0 };
+    1 + expiry + blob_size(key.len() as u64)
+}
+
+impl<W: Write> Formatter for Biggest<W> {
+    fn end_rdb(&mut self) -> RdbResult<()> {
+        if self.report == Report::Json {
+            write_str(&mut self.out, "[")?;
+        }
+
+        let rankings = std::mem::take(&mut self.rankings);
+        let mut first = true;
+        for ((db, typ), rankings) in rankings {
+            for (metric, heap) in IntoIterator::into_iter(rankings.0).enumerate() {
+                // Largest first
+                let keys: Vec<Rc<Stats>> = heap
+                    .into_sorted_vec()
+                    .into_iter()
+                    .map(|Reverse(ranked)| ranked.stats)
+                    .collect();
+                match self.report {
+                    Report::Table => self.write_table(db, TYPES[typ], METRICS[metric], &keys)?,
+                    Report::Json => {
+                        self.write_json(first, db, TYPES[typ], METRICS[metric], &keys)?
+                    }
+                }
+                first = false;
+            }
+        }
+
+        if self.report == Report::Json {
+            write_str(&mut self.out, if first { "]\n" } else { "\n]\n" })?;
+        }
+        self.out.flush()?;
+
+        Ok(())
+    }
+
+    fn aux_info(&mut self, info: &AuxInfo) -> RdbResult<()> {
+        if let (true, Some(version)) = (self.detect_version, &info.redis_ver) {
+            // Files of unknown versions keep the default model
+            let _ = self.model.set_redis_version(version);
+        }
+
+        Ok(())
+    }
+
+    fn start_database(&mut self, db_index: u32) -> RdbResult<()> {
+        self.db = db_index;
+
+        Ok(())
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<Control> {
+        let length = value.len() as u64;
+        self.rank(Stats {
+            key: key.to_vec(),
+            typ: Type::String,
+            encoding: EncodingType::String,
+            serialized: header_size(key, expiry) + blob_size(length),
+            memory: self.model.key(key, expiry.is_some()) + self.model.string(value),
+            elements: length,
+            largest: None,
+        });
+
+        Ok(Control::Continue)
+    }
+
+    fn raw_object(
+        &mut self,
+        key: &[u8],
+        value_type: u8,
+        raw: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        let length = raw.len() as u64;
+        self.rank(Stats {
+            key: key.to_vec(),
+            typ: Type::from_encoding(value_type),
+            encoding: EncodingType::String,
+            serialized: header_size(key, expiry) + length,
+            memory: self.model.key(key, expiry.is_some()) + self.model.allocation(length),
+            elements: 0,
+            largest: None,
+        });
+
+        Ok(Control::Continue)
+    }
+
+    fn start_hash(
+        &mut self,
+        key: &[u8],
+        length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Type::Hash, length, expiry, info)
+    }
+
+    fn end_hash(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end();
+
+        Ok(())
+    }
+
+    fn hash_element(&mut self, _key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
+        let serialized = blob_size(field.len() as u64) + blob_size(value.len() as u64);
+        self.element(&[field, value], serialized)
+    }
+
+    fn start_set(
+        &mut self,
+        key: &[u8],
+        cardinality: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Type::Set, cardinality, expiry, info)
+    }
+
+    fn end_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end();
+
+        Ok(())
+    }
+
+    fn set_element(&mut self, _key: &[u8], member: &[u8]) -> RdbResult<Control> {
+        self.element(&[member], blob_size(member.len() as u64))
+    }
+
+    fn start_list(
+        &mut self,
+        key: &[u8],
+        length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Type::List, length, expiry, info)
+    }
+
+    fn end_list(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end();
+
+        Ok(())
+    }
+
+    fn list_node(&mut self, _key: &[u8], size: u64) -> RdbResult<()> {
+        if let Some((ref mut stats, _)) = self.current {
+            stats.serialized += size;
+            self.nodes += 1;
+        }
+
+        Ok(())
+    }
+
+    fn list_element(&mut self, _key: &[u8], value: &[u8]) -> RdbResult<Control> {
+        self.element(&[value], blob_size(value.len() as u64))
+    }
+
+    fn start_sorted_set(
+        &mut self,
+        key: &[u8],
+        length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Type::SortedSet, length, expiry, info)
+    }
+
+    fn end_sorted_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end();
+
+        Ok(())
+    }
+
+    fn sorted_set_element(
+        &mut self,
+        _key: &[u8],
+        _score: f64,
+        member: &[u8],
+    ) -> RdbResult<Control> {
+        // With a binary score
+        self.element(&[member], blob_size(member.len() as u64) + 8)
+    }
+}
@@ -1,11 +1,12 @@
 use super::write_str;
-use crate::formatter::Formatter;
+use crate::formatter::{Control, Formatter};
 use crate::types::{EncodingType, RdbResult};
 use std::io;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 
-pub struct JSON {
-    out: Box<dyn Write + 'static>,
+/// Writes one JSON object per database, mapping keys to their values
+pub struct JSON<W: Write = io::Stdout> {
+    out: BufWriter<W>,
     is_first_db: bool,
     has_databases: bool,
     is_first_key_in_db: bool,
@@ -14,10 +15,17 @@ pub struct JSON {
 }
 
 impl JSON {
+    /// Writes to stdout.
     pub fn new() -> JSON {
-        let out = Box::new(io::stdout());
+        JSON::with_writer(io::stdout())
+    }
+}
+
+impl<W: Write> JSON<W> {
+    /// Writes to `out`, buffered.
+    pub fn with_writer(out: W) -> JSON<W> {
         JSON {
-            out,
+            out: BufWriter::new(out),
             is_first_db: true,
             has_databases: false,
             is_first_key_in_db: true,
@@ -25,6 +33,17 @@ impl JSON {
             element_index: 0,
         }
     }
+
+    /// Flushes the buffer and returns the writer.
+    pub fn into_inner(self) -> RdbResult<W> {
+        Ok(self.out.into_inner().map_err(|e| e.into_error())?)
+    }
+}
+
+impl Default for JSON {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 fn encode_to_ascii(value: &[u8]) -> String {
@@ -32,7 +51,7 @@ fn encode_to_ascii(value: &[u8]) -> String {
     serde_json::to_string(&s).unwrap()
 }
 
-impl JSON {
+impl<W: Write> JSON<W> {
     fn start_key(&mut self, length: u32) -> RdbResult<()> {
         if !self.is_first_key_in_db {
             write_str(&mut self.out, ",")?;
@@ -69,7 +88,7 @@ impl JSON {
     }
 }
 
-impl Formatter for JSON {
+impl<W: Write> Formatter for JSON<W> {
     fn start_rdb(&mut self) -> RdbResult<()> {
         write_str(&mut self.out, "[")
     }
@@ -79,6 +98,7 @@ impl Formatter for JSON {
             write_str(&mut self.out, "}")?;
         }
         write_str(&mut self.out, "]\n")?;
+        self.out.flush()?;
 
         Ok(())
     }
@@ -96,13 +116,13 @@ impl Formatter for JSON {
         Ok(())
     }
 
-    fn set(&mut self, key: &[u8], value: &[u8], _expiry: Option<u64>) -> RdbResult<()> {
+    fn set(&mut self, key: &[u8], value: &[u8], _expiry: Option<u64>) -> RdbResult<Control> {
         self.start_key(0)?;
         self.write_key(key)?;
         write_str(&mut self.out, ":")?;
         self.write_value(value)?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn start_hash(
@@ -111,31 +131,28 @@ impl Formatter for JSON {
         length: u32,
         _expiry: Option<u64>,
         _info: EncodingType,
-    ) -> RdbResult<()> {
+    ) -> RdbResult<Control> {
         self.start_key(length)?;
         self.write_key(key)?;
         write_str(&mut self.out, ":{")?;
-        self.out.flush()?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn end_hash(&mut self, _key: &[u8]) -> RdbResult<()> {
         self.end_key();
         write_str(&mut self.out, "}")?;
-        self.out.flush()?;
 
         Ok(())
     }
 
-    fn hash_element(&mut self, _key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<()> {
+    fn hash_element(&mut self, _key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
         self.write_comma()?;
         self.write_key(field)?;
         write_str(&mut self.out, ":")?;
         self.write_value(value)?;
-        self.out.flush()?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn start_set(
@@ -144,13 +161,12 @@ impl Formatter for JSON {
         cardinality: u32,
         _expiry: Option<u64>,
         _info: EncodingType,
-    ) -> RdbResult<()> {
+    ) -> RdbResult<Control> {
         self.start_key(cardinality)?;
         self.write_key(key)?;
         write_str(&mut self.out, ":[")?;
-        self.out.flush()?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn end_set(&mut self, _key: &[u8]) -> RdbResult<()> {
@@ -160,11 +176,11 @@ impl Formatter for JSON {
         Ok(())
     }
 
-    fn set_element(&mut self, _key: &[u8], member: &[u8]) -> RdbResult<()> {
+    fn set_element(&mut self, _key: &[u8], member: &[u8]) -> RdbResult<Control> {
         self.write_comma()?;
         self.write_value(member)?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn start_list(
@@ -173,12 +189,12 @@ impl Formatter for JSON {
         length: u32,
         _expiry: Option<u64>,
         _info: EncodingType,
-    ) -> RdbResult<()> {
+    ) -> RdbResult<Control> {
         self.start_key(length)?;
         self.write_key(key)?;
         write_str(&mut self.out, ":[")?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn end_list(&mut self, _key: &[u8]) -> RdbResult<()> {
@@ -188,11 +204,11 @@ impl Formatter for JSON {
         Ok(())
     }
 
-    fn list_element(&mut self, _key: &[u8], value: &[u8]) -> RdbResult<()> {
+    fn list_element(&mut self, _key: &[u8], value: &[u8]) -> RdbResult<Control> {
         self.write_comma()?;
         self.write_value(value)?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn start_sorted_set(
@@ -201,12 +217,12 @@ impl Formatter for JSON {
         length: u32,
         _expiry: Option<u64>,
         _info: EncodingType,
-    ) -> RdbResult<()> {
+    ) -> RdbResult<Control> {
         self.start_key(length)?;
         self.write_key(key)?;
         write_str(&mut self.out, ":{")?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn end_sorted_set(&mut self, _key: &[u8]) -> RdbResult<()> {
@@ -216,12 +232,12 @@ impl Formatter for JSON {
         Ok(())
     }
 
-    fn sorted_set_element(&mut self, _key: &[u8], score: f64, member: &[u8]) -> RdbResult<()> {
+    fn sorted_set_element(&mut self, _key: &[u8], score: f64, member: &[u8]) -> RdbResult<Control> {
         self.write_comma()?;
         self.write_key(member)?;
         write_str(&mut self.out, ":")?;
         self.write_value(score.to_string().as_bytes())?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 }
@@ -0,0 +1,299 @@
+use super::typed_json::{encoding_name, escape, type_name};
+use super::write_str;
+use crate::formatter::{Control, Formatter};
+use crate::memory::{Allocator, Collection, Model};
+use crate::types::{AuxInfo, EncodingType, RdbResult, Type};
+use std::io;
+use std::io::{BufWriter, Write};
+
+/// Writes a CSV row per key with an estimate of the memory it takes in Redis
+///
+/// ```text
+/// database,type,key,size_in_bytes,encoding,num_elements,len_largest_element,expiry
+/// 0,hash,user:1,120,listpack,2,4,
+/// 0,string,name,72,string,5,5,1700000000000
+/// ```
+///
+/// The estimate covers the key, its value and, for keys with an expiry, the entry in
+/// the expires dictionary, following the `Model` of the Redis version that wrote the
+/// file. For strings, `num_elements` and `len_largest_element` are the length of the
+/// value. Expiries are Unix times in milliseconds.
+pub struct Memory<W: Write = io::Stdout> {
+    out: BufWriter<W>,
+    model: Model,
+    /// Whether the model follows the `redis-ver` aux field
+    detect_version: bool,
+    db: u32,
+    current: Option<(Key, Collection)>,
+}
+
+/// A key and what the row shows of it
+struct Key {
+    key: Vec<u8>,
+    typ: Type,
+    encoding: EncodingType,
+    expiry: Option<u64>,
+    elements: u64,
+    largest: u64,
+}
+
+impl Memory {
+    /// Writes to stdout.
+    pub fn new() -> Memory {
+        Memory::with_writer(io::stdout())
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> Memory<W> {
+    /// Writes to `out`, buffered.
+    pub fn with_writer(out: W) -> Memory<W> {
+        Memory {
+            out: BufWriter::new(out),
+            model: Model::new(),
+            detect_version: true,
+            db: 0,
+            current: None,
+        }
+    }
+
+    /// Sets the model of the estimates, instead of the one of the Redis version that
+    /// wrote the file.
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+        self.detect_version = false;
+    }
+
+    /// Sets the allocator, keeping the Redis version of the model.
+    pub fn set_allocator(&mut self, allocator: Allocator) {
+        self.model.set_allocator(allocator);
+    }
+
+    /// Flushes the buffer and returns the writer.
+    pub fn into_inner(self) -> RdbResult<W> {
+        Ok(self.out.into_inner().map_err(|e| e.into_error())?)
+    }
+
+    fn start(
+        &mut self,
+        key: &[u8],
+        typ: Type,
+        encoding: EncodingType,
+        expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        let row = Key {
+            key: key.to_vec(),
+            typ,
+            encoding,
+            expiry,
+            elements: 0,
+            largest: 0,
+        };
+        self.current = Some((row, self.model.collection(typ, encoding)));
+
+        Ok(Control::Continue)
+    }
+
+    /// Adds an element made of `parts`, e.g. a field and its value.
+    fn element(&mut self, parts: &[&[u8]]) -> RdbResult<Control> {
+        if let Some((ref mut row, ref mut collection)) = self.current {
+            row.elements += 1;
+            for part in parts {
+                row.largest = row.largest.max(part.len() as u64);
+            }
+            collection.add(parts);
+        }
+
+        Ok(Control::Continue)
+    }
+
+    fn end(&mut self) -> RdbResult<()> {
+        if let Some((row, collection)) = self.current.take() {
+            let size = self.model.key(&row.key, row.expiry.is_some()) + collection.size();
+            self.write_row(&row, size)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_row(&mut self, key: &Key, size: u64) -> RdbResult<()> {
+        let encoding = match key.typ {
+            Type::Stream | Type::Module => "raw",
+            typ => encoding_name(typ, key.encoding),
+        };
+        let row = format!(
+            "{},{},{},{},{},{},{},{}\n",
+            self.db,
+            type_name(key.typ),
+            csv_field(&key.key),
+            size,
+            encoding,
+            key.elements,
+            key.largest,
+            key.expiry
+                .map(|expiry| expiry.to_string())
+                .unwrap_or_default()
+        );
+        write_str(&mut self.out, &row)
+    }
+}
+
+/// Escapes bytes that are not UTF-8 and quotes the field if needed
+fn csv_field(value: &[u8]) -> String {
+    let field = escape(value);
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+impl<W: Write> Formatter for Memory<W> {
+    fn start_rdb(&mut self) -> RdbResult<()> {
+        write_str(
+            &mut self.out,
+            "database,type,key,size_in_bytes,encoding,num_elements,len_largest_element,expiry\n",
+        )
+    }
+
+    fn end_rdb(&mut self) -> RdbResult<()> {
+        self.out.flush()?;
+
+        Ok(())
+    }
+
+    fn aux_info(&mut self, info: &AuxInfo) -> RdbResult<()> {
+        if let (true, Some(version)) = (self.detect_version, &info.redis_ver) {
+            // Files of unknown versions keep the default model
+            let _ = self.model.set_redis_version(version);
+        }
+
+        Ok(())
+    }
+
+    fn start_database(&mut self, db_index: u32) -> RdbResult<()> {
+        self.db = db_index;
+
+        Ok(())
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<Control> {
+        let length = value.len() as u64;
+        let row = Key {
+            key: key.to_vec(),
+            typ: Type::String,
+            encoding: EncodingType::String,
+            expiry,
+            elements: length,
+            largest: length,
+        };
+        let size = self.model.key(key, expiry.is_some()) + self.model.string(value);
+        self.write_row(&row, size)?;
+
+        Ok(Control::Continue)
+    }
+
+    fn raw_object(
+        &mut self,
+        key: &[u8],
+        value_type: u8,
+        raw: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        // Only the serialized size is known
+        let row = Key {
+            key: key.to_vec(),
+            typ: Type::from_encoding(value_type),
+            encoding: EncodingType::String,
+            expiry,
+            elements: 0,
+            largest: 0,
+        };
+        let size = self.model.key(key, expiry.is_some()) + self.model.allocation(raw.len() as u64);
+        self.write_row(&row, size)?;
+
+        Ok(Control::Continue)
+    }
+
+    fn start_hash(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Type::Hash, info, expiry)
+    }
+
+    fn end_hash(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end()
+    }
+
+    fn hash_element(&mut self, _key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
+        self.element(&[field, value])
+    }
+
+    fn start_set(
+        &mut self,
+        key: &[u8],
+        _cardinality: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Type::Set, info, expiry)
+    }
+
+    fn end_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end()
+    }
+
+    fn set_element(&mut self, _key: &[u8], member: &[u8]) -> RdbResult<Control> {
+        self.element(&[member])
+    }
+
+    fn start_list(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Type::List, info, expiry)
+    }
+
+    fn end_list(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end()
+    }
+
+    fn list_element(&mut self, _key: &[u8], value: &[u8]) -> RdbResult<Control> {
+        self.element(&[value])
+    }
+
+    fn start_sorted_set(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Type::SortedSet, info, expiry)
+    }
+
+    fn end_sorted_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end()
+    }
+
+    fn sorted_set_element(
+        &mut self,
+        _key: &[u8],
+        _score: f64,
+        member: &[u8],
+    ) -> RdbResult<Control> {
+        self.element(&[member])
+    }
+}
@@ -1,19 +1,41 @@
 use std::io::Write;
 
+pub use self::biggest::Biggest;
 pub use self::json::JSON;
+pub use self::memory::Memory;
+pub use self::ndjson::NDJSON;
 pub use self::nil::Nil;
 pub use self::plain::Plain;
 pub use self::protocol::Protocol;
+pub use self::rdb::Rdb;
+pub use self::typed_json::TypedJSON;
 
-use super::types::{EncodingType, RdbResult};
+use super::types::{AuxInfo, EncodingType, RdbResult};
 
+pub mod biggest;
 pub mod json;
+pub mod memory;
+pub mod ndjson;
 pub mod nil;
 pub mod plain;
 pub mod protocol;
+pub mod rdb;
+pub mod typed_json;
+
+/// Tells the parser how to proceed after a callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    /// Keep on parsing
+    Continue,
+    /// Skip the remaining elements of the current key.
+    /// The matching `end_*` callback is still called.
+    SkipKey,
+    /// Stop parsing right away, no further callbacks are called
+    Stop,
+}
 
 pub fn write_str<W: Write>(out: &mut W, data: &str) -> RdbResult<()> {
-    out.write(data.as_bytes())?;
+    out.write_all(data.as_bytes())?;
 
     Ok(())
 }
@@ -43,25 +65,58 @@ pub trait Formatter {
     fn aux_field(&mut self, key: &[u8], value: &[u8]) -> RdbResult<()> {
         Ok(())
     }
+    /// Called once all aux fields at the start of the file are read,
+    /// before the first database
+    fn aux_info(&mut self, info: &AuxInfo) -> RdbResult<()> {
+        Ok(())
+    }
+
+    /// A library of Redis functions, with its code
+    fn function(&mut self, code: &[u8]) -> RdbResult<()> {
+        Ok(())
+    }
+    /// Data of a module that is not tied to a key, as stored in the file
+    fn module_aux(&mut self, raw: &[u8]) -> RdbResult<()> {
+        Ok(())
+    }
 
-    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<()> {
+    /// Called before the callbacks of a key that has LRU or LFU information:
+    /// the idle time in seconds or the logarithmic access frequency.
+    /// Both are only hints for eviction.
+    fn eviction_info(&mut self, key: &[u8], idle: Option<u64>, freq: Option<u8>) -> RdbResult<()> {
         Ok(())
     }
 
+    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<Control> {
+        Ok(Control::Continue)
+    }
+
+    /// A value that is not decoded, namely streams and module values.
+    /// `raw` holds the value as stored in the file after the key.
+    fn raw_object(
+        &mut self,
+        key: &[u8],
+        value_type: u8,
+        raw: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        Ok(Control::Continue)
+    }
+
     fn start_hash(
         &mut self,
         key: &[u8],
         length: u32,
         expiry: Option<u64>,
         info: EncodingType,
-    ) -> RdbResult<()> {
-        Ok(())
+    ) -> RdbResult<Control> {
+        Ok(Control::Continue)
     }
     fn end_hash(&mut self, key: &[u8]) -> RdbResult<()> {
         Ok(())
     }
-    fn hash_element(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<()> {
-        Ok(())
+    fn hash_element(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
+        Ok(Control::Continue)
     }
 
     fn start_set(
@@ -70,14 +125,14 @@ pub trait Formatter {
         cardinality: u32,
         expiry: Option<u64>,
         info: EncodingType,
-    ) -> RdbResult<()> {
-        Ok(())
+    ) -> RdbResult<Control> {
+        Ok(Control::Continue)
     }
     fn end_set(&mut self, key: &[u8]) -> RdbResult<()> {
         Ok(())
     }
-    fn set_element(&mut self, key: &[u8], member: &[u8]) -> RdbResult<()> {
-        Ok(())
+    fn set_element(&mut self, key: &[u8], member: &[u8]) -> RdbResult<Control> {
+        Ok(Control::Continue)
     }
 
     fn start_list(
@@ -86,15 +141,20 @@ pub trait Formatter {
         length: u32,
         expiry: Option<u64>,
         info: EncodingType,
-    ) -> RdbResult<()> {
-        Ok(())
+    ) -> RdbResult<Control> {
+        Ok(Control::Continue)
     }
     fn end_list(&mut self, key: &[u8]) -> RdbResult<()> {
         Ok(())
     }
-    fn list_element(&mut self, key: &[u8], value: &[u8]) -> RdbResult<()> {
+    /// Called before the elements of every node of a quicklist with the bytes
+    /// the node takes in the file, as compressed there.
+    fn list_node(&mut self, key: &[u8], size: u64) -> RdbResult<()> {
         Ok(())
     }
+    fn list_element(&mut self, key: &[u8], value: &[u8]) -> RdbResult<Control> {
+        Ok(Control::Continue)
+    }
 
     fn start_sorted_set(
         &mut self,
@@ -102,13 +162,13 @@ pub trait Formatter {
         length: u32,
         expiry: Option<u64>,
         info: EncodingType,
-    ) -> RdbResult<()> {
-        Ok(())
+    ) -> RdbResult<Control> {
+        Ok(Control::Continue)
     }
     fn end_sorted_set(&mut self, key: &[u8]) -> RdbResult<()> {
         Ok(())
     }
-    fn sorted_set_element(&mut self, key: &[u8], score: f64, member: &[u8]) -> RdbResult<()> {
-        Ok(())
+    fn sorted_set_element(&mut self, key: &[u8], score: f64, member: &[u8]) -> RdbResult<Control> {
+        Ok(Control::Continue)
     }
 }
@@ -0,0 +1,257 @@
+use super::typed_json::{json_score, json_string, type_name, Binary};
+use super::write_str;
+use crate::formatter::{Control, Formatter};
+use crate::types::{EncodingType, RdbResult, Type};
+use std::io;
+use std::io::{BufWriter, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Writes one JSON object per line and key, also known as JSON Lines
+///
+/// ```json
+/// {"db":0,"key":"name","type":"string","ttl":null,"value":"value"}
+/// {"db":0,"key":"scores","type":"sortedset","ttl":86400000,"value":{"jane":1.5}}
+/// ```
+///
+/// Values look like the ones of `TypedJSON`. `ttl` is the number of milliseconds
+/// left, negative for keys that already expired.
+///
+/// Collections can be split into lines of a maximum number of elements, each with
+/// the same `db`, `key`, `type` and `ttl` and a part of the value.
+pub struct NDJSON<W: Write = io::Stdout> {
+    out: BufWriter<W>,
+    binary: Binary,
+    now: u64,
+    elements_per_line: Option<u32>,
+    db: u32,
+    /// Start of every line of the current key, up to its value
+    line_start: String,
+    /// Brackets around the elements of the current key
+    brackets: (&'static str, &'static str),
+    elements_in_line: u32,
+}
+
+impl NDJSON {
+    /// Writes to stdout.
+    pub fn new() -> NDJSON {
+        NDJSON::with_writer(io::stdout())
+    }
+}
+
+impl Default for NDJSON {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> NDJSON<W> {
+    /// Writes to `out`, buffered.
+    pub fn with_writer(out: W) -> NDJSON<W> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_millis() as u64)
+            .unwrap_or(0);
+
+        NDJSON {
+            out: BufWriter::new(out),
+            binary: Binary::default(),
+            now,
+            elements_per_line: None,
+            db: 0,
+            line_start: String::new(),
+            brackets: ("", ""),
+            elements_in_line: 0,
+        }
+    }
+
+    pub fn set_binary(&mut self, binary: Binary) {
+        self.binary = binary;
+    }
+
+    /// Sets the time the TTLs are counted from, as a Unix time in milliseconds.
+    /// Defaults to now.
+    pub fn set_time(&mut self, now: u64) {
+        self.now = now;
+    }
+
+    /// Writes lists, sets, sorted sets and hashes in lines of at most `elements` elements.
+    pub fn set_elements_per_line(&mut self, elements: u32) {
+        self.elements_per_line = Some(elements.max(1));
+    }
+
+    /// Flushes the buffer and returns the writer.
+    pub fn into_inner(self) -> RdbResult<W> {
+        Ok(self.out.into_inner().map_err(|e| e.into_error())?)
+    }
+
+    fn start_key(&mut self, key: &[u8], typ: Type, expiry: Option<u64>) {
+        let ttl = expiry.map_or("null".into(), |expiry| {
+            (expiry as i64 - self.now as i64).to_string()
+        });
+        self.line_start = format!(
+            "{{\"db\":{},\"key\":{},\"type\":\"{}\",\"ttl\":{},\"value\":",
+            self.db,
+            json_string(key, self.binary),
+            type_name(typ),
+            ttl
+        );
+    }
+
+    /// Writes a value that is not split into several lines.
+    fn write_line(&mut self, value: &str) -> RdbResult<Control> {
+        write_str(&mut self.out, &self.line_start)?;
+        write_str(&mut self.out, value)?;
+        write_str(&mut self.out, "}\n")?;
+
+        Ok(Control::Continue)
+    }
+
+    fn start_collection(
+        &mut self,
+        key: &[u8],
+        typ: Type,
+        expiry: Option<u64>,
+        brackets: (&'static str, &'static str),
+    ) -> RdbResult<Control> {
+        self.start_key(key, typ, expiry);
+        self.brackets = brackets;
+        self.elements_in_line = 0;
+        write_str(&mut self.out, &self.line_start)?;
+        write_str(&mut self.out, self.brackets.0)?;
+
+        Ok(Control::Continue)
+    }
+
+    fn end_collection(&mut self) -> RdbResult<()> {
+        write_str(&mut self.out, self.brackets.1)?;
+        write_str(&mut self.out, "}\n")
+    }
+
+    /// Writes `element`, in a new line if the current one is full.
+    fn write_element(&mut self, element: &str) -> RdbResult<Control> {
+        if Some(self.elements_in_line) == self.elements_per_line {
+            self.end_collection()?;
+            write_str(&mut self.out, &self.line_start)?;
+            write_str(&mut self.out, self.brackets.0)?;
+            self.elements_in_line = 0;
+        }
+
+        if self.elements_in_line > 0 {
+            write_str(&mut self.out, ",")?;
+        }
+        self.elements_in_line += 1;
+        write_str(&mut self.out, element)?;
+
+        Ok(Control::Continue)
+    }
+}
+
+impl<W: Write> Formatter for NDJSON<W> {
+    fn end_rdb(&mut self) -> RdbResult<()> {
+        self.out.flush()?;
+
+        Ok(())
+    }
+
+    fn start_database(&mut self, db_index: u32) -> RdbResult<()> {
+        self.db = db_index;
+
+        Ok(())
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<Control> {
+        self.start_key(key, Type::String, expiry);
+        self.write_line(&json_string(value, self.binary))
+    }
+
+    fn raw_object(
+        &mut self,
+        key: &[u8],
+        value_type: u8,
+        raw: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        self.start_key(key, Type::from_encoding(value_type), expiry);
+        self.write_line(&json_string(raw, self.binary))
+    }
+
+    fn start_hash(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start_collection(key, Type::Hash, expiry, ("{", "}"))
+    }
+
+    fn end_hash(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end_collection()
+    }
+
+    fn hash_element(&mut self, _key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
+        let element = format!(
+            "{}:{}",
+            json_string(field, self.binary),
+            json_string(value, self.binary)
+        );
+        self.write_element(&element)
+    }
+
+    fn start_set(
+        &mut self,
+        key: &[u8],
+        _cardinality: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start_collection(key, Type::Set, expiry, ("[", "]"))
+    }
+
+    fn end_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end_collection()
+    }
+
+    fn set_element(&mut self, _key: &[u8], member: &[u8]) -> RdbResult<Control> {
+        let element = json_string(member, self.binary);
+        self.write_element(&element)
+    }
+
+    fn start_list(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start_collection(key, Type::List, expiry, ("[", "]"))
+    }
+
+    fn end_list(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end_collection()
+    }
+
+    fn list_element(&mut self, _key: &[u8], value: &[u8]) -> RdbResult<Control> {
+        let element = json_string(value, self.binary);
+        self.write_element(&element)
+    }
+
+    fn start_sorted_set(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start_collection(key, Type::SortedSet, expiry, ("{", "}"))
+    }
+
+    fn end_sorted_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end_collection()
+    }
+
+    fn sorted_set_element(&mut self, _key: &[u8], score: f64, member: &[u8]) -> RdbResult<Control> {
+        let element = format!("{}:{}", json_string(member, self.binary), json_score(score));
+        self.write_element(&element)
+    }
+}
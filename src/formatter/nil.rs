@@ -8,4 +8,10 @@ impl Nil {
     }
 }
 
+impl Default for Nil {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Formatter for Nil {}
@@ -1,25 +1,38 @@
 use super::write_str;
-use crate::formatter::Formatter;
+use crate::formatter::{Control, Formatter};
 use crate::types::{EncodingType, RdbResult};
 use std::io;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 
-pub struct Plain {
-    out: Box<dyn Write + 'static>,
+/// Writes one line per value or element
+pub struct Plain<W: Write = io::Stdout> {
+    out: BufWriter<W>,
     dbnum: u32,
     index: u32,
 }
 
 impl Plain {
+    /// Writes to stdout.
     pub fn new() -> Plain {
-        let out = Box::new(io::stdout());
+        Plain::with_writer(io::stdout())
+    }
+}
+
+impl<W: Write> Plain<W> {
+    /// Writes to `out`, buffered.
+    pub fn with_writer(out: W) -> Plain<W> {
         Plain {
-            out,
+            out: BufWriter::new(out),
             dbnum: 0,
             index: 0,
         }
     }
 
+    /// Flushes the buffer and returns the writer.
+    pub fn into_inner(self) -> RdbResult<W> {
+        Ok(self.out.into_inner().map_err(|e| e.into_error())?)
+    }
+
     fn write_line_start(&mut self) -> RdbResult<()> {
         write_str(&mut self.out, &format!("db={} ", self.dbnum))?;
 
@@ -27,11 +40,25 @@ impl Plain {
     }
 }
 
-impl Formatter for Plain {
+impl Default for Plain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> Formatter for Plain<W> {
+    fn end_rdb(&mut self) -> RdbResult<()> {
+        self.out.flush()?;
+
+        Ok(())
+    }
+
+    /// Comes after `end_rdb`
     fn checksum(&mut self, checksum: &[u8]) -> RdbResult<()> {
         write_str(&mut self.out, "checksum ")?;
-        write_str(&mut self.out, &hex::encode(&checksum))?;
+        write_str(&mut self.out, &hex::encode(checksum))?;
         write_str(&mut self.out, "\n")?;
+        self.out.flush()?;
 
         Ok(())
     }
@@ -42,16 +69,15 @@ impl Formatter for Plain {
         Ok(())
     }
 
-    fn set(&mut self, key: &[u8], value: &[u8], _expiry: Option<u64>) -> RdbResult<()> {
+    fn set(&mut self, key: &[u8], value: &[u8], _expiry: Option<u64>) -> RdbResult<Control> {
         self.write_line_start()?;
         self.out.write_all(key)?;
         write_str(&mut self.out, " -> ")?;
 
         self.out.write_all(value)?;
         write_str(&mut self.out, "\n")?;
-        self.out.flush()?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn aux_field(&mut self, key: &[u8], value: &[u8]) -> RdbResult<()> {
@@ -60,12 +86,11 @@ impl Formatter for Plain {
         write_str(&mut self.out, " -> ")?;
         self.out.write_all(value)?;
         write_str(&mut self.out, "\n")?;
-        self.out.flush()?;
 
         Ok(())
     }
 
-    fn hash_element(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<()> {
+    fn hash_element(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
         self.write_line_start()?;
 
         self.out.write_all(key)?;
@@ -74,12 +99,11 @@ impl Formatter for Plain {
         write_str(&mut self.out, " -> ")?;
         self.out.write_all(value)?;
         write_str(&mut self.out, "\n")?;
-        self.out.flush()?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn set_element(&mut self, key: &[u8], member: &[u8]) -> RdbResult<()> {
+    fn set_element(&mut self, key: &[u8], member: &[u8]) -> RdbResult<Control> {
         self.write_line_start()?;
 
         self.out.write_all(key)?;
@@ -87,9 +111,8 @@ impl Formatter for Plain {
         self.out.write_all(member)?;
         write_str(&mut self.out, " } ")?;
         write_str(&mut self.out, "\n")?;
-        self.out.flush()?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn start_list(
@@ -98,12 +121,12 @@ impl Formatter for Plain {
         _length: u32,
         _expiry: Option<u64>,
         _info: EncodingType,
-    ) -> RdbResult<()> {
+    ) -> RdbResult<Control> {
         self.index = 0;
 
-        Ok(())
+        Ok(Control::Continue)
     }
-    fn list_element(&mut self, key: &[u8], value: &[u8]) -> RdbResult<()> {
+    fn list_element(&mut self, key: &[u8], value: &[u8]) -> RdbResult<Control> {
         self.write_line_start()?;
 
         self.out.write_all(key)?;
@@ -111,10 +134,9 @@ impl Formatter for Plain {
         write_str(&mut self.out, " -> ")?;
         self.out.write_all(value)?;
         write_str(&mut self.out, "\n")?;
-        self.out.flush()?;
         self.index += 1;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn start_sorted_set(
@@ -123,13 +145,13 @@ impl Formatter for Plain {
         _length: u32,
         _expiry: Option<u64>,
         _info: EncodingType,
-    ) -> RdbResult<()> {
+    ) -> RdbResult<Control> {
         self.index = 0;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn sorted_set_element(&mut self, key: &[u8], score: f64, member: &[u8]) -> RdbResult<()> {
+    fn sorted_set_element(&mut self, key: &[u8], score: f64, member: &[u8]) -> RdbResult<Control> {
         self.write_line_start()?;
 
         self.out.write_all(key)?;
@@ -138,9 +160,8 @@ impl Formatter for Plain {
         self.out.write_all(member)?;
         write_str(&mut self.out, &format!(", score={}", score))?;
         write_str(&mut self.out, "}\n")?;
-        self.out.flush()?;
         self.index += 1;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 }
@@ -1,25 +1,96 @@
 use super::write_str;
-use crate::formatter::Formatter;
-use crate::types::{EncodingType, RdbResult};
+use crate::formatter::{Control, Formatter};
+use crate::types::{EncodingType, RdbError, RdbResult, Value};
+use crate::writer::{dump_payload, dump_raw_payload};
 use std::io;
-use std::io::Write;
+use std::io::{BufWriter, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub struct Protocol {
-    out: Box<dyn Write + 'static>,
+/// Options for writing every key as a RESTORE command with a DUMP payload
+///
+/// Compared to rebuilding the keys element by element, RESTORE is much faster to load
+/// and keeps the compact encodings.
+#[derive(Debug, Clone)]
+pub struct Restore {
+    /// RDB version of the payloads. Redis refuses payloads of versions newer than its own.
+    pub version: u32,
+    /// Adds REPLACE, so that existing keys are overwritten
+    pub replace: bool,
+    /// Passes expiries as Unix times in milliseconds with ABSTTL.
+    /// Otherwise they are turned into TTLs relative to the time the command is written,
+    /// and keys that already expired are left out.
+    pub absttl: bool,
+    /// Passes the LRU idle time or LFU frequency of the keys with IDLETIME or FREQ
+    pub eviction_info: bool,
+}
+
+impl Default for Restore {
+    fn default() -> Restore {
+        Restore {
+            version: super::rdb::DEFAULT_VERSION,
+            replace: false,
+            absttl: true,
+            eviction_info: false,
+        }
+    }
+}
+
+/// Writes the Redis commands that recreate the keys
+pub struct Protocol<W: Write = io::Stdout> {
+    out: BufWriter<W>,
     last_expiry: Option<u64>,
+    restore: Option<Restore>,
+    /// Key, value and expiry of the collection being read, in restore mode
+    pending: Option<(Vec<u8>, Value, Option<u64>)>,
+    idle: Option<u64>,
+    freq: Option<u8>,
 }
 
 impl Protocol {
+    /// Writes to stdout.
     pub fn new() -> Protocol {
-        let out = Box::new(io::stdout());
+        Protocol::with_writer(io::stdout())
+    }
+
+    /// Writes RESTORE commands to stdout instead of rebuilding the keys element by element.
+    pub fn restore(options: Restore) -> Protocol {
+        let mut protocol = Protocol::new();
+        protocol.set_restore(options);
+        protocol
+    }
+}
+
+impl<W: Write> Protocol<W> {
+    /// Writes to `out`, buffered.
+    pub fn with_writer(out: W) -> Protocol<W> {
         Protocol {
-            out: out,
+            out: BufWriter::new(out),
             last_expiry: None,
+            restore: None,
+            pending: None,
+            idle: None,
+            freq: None,
         }
     }
+
+    /// Writes RESTORE commands instead of rebuilding the keys element by element.
+    pub fn set_restore(&mut self, options: Restore) {
+        self.restore = Some(options);
+    }
+
+    /// Flushes the buffer and returns the writer.
+    pub fn into_inner(self) -> RdbResult<W> {
+        Ok(self.out.into_inner().map_err(|e| e.into_error())?)
+    }
 }
 
-impl Protocol {
+impl Default for Protocol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> Protocol<W> {
     fn emit(&mut self, args: Vec<&[u8]>) -> RdbResult<()> {
         write_str(&mut self.out, "*")?;
         self.out.write_all(args.len().to_string().as_bytes())?;
@@ -39,6 +110,89 @@ impl Protocol {
         self.last_expiry = expiry
     }
 
+    fn emit_restore(&mut self, key: &[u8], payload: &[u8], expiry: Option<u64>) -> RdbResult<()> {
+        let options = match self.restore {
+            Some(ref options) => options.clone(),
+            None => return Ok(()),
+        };
+        let (idle, freq) = (self.idle.take(), self.freq.take());
+
+        let ttl = match expiry {
+            None => 0,
+            Some(expiry) if options.absttl => expiry,
+            Some(expiry) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|now| now.as_millis() as u64)
+                    .unwrap_or(0);
+                if expiry <= now {
+                    return Ok(());
+                }
+                expiry - now
+            }
+        };
+        let ttl = ttl.to_string();
+
+        let mut args: Vec<&[u8]> = vec![b"RESTORE", key, ttl.as_bytes(), payload];
+        if options.replace {
+            args.push(b"REPLACE");
+        }
+        if options.absttl && expiry.is_some() {
+            args.push(b"ABSTTL");
+        }
+        let idle = idle.map(|idle| idle.to_string());
+        let freq = freq.map(|freq| freq.to_string());
+        if options.eviction_info {
+            if let Some(ref idle) = idle {
+                args.push(b"IDLETIME");
+                args.push(idle.as_bytes());
+            } else if let Some(ref freq) = freq {
+                args.push(b"FREQ");
+                args.push(freq.as_bytes());
+            }
+        }
+
+        self.emit(args)
+    }
+
+    fn restore_version(&self) -> Option<u32> {
+        self.restore.as_ref().map(|options| options.version)
+    }
+
+    /// Starts a collection, which is either buffered to be restored or rebuilt element by element.
+    fn start(&mut self, key: &[u8], value: Value, expiry: Option<u64>) {
+        if self.restore.is_some() {
+            self.pending = Some((key.to_vec(), value, expiry));
+        } else {
+            self.pre_expire(expiry);
+        }
+    }
+
+    fn end(&mut self, key: &[u8]) -> RdbResult<()> {
+        // Values are only pending in restore mode
+        match (self.pending.take(), self.restore_version()) {
+            (Some((key, value, expiry)), Some(version)) => {
+                let payload = dump_payload(&value, version)?;
+                self.emit_restore(&key, &payload, expiry)
+            }
+            _ => self.post_expire(key),
+        }
+    }
+
+    /// The value of the key being restored, or `None` if it is rebuilt.
+    fn pending(&mut self) -> RdbResult<Option<&mut Value>> {
+        if self.restore.is_none() {
+            return Ok(None);
+        }
+
+        match self.pending {
+            Some((_, ref mut value, _)) => Ok(Some(value)),
+            None => Err(RdbError::Other(
+                "Element callback without a start callback".into(),
+            )),
+        }
+    }
+
     fn post_expire(&mut self, key: &[u8]) -> RdbResult<()> {
         if let Some(expire) = self.last_expiry {
             let expire = expire.to_string();
@@ -50,12 +204,14 @@ impl Protocol {
     }
 }
 
-impl Formatter for Protocol {
+impl<W: Write> Formatter for Protocol<W> {
     fn start_rdb(&mut self) -> RdbResult<()> {
         Ok(())
     }
 
     fn end_rdb(&mut self) -> RdbResult<()> {
+        self.out.flush()?;
+
         Ok(())
     }
 
@@ -66,92 +222,141 @@ impl Formatter for Protocol {
         Ok(())
     }
 
-    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<()> {
+    fn eviction_info(&mut self, _key: &[u8], idle: Option<u64>, freq: Option<u8>) -> RdbResult<()> {
+        if self.restore.is_some() {
+            self.idle = idle;
+            self.freq = freq;
+        }
+
+        Ok(())
+    }
+
+    fn raw_object(
+        &mut self,
+        key: &[u8],
+        value_type: u8,
+        raw: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        // Streams and module values can only be restored, not rebuilt.
+        let version = self.restore_version().ok_or_else(|| {
+            RdbError::Other(format!(
+                "Key {} holds a value of type {}, which can only be written as RESTORE",
+                String::from_utf8_lossy(key),
+                value_type
+            ))
+        })?;
+        let payload = dump_raw_payload(value_type, raw, version)?;
+        self.emit_restore(key, &payload, expiry)?;
+
+        Ok(Control::Continue)
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<Control> {
+        if let Some(version) = self.restore_version() {
+            let payload = dump_payload(&Value::String(value.to_vec()), version)?;
+            self.emit_restore(key, &payload, expiry)?;
+            return Ok(Control::Continue);
+        }
+
         self.pre_expire(expiry);
         self.emit(vec!["SET".as_bytes(), key, value])?;
         self.post_expire(key)?;
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn start_hash(
         &mut self,
-        _key: &[u8],
+        key: &[u8],
         _length: u32,
         expiry: Option<u64>,
         _info: EncodingType,
-    ) -> RdbResult<()> {
-        self.pre_expire(expiry);
+    ) -> RdbResult<Control> {
+        self.start(key, Value::Hash(Vec::new()), expiry);
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn end_hash(&mut self, key: &[u8]) -> RdbResult<()> {
-        self.post_expire(key)?;
-
-        Ok(())
+        self.end(key)
     }
-    fn hash_element(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<()> {
-        self.emit(vec!["HSET".as_bytes(), key, field, value])?;
-        Ok(())
+    fn hash_element(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
+        match self.pending()? {
+            Some(Value::Hash(pairs)) => pairs.push((field.to_vec(), value.to_vec())),
+            Some(_) => {}
+            None => self.emit(vec!["HSET".as_bytes(), key, field, value])?,
+        }
+        Ok(Control::Continue)
     }
 
     fn start_set(
         &mut self,
-        _key: &[u8],
+        key: &[u8],
         _cardinality: u32,
         expiry: Option<u64>,
         _info: EncodingType,
-    ) -> RdbResult<()> {
-        self.pre_expire(expiry);
-        Ok(())
+    ) -> RdbResult<Control> {
+        self.start(key, Value::Set(Vec::new()), expiry);
+        Ok(Control::Continue)
     }
     fn end_set(&mut self, key: &[u8]) -> RdbResult<()> {
-        self.post_expire(key)?;
-        Ok(())
+        self.end(key)
     }
-    fn set_element(&mut self, key: &[u8], member: &[u8]) -> RdbResult<()> {
-        self.emit(vec!["SADD".as_bytes(), key, member])?;
-        Ok(())
+    fn set_element(&mut self, key: &[u8], member: &[u8]) -> RdbResult<Control> {
+        match self.pending()? {
+            Some(Value::Set(members)) => members.push(member.to_vec()),
+            Some(_) => {}
+            None => self.emit(vec!["SADD".as_bytes(), key, member])?,
+        }
+        Ok(Control::Continue)
     }
 
     fn start_list(
         &mut self,
-        _key: &[u8],
+        key: &[u8],
         _length: u32,
         expiry: Option<u64>,
         _info: EncodingType,
-    ) -> RdbResult<()> {
-        self.pre_expire(expiry);
-        Ok(())
+    ) -> RdbResult<Control> {
+        self.start(key, Value::List(Vec::new()), expiry);
+        Ok(Control::Continue)
     }
     fn end_list(&mut self, key: &[u8]) -> RdbResult<()> {
-        self.post_expire(key)?;
-        Ok(())
+        self.end(key)
     }
-    fn list_element(&mut self, key: &[u8], value: &[u8]) -> RdbResult<()> {
-        self.emit(vec!["RPUSH".as_bytes(), key, value])?;
-        Ok(())
+    fn list_element(&mut self, key: &[u8], value: &[u8]) -> RdbResult<Control> {
+        match self.pending()? {
+            Some(Value::List(elements)) => elements.push(value.to_vec()),
+            Some(_) => {}
+            None => self.emit(vec!["RPUSH".as_bytes(), key, value])?,
+        }
+        Ok(Control::Continue)
     }
 
     fn start_sorted_set(
         &mut self,
-        _key: &[u8],
+        key: &[u8],
         _length: u32,
         expiry: Option<u64>,
         _info: EncodingType,
-    ) -> RdbResult<()> {
-        self.pre_expire(expiry);
-        Ok(())
+    ) -> RdbResult<Control> {
+        self.start(key, Value::SortedSet(Vec::new()), expiry);
+        Ok(Control::Continue)
     }
 
     fn end_sorted_set(&mut self, key: &[u8]) -> RdbResult<()> {
-        self.post_expire(key)?;
-        Ok(())
+        self.end(key)
     }
 
-    fn sorted_set_element(&mut self, key: &[u8], score: f64, member: &[u8]) -> RdbResult<()> {
-        let score = score.to_string();
-        self.emit(vec!["ZADD".as_bytes(), key, score.as_bytes(), member])?;
-        Ok(())
+    fn sorted_set_element(&mut self, key: &[u8], score: f64, member: &[u8]) -> RdbResult<Control> {
+        match self.pending()? {
+            Some(Value::SortedSet(elements)) => elements.push((score, member.to_vec())),
+            Some(_) => {}
+            None => {
+                let score = score.to_string();
+                self.emit(vec!["ZADD".as_bytes(), key, score.as_bytes(), member])?;
+            }
+        }
+        Ok(Control::Continue)
     }
 }
@@ -0,0 +1,220 @@
+use crate::constants::version;
+use crate::formatter::{Control, Formatter};
+use crate::types::{EncodingType, RdbError, RdbResult, Value};
+use crate::writer::RdbWriter;
+use std::io::Write;
+
+/// The version written by default, the newest supported one
+pub const DEFAULT_VERSION: u32 = version::SUPPORTED_MAXIMUM;
+
+/// Writes the parsed data as a new RDB file
+///
+/// Collections are buffered until their `end_*` callback and then written in one go.
+/// The checksum of the input is ignored, a new one is computed for the output.
+pub struct Rdb<W: Write> {
+    writer: RdbWriter<W>,
+    pending: Option<(Vec<u8>, Value, Option<u64>)>,
+    /// The idle time and access frequency of the next key
+    eviction: Option<(Option<u64>, Option<u8>)>,
+}
+
+impl<W: Write> Rdb<W> {
+    /// Writes RDB files of the newest supported version.
+    pub fn new(out: W) -> Rdb<W> {
+        Rdb::with_version(out, DEFAULT_VERSION).unwrap()
+    }
+
+    /// Writes RDB files of the given version.
+    pub fn with_version(out: W, version: u32) -> RdbResult<Rdb<W>> {
+        Ok(Rdb::from_writer(RdbWriter::new(out, version)?))
+    }
+
+    /// Writes through `writer`, e.g. to use other encodings.
+    pub fn from_writer(writer: RdbWriter<W>) -> Rdb<W> {
+        Rdb {
+            writer,
+            pending: None,
+            eviction: None,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+
+    /// Writes the eviction info of the next key, if it came with any.
+    fn write_eviction_info(&mut self) -> RdbResult<()> {
+        if let Some((idle, freq)) = self.eviction.take() {
+            if let Some(idle) = idle {
+                self.writer.write_idle(idle)?;
+            }
+            if let Some(freq) = freq {
+                self.writer.write_freq(freq)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn start(&mut self, key: &[u8], value: Value, expiry: Option<u64>) -> RdbResult<Control> {
+        self.pending = Some((key.to_vec(), value, expiry));
+
+        Ok(Control::Continue)
+    }
+
+    fn end(&mut self) -> RdbResult<()> {
+        if let Some((key, value, expiry)) = self.pending.take() {
+            self.write_eviction_info()?;
+            self.writer.write_object(&key, &value, expiry)?;
+        }
+
+        Ok(())
+    }
+
+    fn pending(&mut self) -> RdbResult<&mut Value> {
+        match self.pending {
+            Some((_, ref mut value, _)) => Ok(value),
+            None => Err(RdbError::Other(
+                "Element callback without a start callback".into(),
+            )),
+        }
+    }
+}
+
+impl<W: Write> Formatter for Rdb<W> {
+    fn start_rdb(&mut self) -> RdbResult<()> {
+        self.writer.write_header()
+    }
+
+    fn end_rdb(&mut self) -> RdbResult<()> {
+        self.writer.write_eof()
+    }
+
+    fn start_database(&mut self, db_index: u32) -> RdbResult<()> {
+        self.writer.write_select_db(db_index)
+    }
+
+    fn resizedb(&mut self, db_size: u32, expires_size: u32) -> RdbResult<()> {
+        // Only a hint for Redis, so it doesn't matter if keys get filtered out.
+        self.writer.write_resizedb(db_size, expires_size)
+    }
+
+    fn aux_field(&mut self, key: &[u8], value: &[u8]) -> RdbResult<()> {
+        self.writer.write_aux(key, value)
+    }
+
+    fn function(&mut self, code: &[u8]) -> RdbResult<()> {
+        self.writer.write_function(code)
+    }
+
+    fn module_aux(&mut self, raw: &[u8]) -> RdbResult<()> {
+        self.writer.write_module_aux(raw)
+    }
+
+    fn eviction_info(&mut self, _key: &[u8], idle: Option<u64>, freq: Option<u8>) -> RdbResult<()> {
+        self.eviction = Some((idle, freq));
+
+        Ok(())
+    }
+
+    fn raw_object(
+        &mut self,
+        key: &[u8],
+        value_type: u8,
+        raw: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        self.write_eviction_info()?;
+        self.writer.write_raw_object(key, value_type, raw, expiry)?;
+
+        Ok(Control::Continue)
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<Control> {
+        self.write_eviction_info()?;
+        self.writer
+            .write_object(key, &Value::String(value.to_vec()), expiry)?;
+
+        Ok(Control::Continue)
+    }
+
+    fn start_hash(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Value::Hash(Vec::new()), expiry)
+    }
+    fn end_hash(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end()
+    }
+    fn hash_element(&mut self, _key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
+        if let Value::Hash(elements) = self.pending()? {
+            elements.push((field.to_vec(), value.to_vec()));
+        }
+
+        Ok(Control::Continue)
+    }
+
+    fn start_set(
+        &mut self,
+        key: &[u8],
+        _cardinality: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Value::Set(Vec::new()), expiry)
+    }
+    fn end_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end()
+    }
+    fn set_element(&mut self, _key: &[u8], member: &[u8]) -> RdbResult<Control> {
+        if let Value::Set(elements) = self.pending()? {
+            elements.push(member.to_vec());
+        }
+
+        Ok(Control::Continue)
+    }
+
+    fn start_list(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Value::List(Vec::new()), expiry)
+    }
+    fn end_list(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end()
+    }
+    fn list_element(&mut self, _key: &[u8], value: &[u8]) -> RdbResult<Control> {
+        if let Value::List(elements) = self.pending()? {
+            elements.push(value.to_vec());
+        }
+
+        Ok(Control::Continue)
+    }
+
+    fn start_sorted_set(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Value::SortedSet(Vec::new()), expiry)
+    }
+    fn end_sorted_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        self.end()
+    }
+    fn sorted_set_element(&mut self, _key: &[u8], score: f64, member: &[u8]) -> RdbResult<Control> {
+        if let Value::SortedSet(elements) = self.pending()? {
+            elements.push((score, member.to_vec()));
+        }
+
+        Ok(Control::Continue)
+    }
+}
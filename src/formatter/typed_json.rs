@@ -0,0 +1,373 @@
+use super::write_str;
+use crate::formatter::{Control, Formatter};
+use crate::types::{EncodingType, RdbResult, Type};
+use rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+use std::io;
+use std::io::{BufWriter, Write};
+
+/// How strings that are not valid UTF-8 are written
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Binary {
+    /// Writes bytes that are not part of valid UTF-8 as `\xNN`.
+    /// Backslashes are doubled, so that `\x` in the data stays distinguishable.
+    #[default]
+    Escape,
+    /// Writes all strings, valid UTF-8 or not, in standard base64
+    Base64,
+}
+
+impl Binary {
+    /// The name written to the `binary` field of every key
+    pub fn name(self) -> &'static str {
+        match self {
+            Binary::Escape => "escape",
+            Binary::Base64 => "base64",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Binary> {
+        match name {
+            "escape" => Some(Binary::Escape),
+            "base64" => Some(Binary::Base64),
+            _ => None,
+        }
+    }
+
+    /// Reverses the encoding of a string. Returns `None` if `text` is not valid.
+    pub fn decode(self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            Binary::Escape => unescape(text),
+            Binary::Base64 => text.from_base64().ok(),
+        }
+    }
+}
+
+/// Writes every key as an object with its type, encoding and expiry
+///
+/// ```json
+/// [
+/// {"db":0,"key":"name","type":"string","encoding":"string","expiry":null,"binary":"escape","value":"value"},
+/// {"db":0,"key":"scores","type":"sortedset","encoding":"ziplist","expiry":1700000000000,"binary":"escape","value":{"jane":1.5}}
+/// ]
+/// ```
+///
+/// Lists and sets are arrays, hashes objects and sorted sets objects with numeric scores,
+/// where infinite scores are the strings `"inf"` and `"-inf"`. Expiries are Unix times
+/// in milliseconds. Streams and module values have the encoding `raw` and their value
+/// as stored in the file. `binary` names the encoding of the strings, so that
+/// `import::json` can read them back.
+pub struct TypedJSON<W: Write = io::Stdout> {
+    out: BufWriter<W>,
+    binary: Binary,
+    db: u32,
+    has_keys: bool,
+    element_index: u32,
+}
+
+impl TypedJSON {
+    /// Writes to stdout.
+    pub fn new() -> TypedJSON {
+        TypedJSON::with_writer(io::stdout())
+    }
+}
+
+impl Default for TypedJSON {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> TypedJSON<W> {
+    /// Writes to `out`, buffered.
+    pub fn with_writer(out: W) -> TypedJSON<W> {
+        TypedJSON {
+            out: BufWriter::new(out),
+            binary: Binary::default(),
+            db: 0,
+            has_keys: false,
+            element_index: 0,
+        }
+    }
+
+    pub fn set_binary(&mut self, binary: Binary) {
+        self.binary = binary;
+    }
+
+    /// Flushes the buffer and returns the writer.
+    pub fn into_inner(self) -> RdbResult<W> {
+        Ok(self.out.into_inner().map_err(|e| e.into_error())?)
+    }
+
+    fn write_string(&mut self, value: &[u8]) -> RdbResult<()> {
+        write_str(&mut self.out, &json_string(value, self.binary))
+    }
+
+    /// Writes everything of a key up to its value.
+    fn start_key(
+        &mut self,
+        key: &[u8],
+        typ: Type,
+        encoding: &str,
+        expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        write_str(&mut self.out, if self.has_keys { ",\n" } else { "\n" })?;
+        self.has_keys = true;
+        self.element_index = 0;
+
+        write_str(&mut self.out, &format!("{{\"db\":{},\"key\":", self.db))?;
+        self.write_string(key)?;
+        let expiry = expiry.map_or("null".into(), |expiry| expiry.to_string());
+        write_str(
+            &mut self.out,
+            &format!(
+                ",\"type\":\"{}\",\"encoding\":\"{}\",\"expiry\":{},\"binary\":\"{}\",\"value\":",
+                type_name(typ),
+                encoding,
+                expiry,
+                self.binary.name()
+            ),
+        )?;
+
+        Ok(Control::Continue)
+    }
+
+    fn write_comma(&mut self) -> RdbResult<()> {
+        if self.element_index > 0 {
+            write_str(&mut self.out, ",")?;
+        }
+        self.element_index += 1;
+
+        Ok(())
+    }
+}
+
+/// `value` as a JSON string
+pub(crate) fn json_string(value: &[u8], binary: Binary) -> String {
+    let encoded = match binary {
+        Binary::Escape => escape(value),
+        Binary::Base64 => value.to_base64(STANDARD),
+    };
+    serde_json::to_string(&encoded).unwrap()
+}
+
+/// `score` as a JSON number, or a string if it is infinite
+pub(crate) fn json_score(score: f64) -> String {
+    // JSON has no infinity, NaN can't be stored in sorted sets
+    if score.is_finite() {
+        serde_json::to_string(&score).unwrap()
+    } else if score > 0.0 {
+        "\"inf\"".into()
+    } else {
+        "\"-inf\"".into()
+    }
+}
+
+/// Keeps valid UTF-8, doubles backslashes and writes all other bytes as `\xNN`
+pub(crate) fn escape(value: &[u8]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for chunk in value.utf8_chunks() {
+        escaped.push_str(&chunk.valid().replace('\\', "\\\\"));
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+/// Reverses `escape`
+fn unescape(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        match rest {
+            [b'\\', tail @ ..] => {
+                bytes.push(b'\\');
+                rest = tail;
+            }
+            [b'x', high, low, tail @ ..] if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+                let hex = [*high, *low];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+                rest = tail;
+            }
+            _ => return None,
+        }
+    }
+    Some(bytes)
+}
+
+pub(crate) fn type_name(typ: Type) -> &'static str {
+    match typ {
+        Type::String => "string",
+        Type::List => "list",
+        Type::Set => "set",
+        Type::SortedSet => "sortedset",
+        Type::Hash => "hash",
+        Type::Stream => "stream",
+        Type::Module => "module",
+    }
+}
+
+/// The name `OBJECT ENCODING` uses
+pub(crate) fn encoding_name(typ: Type, encoding: EncodingType) -> &'static str {
+    match encoding {
+        EncodingType::String => "string",
+        // Sets that are neither an intset nor a listpack are hash tables
+        EncodingType::LinkedList if typ == Type::Set => "hashtable",
+        EncodingType::LinkedList => "linkedlist",
+        EncodingType::Hashtable if typ == Type::SortedSet => "skiplist",
+        EncodingType::Hashtable => "hashtable",
+        EncodingType::Skiplist => "skiplist",
+        EncodingType::Intset(_) => "intset",
+        EncodingType::Ziplist(_) => "ziplist",
+        EncodingType::Zipmap(_) => "zipmap",
+        EncodingType::Listpack(_) => "listpack",
+        EncodingType::Quicklist => "quicklist",
+    }
+}
+
+impl<W: Write> Formatter for TypedJSON<W> {
+    fn start_rdb(&mut self) -> RdbResult<()> {
+        write_str(&mut self.out, "[")
+    }
+
+    fn end_rdb(&mut self) -> RdbResult<()> {
+        write_str(&mut self.out, if self.has_keys { "\n]\n" } else { "]\n" })?;
+        self.out.flush()?;
+
+        Ok(())
+    }
+
+    fn start_database(&mut self, db_index: u32) -> RdbResult<()> {
+        self.db = db_index;
+
+        Ok(())
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<Control> {
+        self.start_key(key, Type::String, "string", expiry)?;
+        self.write_string(value)?;
+        write_str(&mut self.out, "}")?;
+
+        Ok(Control::Continue)
+    }
+
+    fn raw_object(
+        &mut self,
+        key: &[u8],
+        value_type: u8,
+        raw: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        self.start_key(key, Type::from_encoding(value_type), "raw", expiry)?;
+        self.write_string(raw)?;
+        write_str(&mut self.out, "}")?;
+
+        Ok(Control::Continue)
+    }
+
+    fn start_hash(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start_key(key, Type::Hash, encoding_name(Type::Hash, info), expiry)?;
+        write_str(&mut self.out, "{")?;
+
+        Ok(Control::Continue)
+    }
+
+    fn end_hash(&mut self, _key: &[u8]) -> RdbResult<()> {
+        write_str(&mut self.out, "}}")
+    }
+
+    fn hash_element(&mut self, _key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
+        self.write_comma()?;
+        self.write_string(field)?;
+        write_str(&mut self.out, ":")?;
+        self.write_string(value)?;
+
+        Ok(Control::Continue)
+    }
+
+    fn start_set(
+        &mut self,
+        key: &[u8],
+        _cardinality: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start_key(key, Type::Set, encoding_name(Type::Set, info), expiry)?;
+        write_str(&mut self.out, "[")?;
+
+        Ok(Control::Continue)
+    }
+
+    fn end_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        write_str(&mut self.out, "]}")
+    }
+
+    fn set_element(&mut self, _key: &[u8], member: &[u8]) -> RdbResult<Control> {
+        self.write_comma()?;
+        self.write_string(member)?;
+
+        Ok(Control::Continue)
+    }
+
+    fn start_list(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start_key(key, Type::List, encoding_name(Type::List, info), expiry)?;
+        write_str(&mut self.out, "[")?;
+
+        Ok(Control::Continue)
+    }
+
+    fn end_list(&mut self, _key: &[u8]) -> RdbResult<()> {
+        write_str(&mut self.out, "]}")
+    }
+
+    fn list_element(&mut self, _key: &[u8], value: &[u8]) -> RdbResult<Control> {
+        self.write_comma()?;
+        self.write_string(value)?;
+
+        Ok(Control::Continue)
+    }
+
+    fn start_sorted_set(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        let encoding = encoding_name(Type::SortedSet, info);
+        self.start_key(key, Type::SortedSet, encoding, expiry)?;
+        write_str(&mut self.out, "{")?;
+
+        Ok(Control::Continue)
+    }
+
+    fn end_sorted_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        write_str(&mut self.out, "}}")
+    }
+
+    fn sorted_set_element(&mut self, _key: &[u8], score: f64, member: &[u8]) -> RdbResult<Control> {
+        self.write_comma()?;
+        self.write_string(member)?;
+        write_str(&mut self.out, ":")?;
+        write_str(&mut self.out, &json_score(score))?;
+
+        Ok(Control::Continue)
+    }
+}
@@ -0,0 +1,333 @@
+//! Generating RDB files with random data, e.g. for benchmarks
+//!
+//! The data only depends on the seed and the settings, so the same `Generator` always
+//! writes the same file.
+//!
+//! ```rust,no_run
+//! # use std::io::BufWriter;
+//! # use std::fs::File;
+//! use rdb::generate::{Generator, Size};
+//! use rdb::Type;
+//!
+//! let mut generator = Generator::new(42);
+//! generator.add_keys(Type::String, 100_000);
+//! generator.add_keys(Type::Hash, 10_000);
+//! generator.set_elements(Size::Exponential { mean: 20, max: 1000 });
+//! generator.set_expiry_ratio(0.25);
+//!
+//! let output = BufWriter::new(File::create("dump.rdb").unwrap());
+//! generator.generate(output).unwrap();
+//! ```
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::formatter::rdb::DEFAULT_VERSION;
+use crate::types::{RdbError, RdbOk, Type, Value};
+use crate::writer::{Encodings, RdbWriter};
+
+const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// How often a member is drawn again when it already exists in its set
+const MAX_ATTEMPTS: usize = 10;
+
+/// A distribution of sizes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Size {
+    Fixed(usize),
+    /// Any size from the first to the second one, inclusive
+    Uniform(usize, usize),
+    /// Mostly small sizes with a long tail, cut off at `max`
+    Exponential {
+        mean: usize,
+        max: usize,
+    },
+}
+
+/// SplitMix64, which is fast and easy to reproduce
+struct Rng(u64);
+
+impl Rng {
+    /// Creates the generator of the values of the key at `index`
+    fn new(seed: u64, index: u64) -> Rng {
+        let mut rng = Rng(seed ^ index.wrapping_mul(0xd1b5_4a32_d192_ed03));
+        rng.next();
+        rng
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number from 0 to 1, excluding 1
+    fn fraction(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound.max(1)
+    }
+
+    fn size(&mut self, size: Size) -> usize {
+        match size {
+            Size::Fixed(size) => size,
+            Size::Uniform(min, max) => min + self.below((max.max(min) - min) as u64 + 1) as usize,
+            Size::Exponential { mean, max } => {
+                let size = -(mean as f64) * (1.0 - self.fraction()).ln();
+                (size as usize).min(max)
+            }
+        }
+    }
+
+    fn string(&mut self, length: usize) -> Vec<u8> {
+        (0..length)
+            .map(|_| ALPHABET[self.below(ALPHABET.len() as u64) as usize])
+            .collect()
+    }
+
+    /// Returns an integer with up to `digits` digits
+    fn integer(&mut self, digits: usize) -> Vec<u8> {
+        let digits = digits.clamp(1, 18) as u32;
+        self.below(10u64.pow(digits)).to_string().into_bytes()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Generator {
+    seed: u64,
+    version: u32,
+    encodings: Encodings,
+    databases: Vec<u32>,
+    keys: Vec<(Type, u64)>,
+    elements: Size,
+    value_size: Size,
+    integer_ratio: f64,
+    expiry_ratio: f64,
+    ttl: Size,
+    ctime: u64,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Generator {
+        Generator {
+            seed,
+            version: DEFAULT_VERSION,
+            encodings: Encodings::default(),
+            databases: Vec::new(),
+            keys: Vec::new(),
+            elements: Size::Uniform(1, 16),
+            value_size: Size::Uniform(4, 32),
+            integer_ratio: 0.0,
+            expiry_ratio: 0.0,
+            ttl: Size::Uniform(60, 86400),
+            ctime: 1_700_000_000,
+        }
+    }
+
+    pub fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+
+    pub fn set_encodings(&mut self, encodings: Encodings) {
+        self.encodings = encodings;
+    }
+
+    /// Spreads the keys evenly over the databases added. Defaults to database 0.
+    pub fn add_database(&mut self, db: u32) {
+        self.databases.push(db);
+    }
+
+    /// Adds `count` keys of type `typ`, which must not be a stream or module value.
+    pub fn add_keys(&mut self, typ: Type, count: u64) {
+        self.keys.push((typ, count));
+    }
+
+    /// Sets the number of elements of lists, sets, sorted sets and hashes.
+    /// Defaults to 1 to 16.
+    pub fn set_elements(&mut self, elements: Size) {
+        self.elements = elements;
+    }
+
+    /// Sets the length of strings, elements and hash values. Defaults to 4 to 32 bytes.
+    pub fn set_value_size(&mut self, value_size: Size) {
+        self.value_size = value_size;
+    }
+
+    /// Sets the share of keys holding integers instead of text, e.g. to get intsets.
+    pub fn set_integer_ratio(&mut self, integer_ratio: f64) {
+        self.integer_ratio = integer_ratio;
+    }
+
+    /// Sets the share of keys with an expiry.
+    pub fn set_expiry_ratio(&mut self, expiry_ratio: f64) {
+        self.expiry_ratio = expiry_ratio;
+    }
+
+    /// Sets the TTL in seconds of the keys with an expiry, counted from the `ctime`.
+    /// Defaults to a minute to a day.
+    pub fn set_ttl(&mut self, ttl: Size) {
+        self.ttl = ttl;
+    }
+
+    /// Sets the creation time of the file, in seconds since the epoch.
+    /// Defaults to a fixed time in November 2023, so that files do not depend on when
+    /// they were generated. As expiries are counted from it, keys with an expiry have
+    /// already expired when such a file is loaded; pass the current time to avoid that.
+    pub fn set_ctime(&mut self, ctime: u64) {
+        self.ctime = ctime;
+    }
+
+    fn total_keys(&self) -> u64 {
+        self.keys.iter().map(|&(_, count)| count).sum()
+    }
+
+    /// Returns the type of the key at `index`
+    fn key_type(&self, mut index: u64) -> Type {
+        for &(typ, count) in &self.keys {
+            if index < count {
+                return typ;
+            }
+            index -= count;
+        }
+        unreachable!("index out of range")
+    }
+
+    /// Writes the RDB file to `output`.
+    pub fn generate<W: Write>(&self, output: W) -> RdbOk {
+        if let Some(&(typ, _)) = self
+            .keys
+            .iter()
+            .find(|(typ, _)| matches!(typ, Type::Stream | Type::Module))
+        {
+            return Err(RdbError::Other(format!(
+                "Generating values of type {:?} is not supported",
+                typ
+            )));
+        }
+
+        let databases = if self.databases.is_empty() {
+            vec![0]
+        } else {
+            self.databases.clone()
+        };
+
+        let mut writer = RdbWriter::with_encodings(output, self.version, self.encodings.clone())?;
+        writer.write_header()?;
+        writer.write_aux(b"redis-bits", b"64")?;
+        writer.write_aux(b"ctime", self.ctime.to_string().as_bytes())?;
+
+        let total = self.total_keys();
+        let step = databases.len() as u64;
+        for (position, &db) in databases.iter().enumerate() {
+            let indexes = (position as u64..total).step_by(step as usize);
+            let keys = indexes.clone().count();
+            if keys == 0 {
+                continue;
+            }
+            let expires = indexes
+                .clone()
+                .filter(|&index| self.expiry(&mut Rng::new(self.seed, index)).is_some())
+                .count();
+
+            writer.write_select_db(db)?;
+            writer.write_resizedb(keys as u32, expires as u32)?;
+            for index in indexes {
+                let mut rng = Rng::new(self.seed, index);
+                let expiry = self.expiry(&mut rng);
+                let typ = self.key_type(index);
+                let value = self.value(&mut rng, typ);
+                writer.write_object(&key_name(typ, index), &value, expiry)?;
+            }
+        }
+
+        writer.write_eof()
+    }
+
+    /// Draws the expiry in milliseconds. Must be the first thing drawn for a key.
+    fn expiry(&self, rng: &mut Rng) -> Option<u64> {
+        let has_expiry = rng.fraction() < self.expiry_ratio;
+        let ttl = rng.size(self.ttl) as u64;
+        if has_expiry {
+            Some((self.ctime + ttl) * 1000)
+        } else {
+            None
+        }
+    }
+
+    fn value(&self, rng: &mut Rng, typ: Type) -> Value {
+        let integers = rng.fraction() < self.integer_ratio;
+        let element = |rng: &mut Rng| {
+            let size = rng.size(self.value_size);
+            if integers {
+                rng.integer(size)
+            } else {
+                rng.string(size)
+            }
+        };
+
+        if typ == Type::String {
+            return Value::String(element(rng));
+        }
+
+        let length = rng.size(self.elements).max(1);
+        match typ {
+            Type::List => Value::List((0..length).map(|_| element(rng)).collect()),
+            Type::Set => Value::Set(unique(rng, length, element)),
+            Type::SortedSet => {
+                let members = unique(rng, length, element);
+                let scores = members.into_iter().map(|member| {
+                    let score = rng.below(1_000_000) as f64 / 100.0;
+                    (score, member)
+                });
+                Value::SortedSet(scores.collect())
+            }
+            Type::Hash => {
+                let fields = unique(rng, length, |rng: &mut Rng| {
+                    let size = rng.size(self.value_size);
+                    rng.string(size)
+                });
+                let values = fields.into_iter().map(|field| (field, element(rng)));
+                Value::Hash(values.collect())
+            }
+            _ => unreachable!("checked in generate"),
+        }
+    }
+}
+
+/// Draws up to `length` distinct elements. Fewer if the elements drawn keep repeating.
+fn unique<F: FnMut(&mut Rng) -> Vec<u8>>(
+    rng: &mut Rng,
+    length: usize,
+    mut draw: F,
+) -> Vec<Vec<u8>> {
+    let mut seen = HashSet::new();
+    let mut elements = Vec::with_capacity(length);
+
+    for _ in 0..length {
+        for _ in 0..MAX_ATTEMPTS {
+            let element = draw(rng);
+            if seen.insert(element.clone()) {
+                elements.push(element);
+                break;
+            }
+        }
+    }
+
+    elements
+}
+
+fn key_name(typ: Type, index: u64) -> Vec<u8> {
+    let prefix = match typ {
+        Type::String => "string",
+        Type::List => "list",
+        Type::Set => "set",
+        Type::SortedSet => "zset",
+        Type::Hash => "hash",
+        _ => "key",
+    };
+    format!("{}:{}", prefix, index).into_bytes()
+}
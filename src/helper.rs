@@ -1,4 +1,5 @@
-use crate::types::RdbResult;
+use crate::types::{RdbOk, RdbResult};
+use std::io;
 use std::io::Read;
 
 pub fn int_to_vec(number: i32) -> Vec<u8> {
@@ -10,9 +11,67 @@ pub fn int_to_vec(number: i32) -> Vec<u8> {
     result
 }
 
+/// Reads `len` bytes, growing the buffer only with the bytes actually read,
+/// so a corrupted length can't allocate more than the input holds.
 pub fn read_exact<T: Read>(reader: &mut T, len: usize) -> RdbResult<Vec<u8>> {
-    let mut buf = vec![0; len];
-    reader.read_exact(&mut buf)?;
+    let mut buf = Vec::new();
+    if reader.take(len as u64).read_to_end(&mut buf)? < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
 
     Ok(buf)
 }
+
+pub fn skip<T: Read>(reader: &mut T, len: u64) -> RdbOk {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+    if skipped < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    Ok(())
+}
+
+/// Keeps a copy of everything read through it
+pub struct Capture<'a, R: Read> {
+    inner: &'a mut R,
+    pub data: Vec<u8>,
+}
+
+impl<'a, R: Read> Capture<'a, R> {
+    pub fn new(inner: &'a mut R) -> Capture<'a, R> {
+        Capture {
+            inner,
+            data: Vec::new(),
+        }
+    }
+}
+
+impl<'a, R: Read> Read for Capture<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.data.extend_from_slice(&buf[..read]);
+
+        Ok(read)
+    }
+}
+
+/// Counts the bytes read through it
+pub struct Counter<'a, R: Read> {
+    inner: &'a mut R,
+    pub count: u64,
+}
+
+impl<'a, R: Read> Counter<'a, R> {
+    pub fn new(inner: &'a mut R) -> Counter<'a, R> {
+        Counter { inner, count: 0 }
+    }
+}
+
+impl<'a, R: Read> Read for Counter<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+
+        Ok(read)
+    }
+}
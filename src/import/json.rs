@@ -0,0 +1,185 @@
+//! Reading keys from JSON
+//!
+//! Two shapes are accepted. The one written by `formatter::JSON` holds an object per
+//! database, the n-th one being database n:
+//!
+//! ```json
+//! [{"name": "value", "queue": ["a", "b"], "user:1": {"name": "Jane"}}]
+//! ```
+//!
+//! It has no type information, so arrays become lists and objects become hashes.
+//! Sets, sorted sets and expiries need the typed shape, an array of keys:
+//!
+//! ```json
+//! [
+//!   {"db": 0, "key": "name", "type": "string", "value": "value", "expiry": 1700000000000},
+//!   {"key": "tags", "type": "set", "value": ["a", "b"]},
+//!   {"key": "scores", "type": "sortedset", "value": {"jane": 1.5, "joe": "2"}}
+//! ]
+//! ```
+//!
+//! `db` defaults to 0 and `expiry`, a Unix time in milliseconds, to none. Lists and sets
+//! are arrays, hashes and sorted sets objects. Scores can be numbers or strings.
+//! With `"binary": "escape"` or `"base64"`, as written by `formatter::TypedJSON`, the
+//! strings of a key are decoded accordingly, so binary data can be read back.
+
+use serde_json::{Map, Value as Json};
+use std::io::{Read, Write};
+
+use super::Keyspace;
+use crate::formatter::typed_json::Binary;
+use crate::types::{RdbError, RdbOk, RdbResult, Value};
+
+fn invalid(message: String) -> RdbError {
+    RdbError::Other(format!("Invalid JSON input: {}", message))
+}
+
+/// Reads keys in either JSON shape from `input` and writes them as a RDB file of `version`.
+pub fn json<R: Read, W: Write>(input: R, output: W, version: u32) -> RdbOk {
+    read_json(input)?.write(output, version)
+}
+
+/// Reads keys in either JSON shape from `input`.
+pub fn read_json<R: Read>(input: R) -> RdbResult<Keyspace> {
+    let json: Json = serde_json::from_reader(input).map_err(|e| invalid(e.to_string()))?;
+    let entries = match json {
+        Json::Array(entries) => entries,
+        _ => return Err(invalid("expected an array".into())),
+    };
+
+    let mut keyspace = Keyspace::new();
+    if !entries.is_empty() && entries.iter().all(is_typed) {
+        for entry in &entries {
+            read_typed(&mut keyspace, entry)?;
+        }
+    } else {
+        for (db, entry) in entries.iter().enumerate() {
+            let keys = match entry {
+                Json::Object(keys) => keys,
+                _ => return Err(invalid(format!("expected the object of database {}", db))),
+            };
+            for (key, value) in keys {
+                let value = untyped_value(key, value)?;
+                keyspace.insert(db as u32, key.as_bytes().to_vec(), value, None);
+            }
+        }
+    }
+
+    Ok(keyspace)
+}
+
+/// Whether `entry` is a key of the typed shape
+fn is_typed(entry: &Json) -> bool {
+    entry["key"].is_string() && entry["type"].is_string() && entry.get("value").is_some()
+}
+
+fn read_typed(keyspace: &mut Keyspace, entry: &Json) -> RdbOk {
+    let key = entry["key"].as_str().unwrap_or_default();
+    let typ = entry["type"].as_str().unwrap_or_default();
+    let json = &entry["value"];
+
+    let binary = match &entry["binary"] {
+        Json::Null => None,
+        binary => Some(
+            binary
+                .as_str()
+                .and_then(Binary::from_name)
+                .ok_or_else(|| invalid(format!("unknown binary encoding of {}", key)))?,
+        ),
+    };
+
+    let db = match &entry["db"] {
+        Json::Null => 0,
+        db => db
+            .as_u64()
+            .ok_or_else(|| invalid(format!("database of {} is not a number", key)))?
+            as u32,
+    };
+    let expiry = match &entry["expiry"] {
+        Json::Null => None,
+        expiry => Some(
+            expiry
+                .as_u64()
+                .ok_or_else(|| invalid(format!("expiry of {} is not a number", key)))?,
+        ),
+    };
+
+    let value = match typ {
+        "string" => Value::String(string(key, json, binary)?),
+        "list" => Value::List(strings(key, json, binary)?),
+        "set" => Value::Set(strings(key, json, binary)?),
+        "sortedset" | "zset" => Value::SortedSet(
+            object(key, json)?
+                .iter()
+                .map(|(member, score)| Ok((score_of(key, score)?, decode(key, member, binary)?)))
+                .collect::<RdbResult<_>>()?,
+        ),
+        "hash" => Value::Hash(pairs(key, object(key, json)?, binary)?),
+        _ => return Err(invalid(format!("unknown type {} of {}", typ, key))),
+    };
+    keyspace.insert(db, decode(key, key, binary)?, value, expiry);
+
+    Ok(())
+}
+
+fn untyped_value(key: &str, json: &Json) -> RdbResult<Value> {
+    Ok(match json {
+        Json::Array(_) => Value::List(strings(key, json, None)?),
+        Json::Object(fields) => Value::Hash(pairs(key, fields, None)?),
+        _ => Value::String(string(key, json, None)?),
+    })
+}
+
+/// `text` as is, or decoded with `binary`
+fn decode(key: &str, text: &str, binary: Option<Binary>) -> RdbResult<Vec<u8>> {
+    match binary {
+        None => Ok(text.as_bytes().to_vec()),
+        Some(binary) => binary
+            .decode(text)
+            .ok_or_else(|| invalid(format!("invalid {} string in {}", binary.name(), key))),
+    }
+}
+
+/// Accepts numbers as well, as hand-written files might hold them
+fn string(key: &str, json: &Json, binary: Option<Binary>) -> RdbResult<Vec<u8>> {
+    match json {
+        Json::String(value) => decode(key, value, binary),
+        Json::Number(value) => Ok(value.to_string().into_bytes()),
+        _ => Err(invalid(format!("expected a string in {}", key))),
+    }
+}
+
+fn strings(key: &str, json: &Json, binary: Option<Binary>) -> RdbResult<Vec<Vec<u8>>> {
+    match json {
+        Json::Array(values) => values
+            .iter()
+            .map(|value| string(key, value, binary))
+            .collect(),
+        _ => Err(invalid(format!("expected an array as value of {}", key))),
+    }
+}
+
+fn object<'a>(key: &str, json: &'a Json) -> RdbResult<&'a Map<String, Json>> {
+    json.as_object()
+        .ok_or_else(|| invalid(format!("expected an object as value of {}", key)))
+}
+
+fn pairs(
+    key: &str,
+    fields: &Map<String, Json>,
+    binary: Option<Binary>,
+) -> RdbResult<Vec<(Vec<u8>, Vec<u8>)>> {
+    fields
+        .iter()
+        .map(|(field, value)| Ok((decode(key, field, binary)?, string(key, value, binary)?)))
+        .collect()
+}
+
+fn score_of(key: &str, json: &Json) -> RdbResult<f64> {
+    let score = match json {
+        Json::Number(score) => score.as_f64(),
+        Json::String(score) => score.parse().ok(),
+        _ => None,
+    };
+    score.ok_or_else(|| invalid(format!("invalid score in {}", key)))
+}
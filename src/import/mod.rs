@@ -0,0 +1,80 @@
+//! Building RDB files from other representations of the data
+//!
+//! The data is collected in a `Keyspace` first, which is then written as a RDB file.
+//! `json` reads keys from JSON, `resp` replays the commands of AOF files.
+//!
+//! ```rust,no_run
+//! # use std::io::{BufReader, BufWriter};
+//! # use std::fs::File;
+//! let input = BufReader::new(File::open("dump.json").unwrap());
+//! let output = BufWriter::new(File::create("dump.rdb").unwrap());
+//! rdb::import::json(input, output, 9).unwrap();
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::types::{RdbOk, Value};
+use crate::writer::RdbWriter;
+
+pub use self::json::json;
+pub use self::resp::resp;
+
+pub mod json;
+pub mod resp;
+
+/// The keys of a database with their values and expiries
+type Database = BTreeMap<Vec<u8>, (Value, Option<u64>)>;
+
+/// The keys of all databases with their values and expiries, sorted by name
+#[derive(Debug, Default)]
+pub struct Keyspace {
+    databases: BTreeMap<u32, Database>,
+}
+
+impl Keyspace {
+    pub fn new() -> Keyspace {
+        Keyspace::default()
+    }
+
+    /// Sets `key` in database `db`, replacing any previous value.
+    /// `expiry` is a Unix time in milliseconds.
+    pub fn insert(&mut self, db: u32, key: Vec<u8>, value: Value, expiry: Option<u64>) {
+        self.databases
+            .entry(db)
+            .or_default()
+            .insert(key, (value, expiry));
+    }
+
+    /// Returns the value and expiry of `key` in database `db`.
+    pub fn get(&self, db: u32, key: &[u8]) -> Option<&(Value, Option<u64>)> {
+        self.databases.get(&db)?.get(key)
+    }
+
+    /// Number of keys in all databases
+    pub fn len(&self) -> usize {
+        self.databases.values().map(|keys| keys.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes all keys as a RDB file of `version`, leaving out empty databases.
+    pub fn write<W: Write>(&self, output: W, version: u32) -> RdbOk {
+        let mut writer = RdbWriter::new(output, version)?;
+        writer.write_header()?;
+
+        for (&db, keys) in self.databases.iter().filter(|(_, keys)| !keys.is_empty()) {
+            let expires = keys.values().filter(|(_, expiry)| expiry.is_some()).count();
+            writer.write_select_db(db)?;
+            writer.write_resizedb(keys.len() as u32, expires as u32)?;
+
+            for (key, (value, expiry)) in keys {
+                writer.write_object(key, value, *expiry)?;
+            }
+        }
+
+        writer.write_eof()
+    }
+}
@@ -0,0 +1,959 @@
+//! Replaying RESP commands, e.g. from AOF files or `redis-cli --pipe` input
+//!
+//! The common commands writing strings, lists, sets, sorted sets and hashes are applied
+//! to a keyspace in memory, together with the ones deleting, copying or moving keys,
+//! setting expiries and selecting, swapping or flushing databases. Other commands are
+//! rejected.
+//!
+//! AOF files starting with a RDB preamble are read as well, and so are the parts of a
+//! multi-part AOF when they are replayed in order. Like Redis, an incomplete command at
+//! the end, as left by a crash, is ignored.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::io::{BufRead, ErrorKind, Write};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::Keyspace;
+use crate::filter::Simple;
+use crate::formatter::{Control, Formatter};
+use crate::types::{EncodingType, RdbError, RdbOk, RdbResult, Value};
+
+/// Longest argument accepted, the default `proto-max-bulk-len` of Redis
+const MAX_BULK_LENGTH: usize = 512 * 1024 * 1024;
+
+#[derive(Clone)]
+enum Data {
+    String(Vec<u8>),
+    List(VecDeque<Vec<u8>>),
+    Set(BTreeSet<Vec<u8>>),
+    SortedSet(BTreeMap<Vec<u8>, f64>),
+    Hash(BTreeMap<Vec<u8>, Vec<u8>>),
+}
+
+impl Data {
+    fn is_empty(&self) -> bool {
+        match self {
+            Data::String(_) => false,
+            Data::List(list) => list.is_empty(),
+            Data::Set(set) => set.is_empty(),
+            Data::SortedSet(zset) => zset.is_empty(),
+            Data::Hash(hash) => hash.is_empty(),
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Data::String(value) => Value::String(value),
+            Data::List(list) => Value::List(list.into()),
+            Data::Set(set) => Value::Set(set.into_iter().collect()),
+            Data::SortedSet(zset) => Value::SortedSet(ranked(&zset)),
+            Data::Hash(hash) => Value::Hash(hash.into_iter().collect()),
+        }
+    }
+}
+
+/// Reads RESP commands from `input`, replays them and writes the resulting keys as a RDB
+/// file of `version`. Relative expiries are counted from now.
+pub fn resp<R: BufRead, W: Write>(input: R, output: W, version: u32) -> RdbOk {
+    let mut replay = Replay::new();
+    replay.read(input)?;
+    replay.into_keyspace().write(output, version)
+}
+
+fn error(message: String) -> RdbError {
+    RdbError::Other(message)
+}
+
+fn number<T: FromStr>(arg: &[u8]) -> RdbResult<T> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse().ok())
+        .ok_or_else(|| error(format!("Invalid number: {}", String::from_utf8_lossy(arg))))
+}
+
+/// Parses the time of an expiry set along with a value, which must be positive.
+fn set_time(arg: &[u8]) -> RdbResult<i64> {
+    match number(arg)? {
+        time if time > 0 => Ok(time),
+        time => Err(error(format!("Invalid expire time: {}", time))),
+    }
+}
+
+fn score(arg: &[u8]) -> RdbResult<f64> {
+    match &arg.to_ascii_lowercase()[..] {
+        b"+inf" | b"inf" => Ok(f64::INFINITY),
+        b"-inf" => Ok(f64::NEG_INFINITY),
+        _ => number(arg),
+    }
+}
+
+/// The members of a sorted set with their scores, ordered by score and then by member
+fn ranked(zset: &BTreeMap<Vec<u8>, f64>) -> Vec<(f64, Vec<u8>)> {
+    let mut members: Vec<(f64, Vec<u8>)> = zset
+        .iter()
+        .map(|(member, &score)| (score, member.clone()))
+        .collect();
+    members.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+    members
+}
+
+/// Parses a bound of `ZREMRANGEBYSCORE`, returning whether it is exclusive.
+fn score_bound(arg: &[u8]) -> RdbResult<(f64, bool)> {
+    match arg.strip_prefix(b"(") {
+        Some(bound) => Ok((score(bound)?, true)),
+        None => Ok((score(arg)?, false)),
+    }
+}
+
+/// Parses `LEFT` or `RIGHT`, returning whether it is the left side.
+fn side(arg: &[u8]) -> RdbResult<bool> {
+    match &arg.to_ascii_uppercase()[..] {
+        b"LEFT" => Ok(true),
+        b"RIGHT" => Ok(false),
+        _ => Err(error(format!(
+            "Expected LEFT or RIGHT, got {}",
+            String::from_utf8_lossy(arg)
+        ))),
+    }
+}
+
+/// Resolves `start` and `stop`, which count from the end if negative, to the inclusive
+/// range of indexes they select in a sequence of `length` elements.
+fn index_range(length: usize, start: i64, stop: i64) -> Option<(usize, usize)> {
+    let length = length as i64;
+    let index = |index: i64| if index < 0 { length + index } else { index };
+    let (start, stop) = (index(start).max(0), index(stop).min(length - 1));
+    if start > stop {
+        None
+    } else {
+        Some((start as usize, stop as usize))
+    }
+}
+
+/// Returns `key` with the remaining arguments, checking there are at least `min` of them.
+fn key_and_args(args: &[Vec<u8>], min: usize) -> RdbResult<(&[u8], &[Vec<u8>])> {
+    if args.len() < 2 + min {
+        return Err(error(format!(
+            "Wrong number of arguments for {}",
+            String::from_utf8_lossy(&args[0])
+        )));
+    }
+    Ok((&args[1], &args[2..]))
+}
+
+/// Returns the arguments in pairs, failing if there is an odd number of them
+fn pairs(args: &[Vec<u8>]) -> RdbResult<std::slice::ChunksExact<'_, Vec<u8>>> {
+    if args.is_empty() || !args.len().is_multiple_of(2) {
+        return Err(error("Arguments are not in pairs".into()));
+    }
+    Ok(args.chunks_exact(2))
+}
+
+/// Reads a line without its line break. Returns `None` at the end of the input or if the
+/// line is incomplete.
+fn read_line<R: BufRead>(input: &mut R) -> RdbResult<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    input.read_until(b'\n', &mut line)?;
+    if !line.ends_with(b"\n") {
+        return Ok(None);
+    }
+    line.pop();
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+/// Reads the next command, either a RESP array of bulk strings or an inline command.
+/// Returns `None` at the end of the input or if the command is incomplete.
+fn read_command<R: BufRead>(input: &mut R) -> RdbResult<Option<Vec<Vec<u8>>>> {
+    loop {
+        let line = match read_line(input)? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        // Lines starting with # are annotations, such as timestamps
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        if line[0] != b'*' {
+            let args = line
+                .split(u8::is_ascii_whitespace)
+                .filter(|arg| !arg.is_empty());
+            return Ok(Some(args.map(|arg| arg.to_vec()).collect()));
+        }
+
+        let count: usize = number(&line[1..])?;
+        let mut args = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            let header = match read_line(input)? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+            if header.first() != Some(&b'$') {
+                return Err(error("Expected a bulk string".into()));
+            }
+            let length: usize = number(&header[1..])?;
+            if length > MAX_BULK_LENGTH {
+                return Err(error(format!("Argument of {} bytes is too long", length)));
+            }
+
+            let mut arg = vec![0; length + 2];
+            match input.read_exact(&mut arg) {
+                Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                result => result?,
+            }
+            if !arg.ends_with(b"\r\n") {
+                return Err(error("Bulk string does not end with a line break".into()));
+            }
+            arg.truncate(length);
+            args.push(arg);
+        }
+        if !args.is_empty() {
+            return Ok(Some(args));
+        }
+    }
+}
+
+/// The keys of a database with their values and expiries
+type Database = HashMap<Vec<u8>, (Data, Option<u64>)>;
+
+/// Applies commands to a keyspace in memory
+pub struct Replay {
+    databases: BTreeMap<u32, Database>,
+    db: u32,
+    now: u64,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Replay {
+    pub fn new() -> Replay {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_millis() as u64)
+            .unwrap_or(0);
+
+        Replay {
+            databases: BTreeMap::new(),
+            db: 0,
+            now,
+        }
+    }
+
+    /// Sets the time relative expiries, as set by `EXPIRE` or `SETEX`, are counted from,
+    /// as a Unix time in milliseconds. Defaults to now.
+    pub fn set_time(&mut self, now: u64) {
+        self.now = now;
+    }
+
+    /// Reads and applies all commands of `input`, after its RDB preamble if it has one.
+    pub fn read<R: BufRead>(&mut self, mut input: R) -> RdbOk {
+        if input.fill_buf()?.starts_with(b"REDIS") {
+            crate::parse(&mut input, Preamble::new(self), Simple::new())?;
+            self.db = 0;
+        }
+
+        let mut index = 0;
+        while let Some(args) = read_command(&mut input)? {
+            index += 1;
+            self.apply(&args).map_err(|e| {
+                error(format!(
+                    "Command {} ({}) failed: {}",
+                    index,
+                    String::from_utf8_lossy(&args[0]),
+                    e
+                ))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the keys with their values and expiries.
+    /// Keys that expired before the time set with `set_time` are left out.
+    pub fn into_keyspace(self) -> Keyspace {
+        let now = self.now;
+        let mut keyspace = Keyspace::new();
+        for (db, keys) in self.databases {
+            for (key, (data, expiry)) in keys {
+                if expiry.is_some_and(|expiry| expiry < now) {
+                    continue;
+                }
+                keyspace.insert(db, key, data.into_value(), expiry);
+            }
+        }
+        keyspace
+    }
+
+    fn keys(&mut self) -> &mut Database {
+        self.databases.entry(self.db).or_default()
+    }
+
+    /// Returns the value of `key`, creating it with `create` if it does not exist
+    fn entry(&mut self, key: &[u8], create: fn() -> Data) -> &mut Data {
+        &mut self
+            .keys()
+            .entry(key.to_vec())
+            .or_insert_with(|| (create(), None))
+            .0
+    }
+
+    fn set_string(&mut self, key: &[u8], value: Vec<u8>, expiry: Option<u64>) {
+        self.keys()
+            .insert(key.to_vec(), (Data::String(value), expiry));
+    }
+
+    fn string(&mut self, key: &[u8]) -> RdbResult<&mut Vec<u8>> {
+        match self.entry(key, || Data::String(Vec::new())) {
+            Data::String(value) => Ok(value),
+            _ => Err(wrong_type(key)),
+        }
+    }
+
+    fn list(&mut self, key: &[u8]) -> RdbResult<&mut VecDeque<Vec<u8>>> {
+        match self.entry(key, || Data::List(VecDeque::new())) {
+            Data::List(list) => Ok(list),
+            _ => Err(wrong_type(key)),
+        }
+    }
+
+    fn set(&mut self, key: &[u8]) -> RdbResult<&mut BTreeSet<Vec<u8>>> {
+        match self.entry(key, || Data::Set(BTreeSet::new())) {
+            Data::Set(set) => Ok(set),
+            _ => Err(wrong_type(key)),
+        }
+    }
+
+    fn sorted_set(&mut self, key: &[u8]) -> RdbResult<&mut BTreeMap<Vec<u8>, f64>> {
+        match self.entry(key, || Data::SortedSet(BTreeMap::new())) {
+            Data::SortedSet(zset) => Ok(zset),
+            _ => Err(wrong_type(key)),
+        }
+    }
+
+    fn hash(&mut self, key: &[u8]) -> RdbResult<&mut BTreeMap<Vec<u8>, Vec<u8>>> {
+        match self.entry(key, || Data::Hash(BTreeMap::new())) {
+            Data::Hash(hash) => Ok(hash),
+            _ => Err(wrong_type(key)),
+        }
+    }
+
+    fn exists(&mut self, key: &[u8]) -> bool {
+        self.keys().contains_key(key)
+    }
+
+    /// Applies one command, given with its arguments.
+    pub fn apply(&mut self, args: &[Vec<u8>]) -> RdbOk {
+        let name = match args.first() {
+            Some(name) => String::from_utf8_lossy(name).to_ascii_uppercase(),
+            None => return Ok(()),
+        };
+
+        match &name[..] {
+            "MULTI" | "EXEC" | "PING" => {}
+            "SELECT" => self.db = number(key_and_args(args, 0)?.0)?,
+            "FLUSHDB" => {
+                self.databases.remove(&self.db);
+            }
+            "FLUSHALL" => self.databases.clear(),
+            "DEL" | "UNLINK" => {
+                for key in &args[1..] {
+                    self.keys().remove(key);
+                }
+            }
+            "RENAME" => {
+                let (key, rest) = key_and_args(args, 1)?;
+                let entry = self
+                    .keys()
+                    .remove(key)
+                    .ok_or_else(|| error("No such key".into()))?;
+                self.keys().insert(rest[0].clone(), entry);
+            }
+            "COPY" => {
+                let (source, rest) = key_and_args(args, 1)?;
+                let (db, replace) = self.copy_options(&rest[1..])?;
+                if let Some(entry) = self.keys().get(source).cloned() {
+                    let keys = self.databases.entry(db).or_default();
+                    if replace || !keys.contains_key(&rest[0]) {
+                        keys.insert(rest[0].clone(), entry);
+                    }
+                }
+            }
+            "MOVE" => {
+                let (key, rest) = key_and_args(args, 1)?;
+                let db: u32 = number(&rest[0])?;
+                let exists = |keys: &Database| keys.contains_key(key);
+                if db != self.db && !self.databases.get(&db).is_some_and(exists) {
+                    if let Some(entry) = self.keys().remove(key) {
+                        self.databases
+                            .entry(db)
+                            .or_default()
+                            .insert(key.to_vec(), entry);
+                    }
+                }
+            }
+            "SWAPDB" => {
+                let (first, rest) = key_and_args(args, 1)?;
+                let (first, second): (u32, u32) = (number(first)?, number(&rest[0])?);
+                let first_keys = self.databases.remove(&first);
+                if let Some(keys) = self.databases.remove(&second) {
+                    self.databases.insert(first, keys);
+                }
+                if let Some(keys) = first_keys {
+                    self.databases.insert(second, keys);
+                }
+            }
+
+            "EXPIRE" | "PEXPIRE" | "EXPIREAT" | "PEXPIREAT" => {
+                let (key, rest) = key_and_args(args, 1)?;
+                let expiry = self.expiry(
+                    number(&rest[0])?,
+                    !name.starts_with('P'),
+                    !name.ends_with("AT"),
+                )?;
+                self.expire(key, expiry, &rest[1..]);
+            }
+            "PERSIST" => {
+                let (key, _) = key_and_args(args, 0)?;
+                if let Some(entry) = self.keys().get_mut(key) {
+                    entry.1 = None;
+                }
+            }
+
+            "SET" => {
+                let (key, rest) = key_and_args(args, 1)?;
+                self.set_command(key, &rest[0], &rest[1..])?;
+            }
+            "SETNX" => {
+                let (key, rest) = key_and_args(args, 1)?;
+                if !self.exists(key) {
+                    self.set_string(key, rest[0].clone(), None);
+                }
+            }
+            "SETEX" | "PSETEX" => {
+                let (key, rest) = key_and_args(args, 2)?;
+                let expiry = self.expiry(set_time(&rest[0])?, name == "SETEX", true)?;
+                self.set_string(key, rest[1].clone(), Some(expiry as u64));
+            }
+            "MSET" => {
+                for pair in pairs(&args[1..])? {
+                    self.set_string(&pair[0], pair[1].clone(), None);
+                }
+            }
+            "APPEND" => {
+                let (key, rest) = key_and_args(args, 1)?;
+                self.string(key)?.extend_from_slice(&rest[0]);
+            }
+            "INCR" | "DECR" | "INCRBY" | "DECRBY" => {
+                let (key, rest) = key_and_args(args, if name.ends_with("BY") { 1 } else { 0 })?;
+                let by: i64 = match rest.first() {
+                    Some(by) => number(by)?,
+                    None => 1,
+                };
+                let by = if name.starts_with("DECR") {
+                    by.checked_neg()
+                        .ok_or_else(|| error("Decrement would overflow".into()))?
+                } else {
+                    by
+                };
+                let value = self.string(key)?;
+                let current: i64 = if value.is_empty() { 0 } else { number(value)? };
+                *value = current
+                    .checked_add(by)
+                    .ok_or_else(|| error("Increment would overflow".into()))?
+                    .to_string()
+                    .into_bytes();
+            }
+
+            "RPUSH" | "LPUSH" => {
+                let (key, rest) = key_and_args(args, 1)?;
+                let list = self.list(key)?;
+                for value in rest {
+                    if name == "RPUSH" {
+                        list.push_back(value.clone());
+                    } else {
+                        list.push_front(value.clone());
+                    }
+                }
+            }
+            "RPOP" | "LPOP" => {
+                let (key, rest) = key_and_args(args, 0)?;
+                let count: usize = match rest.first() {
+                    Some(count) => number(count)?,
+                    None => 1,
+                };
+                if self.exists(key) {
+                    let list = self.list(key)?;
+                    for _ in 0..count {
+                        if name == "RPOP" {
+                            list.pop_back();
+                        } else {
+                            list.pop_front();
+                        }
+                    }
+                }
+            }
+            "LTRIM" => {
+                let (key, rest) = key_and_args(args, 2)?;
+                let (start, stop): (i64, i64) = (number(&rest[0])?, number(&rest[1])?);
+                if self.exists(key) {
+                    let list = self.list(key)?;
+                    match index_range(list.len(), start, stop) {
+                        Some((start, stop)) => {
+                            list.truncate(stop + 1);
+                            list.drain(..start);
+                        }
+                        None => list.clear(),
+                    }
+                }
+            }
+            "LSET" => {
+                let (key, rest) = key_and_args(args, 2)?;
+                let index: i64 = number(&rest[0])?;
+                if !self.exists(key) {
+                    return Err(error("No such key".into()));
+                }
+                let list = self.list(key)?;
+                let index = match index_range(list.len(), index, index) {
+                    Some((index, _)) if index < list.len() => index,
+                    _ => return Err(error("Index out of range".into())),
+                };
+                list[index] = rest[1].clone();
+            }
+            "LREM" => {
+                let (key, rest) = key_and_args(args, 2)?;
+                let count: i64 = number(&rest[0])?;
+                if self.exists(key) {
+                    let list = self.list(key)?;
+                    let limit = match count {
+                        0 => usize::MAX,
+                        count => count.unsigned_abs() as usize,
+                    };
+                    // A negative count removes the last occurrences
+                    if count < 0 {
+                        list.make_contiguous().reverse();
+                    }
+                    let mut removed = 0;
+                    list.retain(|value| {
+                        let remove = removed < limit && *value == rest[1];
+                        removed += remove as usize;
+                        !remove
+                    });
+                    if count < 0 {
+                        list.make_contiguous().reverse();
+                    }
+                }
+            }
+            "LINSERT" => {
+                let (key, rest) = key_and_args(args, 3)?;
+                let after = match &rest[0].to_ascii_uppercase()[..] {
+                    b"BEFORE" => false,
+                    b"AFTER" => true,
+                    _ => return Err(error("Expected BEFORE or AFTER".into())),
+                };
+                if self.exists(key) {
+                    let list = self.list(key)?;
+                    if let Some(index) = list.iter().position(|value| *value == rest[1]) {
+                        list.insert(index + after as usize, rest[2].clone());
+                    }
+                }
+            }
+            "LMOVE" | "RPOPLPUSH" => {
+                let (source, rest) = key_and_args(args, if name == "LMOVE" { 3 } else { 1 })?;
+                let (from_left, to_left) = if name == "LMOVE" {
+                    (side(&rest[1])?, side(&rest[2])?)
+                } else {
+                    (false, true)
+                };
+                if self.exists(source) {
+                    let list = self.list(source)?;
+                    let value = if from_left {
+                        list.pop_front()
+                    } else {
+                        list.pop_back()
+                    };
+                    if let Some(value) = value {
+                        let list = self.list(&rest[0])?;
+                        if to_left {
+                            list.push_front(value);
+                        } else {
+                            list.push_back(value);
+                        }
+                    }
+                }
+            }
+
+            "SADD" => {
+                let (key, rest) = key_and_args(args, 1)?;
+                self.set(key)?.extend(rest.iter().cloned());
+            }
+            "SREM" => {
+                let (key, rest) = key_and_args(args, 1)?;
+                let set = self.set(key)?;
+                for member in rest {
+                    set.remove(member);
+                }
+            }
+            "SMOVE" => {
+                let (source, rest) = key_and_args(args, 2)?;
+                if self.exists(source) && self.set(source)?.remove(&rest[1]) {
+                    self.set(&rest[0])?.insert(rest[1].clone());
+                }
+            }
+
+            "HSET" | "HMSET" => {
+                let (key, rest) = key_and_args(args, 2)?;
+                let fields = pairs(rest)?;
+                let hash = self.hash(key)?;
+                for pair in fields {
+                    hash.insert(pair[0].clone(), pair[1].clone());
+                }
+            }
+            "HSETNX" => {
+                let (key, rest) = key_and_args(args, 2)?;
+                self.hash(key)?
+                    .entry(rest[0].clone())
+                    .or_insert_with(|| rest[1].clone());
+            }
+            "HDEL" => {
+                let (key, rest) = key_and_args(args, 1)?;
+                let hash = self.hash(key)?;
+                for field in rest {
+                    hash.remove(field);
+                }
+            }
+            "HINCRBY" => {
+                let (key, rest) = key_and_args(args, 2)?;
+                let by: i64 = number(&rest[1])?;
+                let value = self.hash(key)?.entry(rest[0].clone()).or_default();
+                let current: i64 = if value.is_empty() { 0 } else { number(value)? };
+                *value = current
+                    .checked_add(by)
+                    .ok_or_else(|| error("Increment would overflow".into()))?
+                    .to_string()
+                    .into_bytes();
+            }
+
+            "ZADD" => {
+                let (key, rest) = key_and_args(args, 2)?;
+                self.zadd(key, rest)?;
+            }
+            "ZINCRBY" => {
+                let (key, rest) = key_and_args(args, 2)?;
+                let by = score(&rest[0])?;
+                *self.sorted_set(key)?.entry(rest[1].clone()).or_insert(0.0) += by;
+            }
+            "ZREM" => {
+                let (key, rest) = key_and_args(args, 1)?;
+                let zset = self.sorted_set(key)?;
+                for member in rest {
+                    zset.remove(member);
+                }
+            }
+            "ZPOPMIN" | "ZPOPMAX" => {
+                let (key, rest) = key_and_args(args, 0)?;
+                let count: usize = match rest.first() {
+                    Some(count) => number(count)?,
+                    None => 1,
+                };
+                if self.exists(key) {
+                    let zset = self.sorted_set(key)?;
+                    let mut members = ranked(zset);
+                    if name == "ZPOPMAX" {
+                        members.reverse();
+                    }
+                    for (_, member) in members.iter().take(count) {
+                        zset.remove(member);
+                    }
+                }
+            }
+            "ZREMRANGEBYRANK" => {
+                let (key, rest) = key_and_args(args, 2)?;
+                let (start, stop): (i64, i64) = (number(&rest[0])?, number(&rest[1])?);
+                if self.exists(key) {
+                    let zset = self.sorted_set(key)?;
+                    let members = ranked(zset);
+                    if let Some((start, stop)) = index_range(members.len(), start, stop) {
+                        for (_, member) in &members[start..=stop] {
+                            zset.remove(member);
+                        }
+                    }
+                }
+            }
+            "ZREMRANGEBYSCORE" => {
+                let (key, rest) = key_and_args(args, 2)?;
+                let (min, max) = (score_bound(&rest[0])?, score_bound(&rest[1])?);
+                if self.exists(key) {
+                    self.sorted_set(key)?.retain(|_, &mut score| {
+                        let above = if min.1 { score > min.0 } else { score >= min.0 };
+                        let below = if max.1 { score < max.0 } else { score <= max.0 };
+                        !(above && below)
+                    });
+                }
+            }
+
+            _ => return Err(error(format!("Unsupported command {}", name))),
+        }
+
+        // Like Redis, collections are deleted once their last element is removed
+        if let Some(key) = args.get(1) {
+            let keys = self.keys();
+            if keys.get(&key[..]).is_some_and(|(data, _)| data.is_empty()) {
+                keys.remove(&key[..]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies `SET key value` with its options, such as `EX` or `NX`.
+    fn set_command(&mut self, key: &[u8], value: &[u8], options: &[Vec<u8>]) -> RdbOk {
+        let mut expiry = None;
+        let mut keep_ttl = false;
+        let mut options = options.iter();
+
+        while let Some(option) = options.next() {
+            let option = option.to_ascii_uppercase();
+            let mut time = || -> RdbResult<i64> {
+                set_time(options.next().ok_or_else(|| error("Missing time".into()))?)
+            };
+            match &option[..] {
+                b"EX" => expiry = Some(self.expiry(time()?, true, true)? as u64),
+                b"PX" => expiry = Some(self.expiry(time()?, false, true)? as u64),
+                b"EXAT" => expiry = Some(self.expiry(time()?, true, false)? as u64),
+                b"PXAT" => expiry = Some(time()? as u64),
+                b"KEEPTTL" => keep_ttl = true,
+                b"NX" if self.exists(key) => return Ok(()),
+                b"XX" if !self.exists(key) => return Ok(()),
+                b"NX" | b"XX" | b"GET" => {}
+                _ => {
+                    return Err(error(format!(
+                        "Unsupported option {}",
+                        String::from_utf8_lossy(&option)
+                    )))
+                }
+            }
+        }
+
+        if keep_ttl {
+            expiry = self.keys().get(key).and_then(|(_, expiry)| *expiry);
+        }
+        self.set_string(key, value.to_vec(), expiry);
+        Ok(())
+    }
+
+    /// Parses the options of `COPY`, returning the target database and whether an
+    /// existing key is replaced.
+    fn copy_options(&self, options: &[Vec<u8>]) -> RdbResult<(u32, bool)> {
+        let mut db = self.db;
+        let mut replace = false;
+        let mut options = options.iter();
+
+        while let Some(option) = options.next() {
+            match &option.to_ascii_uppercase()[..] {
+                b"DB" => {
+                    db = number(
+                        options
+                            .next()
+                            .ok_or_else(|| error("Missing database".into()))?,
+                    )?
+                }
+                b"REPLACE" => replace = true,
+                _ => {
+                    return Err(error(format!(
+                        "Unsupported option {}",
+                        String::from_utf8_lossy(option)
+                    )))
+                }
+            }
+        }
+
+        Ok((db, replace))
+    }
+
+    /// Returns the Unix time in milliseconds an expiry of `time` stands for, with `time`
+    /// given in seconds if `seconds` and counted from now if `relative`.
+    fn expiry(&self, time: i64, seconds: bool, relative: bool) -> RdbResult<i64> {
+        let invalid = || error(format!("Invalid expire time: {}", time));
+        let time = if seconds {
+            time.checked_mul(1000).ok_or_else(invalid)?
+        } else {
+            time
+        };
+        if relative {
+            time.checked_add(self.now as i64).ok_or_else(invalid)
+        } else {
+            Ok(time)
+        }
+    }
+
+    /// Sets the expiry of `key` if it exists, considering the options `NX`, `XX`, `GT` and `LT`.
+    /// An expiry that is not in the future deletes the key.
+    fn expire(&mut self, key: &[u8], expiry: i64, options: &[Vec<u8>]) {
+        let now = self.now as i64;
+        let entry = match self.keys().get_mut(key) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        let applies = options.iter().all(|option| {
+            match &option.to_ascii_uppercase()[..] {
+                b"NX" => entry.1.is_none(),
+                b"XX" => entry.1.is_some(),
+                // Keys without an expiry count as expiring never
+                b"GT" => entry.1.is_some_and(|current| expiry > current as i64),
+                b"LT" => entry.1.is_none_or(|current| expiry < current as i64),
+                _ => true,
+            }
+        });
+        if applies && expiry <= now {
+            self.keys().remove(key);
+        } else if applies {
+            entry.1 = Some(expiry as u64);
+        }
+    }
+
+    /// Applies `ZADD` with its options, such as `NX` or `INCR`.
+    fn zadd(&mut self, key: &[u8], args: &[Vec<u8>]) -> RdbOk {
+        let flags = args
+            .iter()
+            .take_while(|arg| score(arg).is_err())
+            .map(|arg| arg.to_ascii_uppercase())
+            .collect::<Vec<_>>();
+        let has = |flag: &[u8]| flags.iter().any(|f| f == flag);
+        let (nx, xx, gt, lt, incr) = (has(b"NX"), has(b"XX"), has(b"GT"), has(b"LT"), has(b"INCR"));
+
+        let zset = self.sorted_set(key)?;
+        for pair in pairs(&args[flags.len()..])? {
+            let mut score = score(&pair[0])?;
+            let current = zset.get(&pair[1]).copied();
+            if incr {
+                score += current.unwrap_or(0.0);
+            }
+
+            let applies = match current {
+                None => !xx,
+                Some(current) => {
+                    let ignored = (gt && score <= current) || (lt && score >= current);
+                    !nx && !ignored
+                }
+            };
+            if applies {
+                zset.insert(pair[1].clone(), score);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn wrong_type(key: &[u8]) -> RdbError {
+    error(format!(
+        "Key {} holds a value of another type",
+        String::from_utf8_lossy(key)
+    ))
+}
+
+/// Adds the keys of a RDB preamble to a `Replay`
+struct Preamble<'a> {
+    replay: &'a mut Replay,
+}
+
+impl<'a> Preamble<'a> {
+    fn new(replay: &'a mut Replay) -> Preamble<'a> {
+        Preamble { replay }
+    }
+
+    fn start(&mut self, key: &[u8], data: Data, expiry: Option<u64>) -> RdbResult<Control> {
+        self.replay.keys().insert(key.to_vec(), (data, expiry));
+        Ok(Control::Continue)
+    }
+}
+
+impl Formatter for Preamble<'_> {
+    fn start_database(&mut self, db_index: u32) -> RdbResult<()> {
+        self.replay.db = db_index;
+        Ok(())
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<Control> {
+        self.start(key, Data::String(value.to_vec()), expiry)
+    }
+
+    fn raw_object(
+        &mut self,
+        key: &[u8],
+        _value_type: u8,
+        _raw: &[u8],
+        _expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        Err(error(format!(
+            "Key {} holds a stream or module value, which can't be imported",
+            String::from_utf8_lossy(key)
+        )))
+    }
+
+    fn start_hash(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Data::Hash(BTreeMap::new()), expiry)
+    }
+    fn hash_element(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
+        self.replay
+            .hash(key)?
+            .insert(field.to_vec(), value.to_vec());
+        Ok(Control::Continue)
+    }
+
+    fn start_set(
+        &mut self,
+        key: &[u8],
+        _cardinality: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Data::Set(BTreeSet::new()), expiry)
+    }
+    fn set_element(&mut self, key: &[u8], member: &[u8]) -> RdbResult<Control> {
+        self.replay.set(key)?.insert(member.to_vec());
+        Ok(Control::Continue)
+    }
+
+    fn start_list(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Data::List(VecDeque::new()), expiry)
+    }
+    fn list_element(&mut self, key: &[u8], value: &[u8]) -> RdbResult<Control> {
+        self.replay.list(key)?.push_back(value.to_vec());
+        Ok(Control::Continue)
+    }
+
+    fn start_sorted_set(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.start(key, Data::SortedSet(BTreeMap::new()), expiry)
+    }
+    fn sorted_set_element(&mut self, key: &[u8], score: f64, member: &[u8]) -> RdbResult<Control> {
+        self.replay.sorted_set(key)?.insert(member.to_vec(), score);
+        Ok(Control::Continue)
+    }
+}
@@ -0,0 +1,152 @@
+//! Fast inspection of the metadata of a RDB file
+//!
+//! `inspect` reads only the header, the aux fields and the database hints
+//! and seeks over all keys and values without decoding them.
+//!
+//! ```rust,no_run
+//! # use std::io::BufReader;
+//! # use std::fs::File;
+//! let file = BufReader::new(File::open("dump.rdb").unwrap());
+//! let summary = rdb::inspect(file).unwrap();
+//! println!("Written by Redis {:?}", summary.aux.redis_ver);
+//! for db in &summary.databases {
+//!     println!("db{}: {} keys", db.index, db.keys);
+//! }
+//! ```
+
+use byteorder::ReadBytesExt;
+use std::io::{Read, Seek};
+
+use crate::builder::Limits;
+use crate::constants::op_code;
+use crate::helper;
+use crate::parser::{
+    read_blob, read_blob_header, read_length, read_length_u64, read_object_layout, read_version,
+    skip_nested, verify_magic, ObjectLayout,
+};
+use crate::types::{AuxInfo, RdbResult};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DatabaseSummary {
+    pub index: u32,
+    /// Number of keys according to the RESIZEDB hint
+    pub size_hint: Option<u32>,
+    /// Number of keys with an expiry according to the RESIZEDB hint
+    pub expires_hint: Option<u32>,
+    /// Number of keys actually found
+    pub keys: u64,
+    /// Number of keys with an expiry actually found
+    pub expires: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub version: u32,
+    pub aux: AuxInfo,
+    pub databases: Vec<DatabaseSummary>,
+}
+
+fn seek_blob<R: Read + Seek>(input: &mut R) -> RdbResult<()> {
+    let payload_length = read_blob_header(input, &Limits::default())?;
+    input.seek_relative(payload_length as i64)?;
+
+    Ok(())
+}
+
+fn seek_object<R: Read + Seek>(input: &mut R, enc_type: u8) -> RdbResult<()> {
+    match read_object_layout(input, enc_type, &Limits::default())? {
+        ObjectLayout::Blobs(count) => {
+            for _ in 0..count {
+                seek_blob(input)?;
+            }
+        }
+        ObjectLayout::ScoredBlobs(count) => {
+            for _ in 0..count {
+                seek_blob(input)?;
+                input.seek_relative(8)?;
+            }
+        }
+        ObjectLayout::Nodes(count) => {
+            for _ in 0..count {
+                read_length_u64(input)?;
+                seek_blob(input)?;
+            }
+        }
+        ObjectLayout::Nested => skip_nested(input, enc_type, &Limits::default())?,
+    }
+
+    Ok(())
+}
+
+/// Reads the metadata of a RDB file, skipping over all values.
+pub fn inspect<R: Read + Seek>(mut input: R) -> RdbResult<Summary> {
+    verify_magic(&mut input)?;
+
+    let mut summary = Summary {
+        version: read_version(&mut input)?,
+        ..Summary::default()
+    };
+    let mut has_expiry = false;
+
+    loop {
+        let next_op = input.read_u8()?;
+
+        match next_op {
+            op_code::EOF => break,
+            op_code::SELECTDB => {
+                let index = read_length(&mut input)?;
+                summary.databases.push(DatabaseSummary {
+                    index,
+                    ..DatabaseSummary::default()
+                });
+            }
+            op_code::EXPIRETIME_MS => {
+                helper::skip(&mut input, 8)?;
+                has_expiry = true;
+            }
+            op_code::EXPIRETIME => {
+                helper::skip(&mut input, 4)?;
+                has_expiry = true;
+            }
+            op_code::RESIZEDB => {
+                let db_size = read_length(&mut input)?;
+                let expires_size = read_length(&mut input)?;
+
+                if let Some(db) = summary.databases.last_mut() {
+                    db.size_hint = Some(db_size);
+                    db.expires_hint = Some(expires_size);
+                }
+            }
+            op_code::AUX => {
+                let auxkey = read_blob(&mut input)?;
+                let auxval = read_blob(&mut input)?;
+
+                summary.aux.update(&auxkey, &auxval);
+            }
+            op_code::IDLE => {
+                read_length_u64(&mut input)?;
+            }
+            op_code::FREQ => {
+                helper::skip(&mut input, 1)?;
+            }
+            op_code::MODULE_AUX => skip_nested(&mut input, next_op, &Limits::default())?,
+            op_code::FUNCTION2 => seek_blob(&mut input)?,
+            _ => {
+                seek_blob(&mut input)?;
+                seek_object(&mut input, next_op)?;
+
+                if summary.databases.is_empty() {
+                    summary.databases.push(DatabaseSummary::default());
+                }
+                let db = summary.databases.last_mut().unwrap();
+                db.keys += 1;
+                if has_expiry {
+                    db.expires += 1;
+                }
+                has_expiry = false;
+            }
+        }
+    }
+
+    Ok(summary)
+}
@@ -30,21 +30,81 @@
 //! rdb::parse(reader, rdb::formatter::JSON::new(), rdb::filter::Simple::new());
 //! ```
 //!
+//! # Inspection
+//!
+//! To just find out which Redis version wrote a file, when it was written and how many keys
+//! each database contains, `inspect` reads the metadata and seeks over all values.
+//!
+//! # Push-based parsing
+//!
+//! When the data arrives in chunks, e.g. from a socket driven by an event loop,
+//! `PushParser` can be fed with these chunks as they come in.
+//! It keeps incomplete data around and calls the formatter for every entry
+//! as soon as it is completely available.
+//!
+//! # Writing
+//!
+//! `RdbWriter` writes RDB files, including the aux fields, database hints, expiries
+//! and the CRC64 checksum. The `Rdb` formatter uses it to turn the parsed data
+//! back into a RDB file Redis can load.
+//!
+//! `transcode` uses it to convert a RDB file to another version, e.g. to restore
+//! a snapshot of Redis 7 with an older Redis.
+//!
+//! To extract some keys into a smaller RDB file, `copy` copies the keys matching
+//! a filter without decoding their values.
+//! `merge::Merge` combines several RDB files into one in the same way, and
+//! `cluster::Split` distributes the keys of one RDB file to the nodes of a Redis Cluster.
+//!
+//! `generate::Generator` writes RDB files of any size with random data, e.g. for benchmarks.
+//! `import::json` turns JSON, e.g. as written by the JSON formatter, back into a RDB file,
+//! and `import::resp` replays the commands of AOF files into one.
+//!
+//! # Transforming keys
+//!
+//! `transform::Transformed` sits between the parser and any formatter and changes keys
+//! on the way, e.g. renaming them with `transform::Rename`, rewriting their expiries with
+//! `transform::Expiry` or replacing their values with `transform::Anonymize`.
+//!
 //! # Formatter
 //!
-//! rdb-rs brings 4 pre-defined formatters, which can be used:
+//! rdb-rs brings 9 pre-defined formatters, which can be used:
 //!
 //! * `PlainFormatter`: Just plain output for testing
 //! * `JSONFormatter`: JSON-encoded output
+//! * `TypedJSON`: A JSON object per key with its type, encoding and expiry,
+//!   keeping binary data intact
+//! * `NDJSON`: A JSON object per line and key, for tools reading line by line
+//! * `Memory`: A CSV report of the memory each key takes in Redis
+//! * `Biggest`: The top keys of every database and type by size, memory and elements
 //! * `NilFormatter`: Surpresses all output
 //! * `ProtocolFormatter`: Formats the data in [RESP](http://redis.io/topics/protocol),
-//! the Redis Serialization Protocol
+//!   the Redis Serialization Protocol
+//! * `Rdb`: Writes a new RDB file, e.g. to keep only the keys passing a filter
 //!
 //! These formatters adhere to the `RdbParseFormatter` trait
 //! and supply a method for each possible datatype or opcode.
 //! Its up to the formatter to correctly handle all provided data such as
 //! lists, sets, hashes, expires and metadata.
 //!
+//! `JSON`, `TypedJSON`, `NDJSON`, `Memory`, `Biggest`, `Plain` and `Protocol` write to stdout when created with `new`.
+//! `with_writer` makes them write to any `Write`, e.g. a file or a buffer, through
+//! a `BufWriter` of their own. `RdbParser::into_formatter` hands the formatter back
+//! after parsing, and its `into_inner` the writer:
+//!
+//! ```rust,no_run
+//! # use std::fs::File;
+//! let input = File::open("dump.rdb").unwrap();
+//! let formatter = rdb::formatter::JSON::with_writer(Vec::new());
+//! let mut parser = rdb::RdbParser::new(input, formatter, rdb::filter::Simple::new());
+//! parser.parse().unwrap();
+//! let json = parser.into_formatter().into_inner().unwrap();
+//! ```
+//!
+//! Callbacks for keys and their elements return a `Control` value.
+//! A formatter can use it to skip the remaining elements of a key (`Control::SkipKey`)
+//! or to stop parsing altogether (`Control::Stop`), e.g. once it found what it was looking for.
+//!
 //! # Command-line
 //!
 //! rdb-rs brings a Command Line application as well.
@@ -72,30 +132,56 @@
 //! value
 //! ```
 
-use std::io::Read;
+use std::io::{Read, Write};
 
 #[doc(hidden)]
 pub use crate::types::{
     /* error and result types */
-    RdbError, RdbOk, RdbResult, Type, ZiplistEntry,
+    AuxInfo, Limit, RdbError, RdbOk, RdbResult, Type, Value, ZiplistEntry,
 };
 
+pub use crate::builder::RdbParserBuilder;
+pub use crate::copy::copy;
+pub use crate::inspect::inspect;
 pub use crate::parser::RdbParser;
+pub use crate::push::PushParser;
+pub use crate::writer::{Encodings, RdbWriter};
 
 use crate::filter::Filter;
 use crate::formatter::Formatter;
 
-#[macro_use]
-mod macros;
+mod builder;
+mod compact;
 mod constants;
+mod crc16;
+mod crc64;
 mod helper;
 
+pub mod cluster;
+pub mod copy;
 pub mod filter;
 pub mod formatter;
+pub mod generate;
+pub mod import;
+pub mod inspect;
+pub mod memory;
+pub mod merge;
 pub mod parser;
+pub mod push;
+pub mod transform;
 pub mod types;
+pub mod writer;
 
 pub fn parse<R: Read, F: Formatter, T: Filter>(input: R, formatter: F, filter: T) -> RdbOk {
     let mut parser = RdbParser::new(input, formatter, filter);
     parser.parse()
 }
+
+/// Reads a RDB file of any supported version and writes it as a RDB file of `version`.
+///
+/// Values are re-encoded as needed, e.g. listpacks become ziplists before version 10.
+/// Fails if the input contains data `version` can't represent, such as streams before version 9.
+pub fn transcode<R: Read, W: Write>(input: R, output: W, version: u32) -> RdbOk {
+    let formatter = formatter::Rdb::with_version(output, version)?;
+    parse(input, formatter, filter::Simple::new())
+}
@@ -1,27 +1,133 @@
 use getopts::Options;
 use indicatif::{ProgressBar, ProgressStyle};
+use rdb::formatter::typed_json::Binary;
+use rdb::formatter::Formatter;
+use rdb::generate::{Generator, Size};
+use rdb::transform::expiry::{Adjust, Reference};
+use rdb::transform::{Anonymize, Expiry, Rename, Transform, Transformed};
 use regex::bytes::Regex;
 use std::env;
 use std::fs::File;
-use std::io::{BufReader, Write};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::ops::RangeInclusive;
 use std::path::Path;
 
 fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} [options] dump.rdb", program);
+    let brief = format!(
+        "Usage: {0} [options] dump.rdb\n       {0} filter [options] -o out.rdb dump.rdb\n       {0} merge [options] -o out.rdb first.rdb second.rdb...\n       {0} split --slots 0-8191:node-1.rdb --slots 8192-16383:node-2.rdb dump.rdb\n       {0} generate --count string=1000 [options] -o out.rdb\n       {0} import [options] -o out.rdb dump.json\n       {0} import --from aof [options] -o out.rdb appendonly.aof...",
+        program
+    );
     print!("{}", opts.usage(&brief));
 }
 
+fn open_output(path: Option<String>) -> rdb::RdbResult<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(File::create(Path::new(&path))?),
+        None => Box::new(std::io::stdout()),
+    })
+}
+
+fn parse_database_mapping(mapping: &str) -> Option<(u32, u32)> {
+    let mut parts = mapping.splitn(2, ':');
+    let from = parts.next()?.parse().ok()?;
+    let to = parts.next()?.parse().ok()?;
+
+    Some((from, to))
+}
+
+/// Parses with `formatter`, after rewriting the expiries, anonymizing and renaming the keys
+fn parse<R: Read, F: Formatter, T: Transform>(
+    builder: &rdb::RdbParserBuilder,
+    reader: R,
+    formatter: F,
+    filter: rdb::filter::Simple,
+    transform: T,
+) -> rdb::RdbOk {
+    builder
+        .clone()
+        .build(reader, Transformed::new(formatter, transform), filter)
+        .parse()
+}
+
+type SizeSetter = fn(&mut Generator, Size);
+type RatioSetter = fn(&mut Generator, f64);
+
+fn parse_type(typ: &str) -> Option<rdb::Type> {
+    Some(match typ {
+        "string" => rdb::Type::String,
+        "list" => rdb::Type::List,
+        "set" => rdb::Type::Set,
+        "sortedset" | "sorted-set" | "sorted_set" => rdb::Type::SortedSet,
+        "hash" => rdb::Type::Hash,
+        "stream" => rdb::Type::Stream,
+        "module" => rdb::Type::Module,
+        _ => return None,
+    })
+}
+
+/// Parses `10`, `1-100` or `exp:20:1000`, the mean and maximum of an exponential distribution
+fn parse_size(size: &str) -> Option<Size> {
+    if let Some(exponential) = size.strip_prefix("exp:") {
+        let mut parts = exponential.splitn(2, ':');
+        let mean = parts.next()?.parse().ok()?;
+        let max = match parts.next() {
+            Some(max) => max.parse().ok()?,
+            None => usize::MAX,
+        };
+        return Some(Size::Exponential { mean, max });
+    }
+
+    let mut bounds = size.splitn(2, '-');
+    let min = bounds.next()?.parse().ok()?;
+    Some(match bounds.next() {
+        Some(max) => Size::Uniform(min, max.parse().ok()?),
+        None => Size::Fixed(min),
+    })
+}
+
+/// Parses `0-8191:node-1.rdb` or `42:node-1.rdb`
+fn parse_slot_assignment(assignment: &str) -> Option<(RangeInclusive<u16>, String)> {
+    let mut parts = assignment.splitn(2, ':');
+    let slots = parts.next()?;
+    let path = parts.next()?.to_string();
+
+    let mut bounds = slots.splitn(2, '-');
+    let start = bounds.next()?.parse().ok()?;
+    let end = match bounds.next() {
+        Some(end) => end.parse().ok()?,
+        None => start,
+    };
+    if start > end || end >= rdb::cluster::SLOTS {
+        return None;
+    }
+
+    Some((start..=end, path))
+}
+
 pub fn main() {
-    let mut args = env::args();
+    let mut args = env::args().peekable();
     let program = args.next().unwrap();
+    // `rdb filter` writes the matching keys to a new RDB file instead of formatting them,
+    // `rdb merge` combines several RDB files into one,
+    // `rdb split` distributes the keys to the nodes of a Redis Cluster,
+    // `rdb generate` writes a RDB file with random data,
+    // `rdb import` builds a RDB file from another format
+    let command = args
+        .next_if(|arg| ["filter", "merge", "split", "generate", "import"].contains(&arg.as_str()));
     let mut opts = Options::new();
 
     opts.optopt(
         "f",
         "format",
-        "Format to output. Valid: json, plain, nil, protocol",
+        "Format to output. Valid: json, typed-json, ndjson, memory, biggest, plain, nil, protocol, restore, rdb",
         "FORMAT",
     );
+    opts.optopt(
+        "",
+        "rdb-version",
+        "Version of the RDB file written with --format rdb, or of the payloads of --format restore. Defaults to the newest version",
+        "VERSION",
+    );
     opts.optopt(
         "k",
         "keys",
@@ -40,6 +146,198 @@ pub fn main() {
         "Type to show. Can be specified multiple times",
         "TYPE",
     );
+    opts.optopt("o", "output", "File written instead of stdout", "FILE");
+    opts.optopt(
+        "",
+        "conflict",
+        "What `merge` does with keys in more than one file. Valid: first-wins (default), last-wins, newest, error",
+        "POLICY",
+    );
+    opts.optmulti(
+        "",
+        "map-db",
+        "Writes the keys of database FROM to database TO, before filtering by database. Can be specified multiple times",
+        "FROM:TO",
+    );
+    opts.optmulti(
+        "",
+        "slots",
+        "Makes `split` write the keys in the slots FIRST-LAST to FILE. Can be specified multiple times",
+        "FIRST-LAST:FILE",
+    );
+    opts.optmulti(
+        "",
+        "strip-prefix",
+        "Removes PREFIX from the keys starting with it",
+        "PREFIX",
+    );
+    opts.optmulti(
+        "",
+        "rename",
+        "Replaces the first match of REGEX in the keys, which can refer to groups as $1",
+        "REGEX=REPLACEMENT",
+    );
+    opts.optmulti(
+        "",
+        "hash-tag",
+        "Makes the first match of REGEX the hash tag of keys without one",
+        "REGEX",
+    );
+    opts.optmulti(
+        "",
+        "add-prefix",
+        "Adds PREFIX to all keys, after the other renaming options",
+        "PREFIX",
+    );
+    opts.optopt(
+        "",
+        "reference-time",
+        "Time expiries are compared with: now (default), ctime (when the file was written) or a Unix time in milliseconds",
+        "TIME",
+    );
+    opts.optflag(
+        "",
+        "drop-expired",
+        "Leaves out keys already expired at the reference time",
+    );
+    opts.optopt(
+        "",
+        "expiry",
+        "How expiries are passed on: keep (default), shift (keeping the TTL left at the reference time) or ttl (as milliseconds left at the reference time, for json and plain)",
+        "MODE",
+    );
+    opts.optopt(
+        "",
+        "anonymize",
+        "Replaces all values with data of the same shape, derived from SECRET",
+        "SECRET",
+    );
+    opts.optmulti(
+        "",
+        "anonymize-keys",
+        "Makes --anonymize replace the groups matched by REGEX in the keys, or the whole match without groups",
+        "REGEX",
+    );
+    opts.optflag(
+        "",
+        "anonymize-fields",
+        "Makes --anonymize replace the fields of hashes as well",
+    );
+    opts.optflag(
+        "",
+        "random-values",
+        "Makes --anonymize replace strings, list elements and hash values with random data",
+    );
+    opts.optflag(
+        "",
+        "replace",
+        "Makes --format restore overwrite existing keys",
+    );
+    opts.optflag(
+        "",
+        "relative-ttl",
+        "Makes --format restore pass TTLs relative to now instead of Unix times, leaving out expired keys",
+    );
+    opts.optflag(
+        "",
+        "eviction-info",
+        "Makes --format restore pass on the LRU idle time or LFU frequency of the keys",
+    );
+    opts.optopt(
+        "",
+        "binary",
+        "How --format typed-json and ndjson write strings that are not UTF-8. Valid: escape (default), base64, which applies to all strings",
+        "ENCODING",
+    );
+    opts.optopt(
+        "",
+        "elements-per-line",
+        "Makes --format ndjson split collections into lines of at most N elements",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "redis-version",
+        "Redis version --format memory and biggest estimate for, like 6.2. Defaults to the one that wrote the file",
+        "VERSION",
+    );
+    opts.optopt(
+        "",
+        "allocator",
+        "Allocator --format memory and biggest estimate for. Valid: jemalloc (default), exact, which doesn't round allocations",
+        "ALLOCATOR",
+    );
+    opts.optopt(
+        "",
+        "top",
+        "Number of keys --format biggest reports per database, type and metric. Defaults to 10",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "report",
+        "How --format biggest writes its report. Valid: table (default), json",
+        "FORMAT",
+    );
+    opts.optmulti(
+        "",
+        "count",
+        "Makes `generate` write COUNT keys of TYPE. Can be specified multiple times",
+        "TYPE=COUNT",
+    );
+    opts.optopt(
+        "",
+        "seed",
+        "Seed of the data written by `generate`. Defaults to 0",
+        "SEED",
+    );
+    opts.optopt(
+        "",
+        "elements",
+        "Number of elements of the collections written by `generate`: N, MIN-MAX or exp:MEAN:MAX",
+        "SIZE",
+    );
+    opts.optopt(
+        "",
+        "value-size",
+        "Length of the values written by `generate`: N, MIN-MAX or exp:MEAN:MAX",
+        "SIZE",
+    );
+    opts.optopt(
+        "",
+        "expiry-ratio",
+        "Share of the keys written by `generate` with an expiry, from 0 to 1",
+        "RATIO",
+    );
+    opts.optopt(
+        "",
+        "ttl",
+        "TTL in seconds of the keys written by `generate` with an expiry: N, MIN-MAX or exp:MEAN:MAX",
+        "SIZE",
+    );
+    opts.optopt(
+        "",
+        "integer-ratio",
+        "Share of the keys written by `generate` holding integers, from 0 to 1",
+        "RATIO",
+    );
+    opts.optopt(
+        "",
+        "ctime",
+        "Creation time of the file written by `generate`, in seconds since the epoch or now. Defaults to a fixed time; expiries are counted from it",
+        "TIME",
+    );
+    opts.optflag(
+        "",
+        "plain-encodings",
+        "Makes `generate` write all values without compact encodings or compression",
+    );
+    opts.optopt(
+        "",
+        "from",
+        "Format read by `import`. Valid: json (default), resp or aof, which replay the commands of all files given",
+        "FORMAT",
+    );
     opts.optflag("h", "help", "print this help menu");
 
     let matches = match opts.parse(args) {
@@ -56,20 +354,40 @@ pub fn main() {
         return;
     }
 
-    let mut filter = rdb::filter::Simple::new();
+    let mut mappings = Vec::new();
+    for mapping in &matches.opt_strs("map-db") {
+        match parse_database_mapping(mapping) {
+            Some(mapping) => mappings.push(mapping),
+            None => {
+                println!("Invalid database mapping: {}\n", mapping);
+                print_usage(&program, opts);
+                return;
+            }
+        }
+    }
 
+    let mut databases = Vec::new();
     for db in &matches.opt_strs("d") {
-        filter.add_database(db.parse().unwrap());
+        match db.parse() {
+            Ok(db) => databases.push(db),
+            Err(_) => {
+                println!("Invalid database: {}\n", db);
+                print_usage(&program, opts);
+                return;
+            }
+        }
+    }
+
+    let mut filter = rdb::filter::Simple::new();
+
+    for &db in &databases {
+        filter.add_database(db);
     }
 
     for t in &matches.opt_strs("t") {
-        let typ = match &t[..] {
-            "string" => rdb::Type::String,
-            "list" => rdb::Type::List,
-            "set" => rdb::Type::Set,
-            "sortedset" | "sorted-set" | "sorted_set" => rdb::Type::SortedSet,
-            "hash" => rdb::Type::Hash,
-            _ => {
+        let typ = match parse_type(t) {
+            Some(typ) => typ,
+            None => {
                 println!("Unknown type: {}\n", t);
                 print_usage(&program, opts);
                 return;
@@ -90,13 +408,322 @@ pub fn main() {
         filter.add_keys(re);
     }
 
+    let mut rename = Rename::new();
+    for prefix in &matches.opt_strs("strip-prefix") {
+        rename.strip_prefix(prefix.as_bytes());
+    }
+    for rule in &matches.opt_strs("rename") {
+        let (pattern, replacement) = match rule.rfind('=') {
+            Some(index) => (&rule[..index], &rule[index + 1..]),
+            None => {
+                println!("Invalid renaming: {}\n", rule);
+                print_usage(&program, opts);
+                return;
+            }
+        };
+        match Regex::new(pattern) {
+            Ok(re) => rename.replace(re, replacement.as_bytes()),
+            Err(err) => {
+                println!("Incorrect regexp: {:?}\n", err);
+                print_usage(&program, opts);
+                return;
+            }
+        }
+    }
+    for pattern in &matches.opt_strs("hash-tag") {
+        match Regex::new(pattern) {
+            Ok(re) => rename.hash_tag(re),
+            Err(err) => {
+                println!("Incorrect regexp: {:?}\n", err);
+                print_usage(&program, opts);
+                return;
+            }
+        }
+    }
+    for prefix in &matches.opt_strs("add-prefix") {
+        rename.add_prefix(prefix.as_bytes());
+    }
+
+    let reference = match matches.opt_str("reference-time").as_deref() {
+        None | Some("now") => Reference::Now,
+        Some("ctime") => Reference::Ctime,
+        Some(time) => match time.parse() {
+            Ok(time) => Reference::At(time),
+            Err(_) => {
+                println!("Invalid reference time: {}\n", time);
+                print_usage(&program, opts);
+                return;
+            }
+        },
+    };
+    let format = matches.opt_str("f");
+    let mut expiry = Expiry::new(reference);
+    expiry.set_drop_expired(matches.opt_present("drop-expired"));
+    match matches.opt_str("expiry").as_deref() {
+        None | Some("keep") => {}
+        Some("shift") => expiry.set_adjust(Adjust::Shift),
+        // RDB files and Redis commands expect Unix times
+        Some("ttl") if matches!(format.as_deref(), Some("rdb" | "protocol" | "restore")) => {
+            println!(
+                "--expiry ttl is not supported by --format {}\n",
+                format.unwrap()
+            );
+            print_usage(&program, opts);
+            return;
+        }
+        Some("ttl") => expiry.set_adjust(Adjust::Ttl),
+        Some(mode) => {
+            println!("Unknown expiry mode: {}\n", mode);
+            print_usage(&program, opts);
+            return;
+        }
+    }
+    let mut anonymize = matches
+        .opt_str("anonymize")
+        .map(|secret| Anonymize::new(secret.as_bytes()));
+    if let Some(ref mut anonymize) = anonymize {
+        for pattern in &matches.opt_strs("anonymize-keys") {
+            match Regex::new(pattern) {
+                Ok(re) => anonymize.key_segments(re),
+                Err(err) => {
+                    println!("Incorrect regexp: {:?}\n", err);
+                    print_usage(&program, opts);
+                    return;
+                }
+            }
+        }
+        anonymize.set_hash_fields(matches.opt_present("anonymize-fields"));
+        anonymize.set_random_values(matches.opt_present("random-values"));
+    }
+    let transform = (expiry, (anonymize, rename));
+
+    let version = match matches.opt_str("rdb-version") {
+        Some(version) => match version.parse() {
+            Ok(version) => version,
+            Err(_) => {
+                println!("Invalid RDB version: {}\n", version);
+                print_usage(&program, opts);
+                return;
+            }
+        },
+        None => rdb::formatter::rdb::DEFAULT_VERSION,
+    };
+
+    if command.as_deref() == Some("generate") {
+        let seed = match matches.opt_str("seed") {
+            Some(seed) => match seed.parse() {
+                Ok(seed) => seed,
+                Err(_) => {
+                    println!("Invalid seed: {}\n", seed);
+                    print_usage(&program, opts);
+                    return;
+                }
+            },
+            None => 0,
+        };
+        let mut generator = Generator::new(seed);
+        generator.set_version(version);
+
+        for count in &matches.opt_strs("count") {
+            let parsed = count
+                .split_once('=')
+                .and_then(|(typ, count)| Some((parse_type(typ)?, count.parse().ok()?)));
+            match parsed {
+                Some((typ, count)) => generator.add_keys(typ, count),
+                None => {
+                    println!("Invalid key count: {}\n", count);
+                    print_usage(&program, opts);
+                    return;
+                }
+            }
+        }
+        for &db in &databases {
+            generator.add_database(db);
+        }
+
+        let sizes: [(&str, SizeSetter); 3] = [
+            ("elements", Generator::set_elements),
+            ("value-size", Generator::set_value_size),
+            ("ttl", Generator::set_ttl),
+        ];
+        for (name, set) in sizes {
+            if let Some(size) = matches.opt_str(name) {
+                match parse_size(&size) {
+                    Some(size) => set(&mut generator, size),
+                    None => {
+                        println!("Invalid size: {}\n", size);
+                        print_usage(&program, opts);
+                        return;
+                    }
+                }
+            }
+        }
+
+        let ratios: [(&str, RatioSetter); 2] = [
+            ("expiry-ratio", Generator::set_expiry_ratio),
+            ("integer-ratio", Generator::set_integer_ratio),
+        ];
+        for (name, set) in ratios {
+            if let Some(ratio) = matches.opt_str(name) {
+                match ratio.parse() {
+                    Ok(ratio) => set(&mut generator, ratio),
+                    Err(_) => {
+                        println!("Invalid ratio: {}\n", ratio);
+                        print_usage(&program, opts);
+                        return;
+                    }
+                }
+            }
+        }
+
+        // The default ctime keeps the output reproducible; `now` makes expiries lie in the future
+        match matches.opt_str("ctime").as_deref() {
+            None => {}
+            Some("now") => generator.set_ctime(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|now| now.as_secs())
+                    .unwrap_or(0),
+            ),
+            Some(ctime) => match ctime.parse() {
+                Ok(ctime) => generator.set_ctime(ctime),
+                Err(_) => {
+                    println!("Invalid creation time: {}\n", ctime);
+                    print_usage(&program, opts);
+                    return;
+                }
+            },
+        }
+        if matches.opt_present("plain-encodings") {
+            generator.set_encodings(rdb::Encodings::plain());
+        }
+
+        let res = open_output(matches.opt_str("o"))
+            .and_then(|output| generator.generate(BufWriter::new(output)));
+        if let Err(e) = res {
+            eprintln!("Generating failed: {}", e);
+        }
+        return;
+    }
+
     if matches.free.is_empty() {
         print_usage(&program, opts);
         return;
     }
 
+    // `filter`, `split` and `merge` copy the values without decoding them,
+    // so keys, values and expiries can't be changed
+    if let Some(command @ ("filter" | "split" | "merge")) = command.as_deref() {
+        let transforms = [
+            "strip-prefix",
+            "rename",
+            "hash-tag",
+            "add-prefix",
+            "anonymize",
+            "drop-expired",
+            "expiry",
+        ];
+        if let Some(option) = transforms
+            .iter()
+            .find(|&&option| matches.opt_present(option))
+        {
+            println!(
+                "`{}` copies keys unchanged and doesn't support --{}, use --format rdb instead\n",
+                command, option
+            );
+            print_usage(&program, opts);
+            return;
+        }
+    }
+    if command.as_deref() == Some("split") && databases.len() > 1 {
+        println!("`split` reads a single database, give at most one --databases\n");
+        print_usage(&program, opts);
+        return;
+    }
+    if command.as_deref() == Some("filter") && !mappings.is_empty() {
+        println!("`filter` copies keys unchanged and doesn't support --map-db, use --format rdb instead\n");
+        print_usage(&program, opts);
+        return;
+    }
+
+    if command.as_deref() == Some("merge") {
+        let mut merge = rdb::merge::Merge::new();
+
+        if let Some(conflict) = matches.opt_str("conflict") {
+            let conflict = match &conflict[..] {
+                "first-wins" => rdb::merge::Conflict::FirstWins,
+                "last-wins" => rdb::merge::Conflict::LastWins,
+                "newest" => rdb::merge::Conflict::NewestByCtime,
+                "error" => rdb::merge::Conflict::Error,
+                _ => {
+                    println!("Unknown conflict policy: {}\n", conflict);
+                    print_usage(&program, opts);
+                    return;
+                }
+            };
+            merge.set_conflict(conflict);
+        }
+
+        for &(from, to) in &mappings {
+            merge.map_database(from, to);
+        }
+
+        let res = matches
+            .free
+            .iter()
+            .map(|path| Ok(BufReader::new(File::open(Path::new(path))?)))
+            .collect::<rdb::RdbResult<Vec<_>>>()
+            .and_then(|inputs| Ok((inputs, open_output(matches.opt_str("o"))?)))
+            .and_then(|(inputs, output)| merge.merge(inputs, BufWriter::new(output)));
+
+        if let Err(e) = res {
+            eprintln!("Merging failed: {}", e);
+        }
+        return;
+    }
+
+    if command.as_deref() == Some("import") {
+        let res = match matches.opt_str("from").as_deref() {
+            None | Some("json") if matches.free.len() > 1 => {
+                println!("`import --from json` reads a single file\n");
+                print_usage(&program, opts);
+                return;
+            }
+            None | Some("json") => File::open(Path::new(&matches.free[0]))
+                .map_err(rdb::RdbError::from)
+                .and_then(|file| Ok((BufReader::new(file), open_output(matches.opt_str("o"))?)))
+                .and_then(|(input, output)| {
+                    rdb::import::json(input, BufWriter::new(output), version)
+                }),
+            // The parts of a multi-part AOF are replayed in the order given
+            Some("resp") | Some("aof") => {
+                let mut replay = rdb::import::resp::Replay::new();
+                matches
+                    .free
+                    .iter()
+                    .try_for_each(|path| replay.read(BufReader::new(File::open(Path::new(path))?)))
+                    .and_then(|_| open_output(matches.opt_str("o")))
+                    .and_then(|output| {
+                        replay
+                            .into_keyspace()
+                            .write(BufWriter::new(output), version)
+                    })
+            }
+            Some(from) => {
+                println!("Unknown import format: {}\n", from);
+                print_usage(&program, opts);
+                return;
+            }
+        };
+
+        if let Err(e) = res {
+            eprintln!("Importing failed: {}", e);
+        }
+        return;
+    }
+
     let path = matches.free[0].clone();
-    let file = File::open(&Path::new(&*path)).unwrap();
+    let file = File::open(Path::new(&*path)).unwrap();
 
     let file_length = file.metadata().map(|m| m.len()).unwrap_or(0);
 
@@ -109,21 +736,209 @@ pub fn main() {
 
     let reader = BufReader::new(reader);
 
+    let binary = match matches.opt_str("binary").as_deref() {
+        None | Some("escape") => Binary::Escape,
+        Some("base64") => Binary::Base64,
+        Some(binary) => {
+            println!("Unknown binary encoding: {}\n", binary);
+            print_usage(&program, opts);
+            return;
+        }
+    };
+
+    let elements_per_line = match matches.opt_str("elements-per-line") {
+        Some(elements) => match elements.parse() {
+            Ok(elements) if elements > 0 => Some(elements),
+            _ => {
+                println!("Invalid number of elements per line: {}\n", elements);
+                print_usage(&program, opts);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let mut model = None;
+    if let Some(version) = matches.opt_str("redis-version") {
+        let mut redis = rdb::memory::Model::new();
+        if let Err(err) = redis.set_redis_version(&version) {
+            println!("{}\n", err);
+            print_usage(&program, opts);
+            return;
+        }
+        model = Some(redis);
+    }
+
+    let allocator = match matches.opt_str("allocator").as_deref() {
+        None | Some("jemalloc") => rdb::memory::Allocator::Jemalloc,
+        Some("exact") => rdb::memory::Allocator::Exact,
+        Some(allocator) => {
+            println!("Unknown allocator: {}\n", allocator);
+            print_usage(&program, opts);
+            return;
+        }
+    };
+
+    let top = match matches.opt_str("top") {
+        Some(top) => match top.parse() {
+            Ok(top) if top > 0 => Some(top),
+            _ => {
+                println!("Invalid number of keys: {}\n", top);
+                print_usage(&program, opts);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let report = match matches.opt_str("report").as_deref() {
+        None | Some("table") => rdb::formatter::biggest::Report::Table,
+        Some("json") => rdb::formatter::biggest::Report::Json,
+        Some(report) => {
+            println!("Unknown report format: {}\n", report);
+            print_usage(&program, opts);
+            return;
+        }
+    };
+
+    let builder = mappings
+        .iter()
+        .fold(rdb::RdbParserBuilder::new(), |builder, &(from, to)| {
+            builder.map_database(from, to)
+        });
+
     let mut res = Ok(());
 
-    if let Some(f) = matches.opt_str("f") {
+    if command.as_deref() == Some("split") {
+        let mut paths: Vec<String> = Vec::new();
+        let mut assignments = Vec::new();
+        for assignment in &matches.opt_strs("slots") {
+            let (slots, path) = match parse_slot_assignment(assignment) {
+                Some(parsed) => parsed,
+                None => {
+                    println!("Invalid slot assignment: {}\n", assignment);
+                    print_usage(&program, opts);
+                    return;
+                }
+            };
+            let output = match paths.iter().position(|p| *p == path) {
+                Some(output) => output,
+                None => {
+                    paths.push(path);
+                    paths.len() - 1
+                }
+            };
+            assignments.push((slots, output));
+        }
+
+        res = paths
+            .iter()
+            .map(|path| Ok(BufWriter::new(File::create(Path::new(path))?)))
+            .collect::<rdb::RdbResult<Vec<_>>>()
+            .and_then(|outputs| {
+                let mut split = rdb::cluster::Split::new(outputs);
+                for (slots, output) in assignments {
+                    split.assign(slots, output);
+                }
+                if let Some(&db) = databases.first() {
+                    split.set_database(db);
+                }
+                for &(from, to) in &mappings {
+                    split.map_database(from, to);
+                }
+                split.split(reader).map(|_| ())
+            });
+    } else if command.as_deref() == Some("filter") {
+        res = open_output(matches.opt_str("o"))
+            .and_then(|output| rdb::copy(reader, BufWriter::new(output), filter));
+    } else if let Some(f) = matches.opt_str("f") {
+        let output = || open_output(matches.opt_str("o"));
         match &f[..] {
             "json" => {
-                res = rdb::parse(reader, rdb::formatter::JSON::new(), filter);
+                res = output().and_then(|out| {
+                    let formatter = rdb::formatter::JSON::with_writer(out);
+                    parse(&builder, reader, formatter, filter, transform)
+                });
+            }
+            "typed-json" => {
+                res = output().and_then(|out| {
+                    let mut formatter = rdb::formatter::TypedJSON::with_writer(out);
+                    formatter.set_binary(binary);
+                    parse(&builder, reader, formatter, filter, transform)
+                });
+            }
+            "ndjson" => {
+                res = output().and_then(|out| {
+                    let mut formatter = rdb::formatter::NDJSON::with_writer(out);
+                    formatter.set_binary(binary);
+                    if let Some(elements) = elements_per_line {
+                        formatter.set_elements_per_line(elements);
+                    }
+                    parse(&builder, reader, formatter, filter, transform)
+                });
+            }
+            "memory" => {
+                res = output().and_then(|out| {
+                    let mut formatter = rdb::formatter::Memory::with_writer(out);
+                    if let Some(model) = model {
+                        formatter.set_model(model);
+                    }
+                    formatter.set_allocator(allocator);
+                    parse(&builder, reader, formatter, filter, transform)
+                });
+            }
+            "biggest" => {
+                res = output().and_then(|out| {
+                    let mut formatter = rdb::formatter::Biggest::with_writer(out);
+                    formatter.set_report(report);
+                    if let Some(top) = top {
+                        formatter.set_top(top);
+                    }
+                    if let Some(model) = model {
+                        formatter.set_model(model);
+                    }
+                    formatter.set_allocator(allocator);
+                    parse(&builder, reader, formatter, filter, transform)
+                });
             }
             "plain" => {
-                res = rdb::parse(reader, rdb::formatter::Plain::new(), filter);
+                res = output().and_then(|out| {
+                    let formatter = rdb::formatter::Plain::with_writer(out);
+                    parse(&builder, reader, formatter, filter, transform)
+                });
             }
             "nil" => {
-                res = rdb::parse(reader, rdb::formatter::Nil::new(), filter);
+                res = parse(
+                    &builder,
+                    reader,
+                    rdb::formatter::Nil::new(),
+                    filter,
+                    transform,
+                );
             }
             "protocol" => {
-                res = rdb::parse(reader, rdb::formatter::Protocol::new(), filter);
+                res = output().and_then(|out| {
+                    let formatter = rdb::formatter::Protocol::with_writer(out);
+                    parse(&builder, reader, formatter, filter, transform)
+                });
+            }
+            "restore" => {
+                let options = rdb::formatter::protocol::Restore {
+                    version,
+                    replace: matches.opt_present("replace"),
+                    absttl: !matches.opt_present("relative-ttl"),
+                    eviction_info: matches.opt_present("eviction-info"),
+                };
+                res = output().and_then(|out| {
+                    let mut formatter = rdb::formatter::Protocol::with_writer(out);
+                    formatter.set_restore(options);
+                    parse(&builder, reader, formatter, filter, transform)
+                });
+            }
+            "rdb" => {
+                res = output()
+                    .and_then(|out| rdb::formatter::Rdb::with_version(BufWriter::new(out), version))
+                    .and_then(|formatter| parse(&builder, reader, formatter, filter, transform));
             }
             _ => {
                 println!("Unknown format: {}\n", f);
@@ -131,17 +946,20 @@ pub fn main() {
             }
         }
     } else {
-        res = rdb::parse(reader, rdb::formatter::JSON::new(), filter);
+        res = open_output(matches.opt_str("o")).and_then(|out| {
+            let formatter = rdb::formatter::JSON::with_writer(out);
+            parse(&builder, reader, formatter, filter, transform)
+        });
     }
 
     match res {
         Ok(()) => {}
         Err(e) => {
-            println!("");
+            println!();
             let mut stderr = std::io::stderr();
 
             let out = format!("Parsing failed: {}\n", e);
-            stderr.write(out.as_bytes()).unwrap();
+            stderr.write_all(out.as_bytes()).unwrap();
         }
     }
 }
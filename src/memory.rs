@@ -0,0 +1,343 @@
+//! Estimating the memory keys take in Redis
+//!
+//! The estimates follow the data structures of the Redis version they are made for:
+//! SDS string headers, embedded strings, dictionaries, skip lists, quicklists and the
+//! compact encodings, each allocation rounded up to a jemalloc size class.
+//!
+//! ```rust
+//! use rdb::memory::Model;
+//! use rdb::types::EncodingType;
+//! use rdb::Type;
+//!
+//! let mut model = Model::new();
+//! model.set_redis_version("6.2.14").unwrap();
+//!
+//! let mut set = model.collection(Type::Set, EncodingType::Hashtable);
+//! set.add(&[b"member"]);
+//! let size = model.key(b"key", false) + set.size();
+//! ```
+
+use crate::types::{EncodingType, RdbError, RdbOk, Type};
+
+/// How allocations are sized
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Allocator {
+    /// Rounded up to the size classes of jemalloc, the default allocator of Redis
+    #[default]
+    Jemalloc,
+    /// Exactly as requested
+    Exact,
+}
+
+/// Sizes of the data structures of a 64-bit Redis server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Model {
+    version: (u32, u32),
+    allocator: Allocator,
+}
+
+impl Default for Model {
+    fn default() -> Model {
+        Model {
+            version: (7, 2),
+            allocator: Allocator::default(),
+        }
+    }
+}
+
+const POINTER: u64 = 8;
+/// `robj` with its type, encoding, LRU, reference count and pointer
+const OBJECT: u64 = 16;
+/// `dictEntry` with key, value and next pointers
+const DICT_ENTRY: u64 = 3 * POINTER;
+/// Integers from 0 to 9999 are shared objects
+const SHARED_INTEGERS: i64 = 10000;
+/// Quicklist nodes are limited to 8 KB by the default `list-max-listpack-size -2`
+const QUICKLIST_NODE: u64 = 8192;
+/// Skip list levels, each with a forward pointer and a span
+const SKIPLIST_LEVELS: u32 = 32;
+const SKIPLIST_LEVEL: u64 = 16;
+
+impl Model {
+    /// A model of Redis 7.2 with jemalloc
+    pub fn new() -> Model {
+        Model::default()
+    }
+
+    /// Sets the Redis version from a string like `6.2.14`, as in the `redis-ver` aux field.
+    pub fn set_redis_version(&mut self, version: &str) -> RdbOk {
+        let mut parts = version.split('.').map(|part| part.parse().ok());
+        match (parts.next().flatten(), parts.next().flatten()) {
+            (Some(major), Some(minor)) => {
+                self.version = (major, minor);
+                Ok(())
+            }
+            _ => Err(RdbError::Other(format!(
+                "Invalid Redis version: {}",
+                version
+            ))),
+        }
+    }
+
+    pub fn set_allocator(&mut self, allocator: Allocator) {
+        self.allocator = allocator;
+    }
+
+    fn at_least(&self, major: u32, minor: u32) -> bool {
+        self.version >= (major, minor)
+    }
+
+    /// The memory an allocation of `size` bytes takes
+    pub fn allocation(&self, size: u64) -> u64 {
+        match self.allocator {
+            Allocator::Exact => size,
+            Allocator::Jemalloc => size_class(size),
+        }
+    }
+
+    fn sds_header(&self, length: u64) -> u64 {
+        if !self.at_least(3, 2) {
+            return 8;
+        }
+        match length {
+            1..=31 => 1,
+            0 | 32..=255 => 3,
+            256..=65535 => 5,
+            65536..=0xffff_ffff => 9,
+            _ => 17,
+        }
+    }
+
+    /// A SDS string of `length` bytes
+    pub fn sds(&self, length: u64) -> u64 {
+        self.allocation(self.sds_header(length) + length + 1)
+    }
+
+    /// The key in the keyspace dictionary and, with an expiry, the entry in the
+    /// expires dictionary. The object of the value is part of the value.
+    pub fn key(&self, key: &[u8], has_expiry: bool) -> u64 {
+        let entry = self.allocation(DICT_ENTRY);
+        let expires = if has_expiry { entry } else { 0 };
+        entry + self.sds(key.len() as u64) + expires
+    }
+
+    /// A string value with its object
+    pub fn string(&self, value: &[u8]) -> u64 {
+        match integer(value) {
+            Some(integer) if (0..SHARED_INTEGERS).contains(&integer) => 0,
+            // Stored in the pointer of the object
+            Some(_) => self.allocation(OBJECT),
+            None => {
+                let length = value.len() as u64;
+                let embedded = if self.at_least(3, 2) { 44 } else { 39 };
+                if self.at_least(3, 0) && length <= embedded {
+                    // Object and SDS string with an 8 bit header in one allocation
+                    self.allocation(OBJECT + 3 + length + 1)
+                } else {
+                    self.allocation(OBJECT) + self.sds(length)
+                }
+            }
+        }
+    }
+
+    /// A dictionary with its bucket array for `elements` entries, without the entries
+    fn dict(&self, elements: u64) -> u64 {
+        let dict = if self.at_least(7, 0) { 56 } else { 88 };
+        let buckets = if elements == 0 {
+            0
+        } else {
+            self.allocation(elements.next_power_of_two().max(4) * POINTER)
+        };
+        self.allocation(dict) + buckets
+    }
+
+    /// Starts the estimate of a collection stored with `encoding`.
+    pub fn collection(&self, typ: Type, encoding: EncodingType) -> Collection {
+        Collection {
+            model: *self,
+            typ,
+            encoding,
+            elements: 0,
+            size: 0,
+            entries: 0,
+            previous: 0,
+        }
+    }
+
+    /// The expected size of a skip list node, with levels drawn with a probability of 1/4
+    fn skiplist_node(&self) -> u64 {
+        let mut probability = 0.75;
+        let mut expected = 0.0;
+        for level in 1..=SKIPLIST_LEVELS as u64 {
+            let size = self.allocation(3 * POINTER + level * SKIPLIST_LEVEL);
+            expected += probability * size as f64;
+            probability /= 4.0;
+        }
+        expected.round() as u64
+    }
+
+    /// An entry of a listpack, since Redis 7.0, or of a ziplist before, following an
+    /// entry of `previous` bytes
+    fn list_entry(&self, value: &[u8], previous: u64) -> u64 {
+        let length = value.len() as u64;
+        if self.at_least(7, 0) {
+            let data = match integer(value) {
+                Some(0..=127) => 1,
+                Some(-4096..=4095) => 2,
+                Some(-32768..=32767) => 3,
+                Some(-8388608..=8388607) => 4,
+                Some(-2147483648..=2147483647) => 5,
+                Some(_) => 9,
+                None if length < 64 => 1 + length,
+                None if length < 4096 => 2 + length,
+                None => 5 + length,
+            };
+            let backlength = match data {
+                0..=127 => 1,
+                128..=16383 => 2,
+                16384..=2097151 => 3,
+                2097152..=268435455 => 4,
+                _ => 5,
+            };
+            data + backlength
+        } else {
+            let previous_length = if previous < 254 { 1 } else { 5 };
+            let data = match integer(value) {
+                Some(0..=12) => 1,
+                Some(-128..=127) => 2,
+                Some(-32768..=32767) => 3,
+                Some(-8388608..=8388607) => 4,
+                Some(-2147483648..=2147483647) => 5,
+                Some(_) => 9,
+                None if length < 64 => 1 + length,
+                None if length < 16384 => 2 + length,
+                None => 5 + length,
+            };
+            previous_length + data
+        }
+    }
+}
+
+/// Accumulates the memory of the elements of a collection
+#[derive(Debug, Clone)]
+pub struct Collection {
+    model: Model,
+    typ: Type,
+    encoding: EncodingType,
+    elements: u64,
+    /// Memory of the elements stored outside of the collection's own structures
+    size: u64,
+    /// Bytes of the entries of quicklist nodes
+    entries: u64,
+    previous: u64,
+}
+
+impl Collection {
+    /// Adds an element made of `parts`: a list element, a set or sorted set member,
+    /// or a field and its value.
+    pub fn add(&mut self, parts: &[&[u8]]) {
+        let model = &self.model;
+        self.elements += 1;
+        self.size += match (self.typ, self.encoding) {
+            // Included in the size of the encoding
+            (_, EncodingType::Ziplist(_))
+            | (_, EncodingType::Listpack(_))
+            | (_, EncodingType::Intset(_))
+            | (_, EncodingType::Zipmap(_)) => 0,
+            (Type::List, EncodingType::LinkedList) => {
+                model.allocation(3 * POINTER) + model.string(parts[0])
+            }
+            (Type::List, _) => {
+                let entry = model.list_entry(parts[0], self.previous);
+                self.entries += entry;
+                self.previous = entry;
+                0
+            }
+            // The member is shared by the dictionary and the skip list
+            (Type::SortedSet, _) => {
+                model.allocation(DICT_ENTRY)
+                    + model.sds(parts[0].len() as u64)
+                    + model.skiplist_node()
+            }
+            (Type::Set, _) if model.at_least(7, 2) => {
+                // Entries of sets have no value
+                model.allocation(2 * POINTER) + model.sds(parts[0].len() as u64)
+            }
+            _ => {
+                model.allocation(DICT_ENTRY)
+                    + parts
+                        .iter()
+                        .map(|part| model.sds(part.len() as u64))
+                        .sum::<u64>()
+            }
+        };
+    }
+
+    /// Number of elements added
+    pub fn len(&self) -> u64 {
+        self.elements
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements == 0
+    }
+
+    /// The memory of the collection and its object
+    pub fn size(&self) -> u64 {
+        let model = &self.model;
+        let structures = match (self.typ, self.encoding) {
+            (_, EncodingType::Ziplist(size))
+            | (_, EncodingType::Listpack(size))
+            | (_, EncodingType::Intset(size))
+            | (_, EncodingType::Zipmap(size)) => model.allocation(size),
+            (Type::SortedSet, _) => {
+                let header = 3 * POINTER + SKIPLIST_LEVELS as u64 * SKIPLIST_LEVEL;
+                model.allocation(2 * POINTER)
+                    + model.dict(self.elements)
+                    + model.allocation(4 * POINTER)
+                    + model.allocation(header)
+            }
+            (Type::List, EncodingType::LinkedList) => model.allocation(6 * POINTER),
+            (Type::List, _) => self.quicklist(),
+            _ => model.dict(self.elements),
+        };
+        model.allocation(OBJECT) + structures + self.size
+    }
+
+    /// The quicklist with its nodes, assuming all but the last one are full
+    fn quicklist(&self) -> u64 {
+        let model = &self.model;
+        let header = if model.at_least(7, 0) { 7 } else { 11 };
+        let nodes = self.entries.div_ceil(QUICKLIST_NODE - header).max(1);
+        let per_node = self.entries / nodes + header;
+        model.allocation(5 * POINTER) + nodes * (model.allocation(32) + model.allocation(per_node))
+    }
+}
+
+/// `value` as an integer, if Redis would store it as one
+fn integer(value: &[u8]) -> Option<i64> {
+    if value.is_empty() || value.len() > 20 {
+        return None;
+    }
+    let integer: i64 = std::str::from_utf8(value).ok()?.parse().ok()?;
+    if integer.to_string().as_bytes() == value {
+        Some(integer)
+    } else {
+        None
+    }
+}
+
+/// The jemalloc size class of an allocation: multiples of 8 and 16 up to 128,
+/// then four classes for every doubling
+fn size_class(size: u64) -> u64 {
+    match size {
+        0 => 0,
+        1..=8 => 8,
+        9..=128 => size.div_ceil(16) * 16,
+        _ => {
+            let group = 1 << (63 - (size - 1).leading_zeros());
+            let step = group / 4;
+            size.div_ceil(step) * step
+        }
+    }
+}
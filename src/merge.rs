@@ -0,0 +1,242 @@
+//! Merging several RDB files into one
+//!
+//! The values are copied without being re-encoded, so the output has the newest version
+//! of all inputs. Keys that exist in more than one input are resolved with a `Conflict`
+//! policy, and databases can be renumbered on the way.
+//!
+//! ```rust,no_run
+//! # use std::io::{BufReader, BufWriter};
+//! # use std::fs::File;
+//! use rdb::merge::{Conflict, Merge};
+//!
+//! let inputs = vec![
+//!     BufReader::new(File::open("shard-1.rdb").unwrap()),
+//!     BufReader::new(File::open("shard-2.rdb").unwrap()),
+//! ];
+//! let output = BufWriter::new(File::create("dump.rdb").unwrap());
+//!
+//! let mut merge = Merge::new();
+//! merge.set_conflict(Conflict::LastWins);
+//! merge.map_database(1, 0);
+//! merge.merge(inputs, output).unwrap();
+//! ```
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::constants::op_code;
+use crate::copy::{RawEntry, RawReader};
+use crate::parser::read_blob;
+use crate::types::{AuxInfo, RdbError, RdbOk};
+use crate::writer::RdbWriter;
+
+/// What to do with a key that exists in more than one input
+///
+/// A key that occurs more than once in one input, e.g. when `Merge::map_database`
+/// folds two of its databases into one, is resolved the same way, with the
+/// occurrences taken in the order of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Conflict {
+    /// Keep the first value of the key
+    #[default]
+    FirstWins,
+    /// Keep the last value of the key
+    LastWins,
+    /// Keep the value of the input with the newest `ctime` aux field.
+    /// Inputs without one count as the oldest, ties go to the earlier value.
+    NewestByCtime,
+    /// Fail with `RdbError::DuplicateKey`
+    Error,
+}
+
+/// The occurrence of a key whose value is written
+struct Winner {
+    input: usize,
+    /// The number of keys before it in its input
+    position: u64,
+    has_expiry: bool,
+}
+
+#[derive(Default)]
+pub struct Merge {
+    conflict: Conflict,
+    databases: HashMap<u32, u32>,
+}
+
+impl Merge {
+    pub fn new() -> Merge {
+        Merge::default()
+    }
+
+    pub fn set_conflict(&mut self, conflict: Conflict) {
+        self.conflict = conflict;
+    }
+
+    /// Writes the keys of database `from` of all inputs to database `to`.
+    pub fn map_database(&mut self, from: u32, to: u32) {
+        self.databases.insert(from, to);
+    }
+
+    fn target_database(&self, db: u32) -> u32 {
+        self.databases.get(&db).copied().unwrap_or(db)
+    }
+
+    /// Merges `inputs` into one RDB file written to `output`.
+    ///
+    /// Every input is read twice: once to resolve conflicts and count the keys of each
+    /// database for the RESIZEDB hints, and once to copy the values.
+    /// Aux fields are left out, as they describe the individual snapshots.
+    /// Functions and module aux data are copied, but only once if several inputs
+    /// contain the same.
+    pub fn merge<R: Read + Seek, W: Write>(&self, inputs: Vec<R>, output: W) -> RdbOk {
+        let mut version = 0;
+        let mut winners = HashMap::new();
+        let mut ctimes = Vec::new();
+        let mut readers = Vec::with_capacity(inputs.len());
+
+        for (index, input) in inputs.into_iter().enumerate() {
+            let mut reader = RawReader::new(input)?;
+            version = version.max(reader.version());
+            self.resolve(index, &mut reader, &mut winners, &mut ctimes)?;
+
+            let mut input = reader.into_inner();
+            input.seek(SeekFrom::Start(0))?;
+            readers.push(RawReader::new(input)?);
+        }
+
+        // Sizes of the databases of the output
+        let mut sizes: BTreeMap<u32, (u32, u32)> = BTreeMap::new();
+        for ((db, _), winner) in &winners {
+            let (size, expires) = sizes.entry(*db).or_default();
+            *size = size.saturating_add(1);
+            if winner.has_expiry {
+                *expires = expires.saturating_add(1);
+            }
+        }
+
+        let mut writer = RdbWriter::new(output, version)?;
+        writer.write_header()?;
+
+        let mut copied_globals = HashSet::new();
+        let mut hinted = HashSet::new();
+        let mut selected = None;
+
+        for (index, reader) in readers.iter_mut().enumerate() {
+            let mut database = 0;
+            let mut position = 0;
+
+            loop {
+                let entry = reader.next_entry()?;
+
+                match entry {
+                    RawEntry::Eof => break,
+                    RawEntry::SelectDb(db) => database = self.target_database(db),
+                    RawEntry::Global { op_code: op, raw } => {
+                        if op != op_code::AUX && copied_globals.insert((op, raw.clone())) {
+                            writer.write_u8(op)?;
+                            writer.write_raw(&raw)?;
+                        }
+                    }
+                    RawEntry::Key {
+                        value_type,
+                        ref key,
+                        ..
+                    } => {
+                        let is_winner = winners
+                            .get(&(database, key.clone()))
+                            .is_some_and(|w| w.input == index && w.position == position);
+                        position += 1;
+                        if !is_winner {
+                            reader.skip_value(value_type)?;
+                            continue;
+                        }
+
+                        if selected != Some(database) {
+                            writer.write_select_db(database)?;
+                            if hinted.insert(database) {
+                                let (size, expires) = sizes[&database];
+                                writer.write_resizedb(size, expires)?;
+                            }
+                            selected = Some(database);
+                        }
+                        reader.copy_value(&entry, &mut writer)?;
+                    }
+                }
+            }
+        }
+
+        writer.write_eof()
+    }
+
+    /// Reads all keys of the input with `index` and updates the winner of every key.
+    /// `ctimes` holds the `ctime` aux field of every input read so far.
+    fn resolve<R: Read>(
+        &self,
+        index: usize,
+        reader: &mut RawReader<R>,
+        winners: &mut HashMap<(u32, Vec<u8>), Winner>,
+        ctimes: &mut Vec<Option<u64>>,
+    ) -> RdbOk {
+        let mut aux = AuxInfo::default();
+        let mut database = 0;
+        let mut position = 0;
+        ctimes.push(None);
+
+        loop {
+            match reader.next_entry()? {
+                RawEntry::Eof => break,
+                RawEntry::SelectDb(db) => database = self.target_database(db),
+                RawEntry::Global { op_code: op, raw } => {
+                    if op == op_code::AUX {
+                        let mut raw = &raw[..];
+                        let key = read_blob(&mut raw)?;
+                        let value = read_blob(&mut raw)?;
+                        aux.update(&key, &value);
+                        ctimes[index] = aux.ctime;
+                    }
+                }
+                RawEntry::Key {
+                    value_type,
+                    key,
+                    has_expiry,
+                    ..
+                } => {
+                    reader.skip_value(value_type)?;
+                    let winner = Winner {
+                        input: index,
+                        position,
+                        has_expiry,
+                    };
+                    position += 1;
+
+                    match winners.entry((database, key)) {
+                        Entry::Vacant(entry) => {
+                            entry.insert(winner);
+                        }
+                        Entry::Occupied(mut entry) => {
+                            let replace = match self.conflict {
+                                Conflict::FirstWins => false,
+                                Conflict::LastWins => true,
+                                Conflict::NewestByCtime => {
+                                    ctimes[index] > ctimes[entry.get().input]
+                                }
+                                Conflict::Error => {
+                                    return Err(RdbError::DuplicateKey {
+                                        database,
+                                        key: entry.key().1.clone(),
+                                    })
+                                }
+                            };
+                            if replace {
+                                entry.insert(winner);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,26 +1,42 @@
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use std::collections::HashMap;
 use std::io::{Cursor, Read};
 use std::{f64, str};
 
+use crate::builder::{Limits, RdbParserBuilder};
 use crate::filter::Filter;
-use crate::formatter::Formatter;
+use crate::formatter::{Control, Formatter};
 use crate::helper;
-use crate::helper::read_exact;
+use crate::helper::{read_exact, Capture, Counter};
 
 #[doc(hidden)]
-use crate::constants::{constant, encoding, encoding_type, op_code, version};
+use crate::constants::{
+    constant, encoding, encoding_type, module_op_code, op_code, quicklist_container,
+};
 
 #[doc(hidden)]
 pub use crate::types::{
-    EncodingType, /* error and result types */
+    AuxInfo, EncodingType, /* error and result types */
     RdbError, RdbOk, RdbResult, Type, ZiplistEntry,
 };
 
 pub struct RdbParser<R: Read, F: Formatter, L: Filter> {
-    input: R,
+    pub(crate) input: R,
     formatter: F,
     filter: L,
     last_expiretime: Option<u64>,
+    last_idle: Option<u64>,
+    last_freq: Option<u8>,
+    last_database: u32,
+    /// Database renumbering, applied before the filter and formatter see the index
+    databases: HashMap<u32, u32>,
+    /// The database read from the input for each database passed on
+    sources: HashMap<u32, u32>,
+    pub(crate) version: u32,
+    pub(crate) limits: Limits,
+    keys: u64,
+    aux: AuxInfo,
+    aux_done: bool,
 }
 
 #[inline]
@@ -28,7 +44,8 @@ fn other_error(desc: impl Into<String>) -> RdbError {
     RdbError::Other(desc.into())
 }
 
-pub fn read_length_with_encoding<R: Read>(input: &mut R) -> RdbResult<(u32, bool)> {
+/// Reads a length, which may take up to 64 bit.
+pub(crate) fn read_length_with_encoding_u64<R: Read>(input: &mut R) -> RdbResult<(u64, bool)> {
     let length;
     let mut is_encoded = false;
 
@@ -37,28 +54,51 @@ pub fn read_length_with_encoding<R: Read>(input: &mut R) -> RdbResult<(u32, bool
     match (enc_type & 0xC0) >> 6 {
         constant::RDB_ENCVAL => {
             is_encoded = true;
-            length = (enc_type & 0x3F) as u32;
+            length = (enc_type & 0x3F) as u64;
         }
         constant::RDB_6BITLEN => {
-            length = (enc_type & 0x3F) as u32;
+            length = (enc_type & 0x3F) as u64;
         }
         constant::RDB_14BITLEN => {
             let next_byte = input.read_u8()?;
-            length = (((enc_type & 0x3F) as u32) << 8) | next_byte as u32;
+            length = (((enc_type & 0x3F) as u64) << 8) | next_byte as u64;
         }
         _ => {
-            length = input.read_u32::<BigEndian>()?;
+            length = match enc_type {
+                constant::RDB_32BITLEN => input.read_u32::<BigEndian>()? as u64,
+                constant::RDB_64BITLEN => input.read_u64::<BigEndian>()?,
+                _ => {
+                    return Err(other_error(format!(
+                        "Unknown length encoding: {}",
+                        enc_type
+                    )))
+                }
+            };
         }
     }
 
     Ok((length, is_encoded))
 }
 
+pub fn read_length_with_encoding<R: Read>(input: &mut R) -> RdbResult<(u32, bool)> {
+    let (length, is_encoded) = read_length_with_encoding_u64(input)?;
+    if length > u32::MAX as u64 {
+        return Err(other_error(format!("Length too big: {}", length)));
+    }
+
+    Ok((length as u32, is_encoded))
+}
+
 pub fn read_length<R: Read>(input: &mut R) -> RdbResult<u32> {
     let (length, _) = read_length_with_encoding(input)?;
     Ok(length)
 }
 
+pub(crate) fn read_length_u64<R: Read>(input: &mut R) -> RdbResult<u64> {
+    let (length, _) = read_length_with_encoding_u64(input)?;
+    Ok(length)
+}
+
 pub fn verify_magic<R: Read>(input: &mut R) -> RdbOk {
     let mut magic = [0; 5];
     if input.read(&mut magic)? != 5 {
@@ -72,54 +112,84 @@ pub fn verify_magic<R: Read>(input: &mut R) -> RdbOk {
     }
 }
 
-pub fn verify_version<R: Read>(input: &mut R) -> RdbOk {
+fn read_version_number<R: Read>(input: &mut R) -> RdbResult<u32> {
     let mut version = [0; 4];
     if input.read(&mut version)? != 4 {
         return Err(other_error("Could not read enough bytes for the version"));
     }
 
+    if !version.iter().all(u8::is_ascii_digit) {
+        return Err(other_error("Invalid version number"));
+    }
+
     let version = (version[0] - 48) as u32 * 1000
         + (version[1] - 48) as u32 * 100
         + (version[2] - 48) as u32 * 10
         + (version[3] - 48) as u32;
 
-    let is_ok = version >= version::SUPPORTED_MINIMUM && version <= version::SUPPORTED_MAXIMUM;
+    Ok(version)
+}
 
-    if is_ok {
-        Ok(())
-    } else {
-        Err(other_error(format!(
-            "Version {} RDB files are not supported. Supported versions are {}-{}",
-            version,
-            version::SUPPORTED_MINIMUM,
-            version::SUPPORTED_MAXIMUM
-        )))
-    }
+pub fn read_version<R: Read>(input: &mut R) -> RdbResult<u32> {
+    let version = read_version_number(input)?;
+    Limits::default().check_version(version)?;
+
+    Ok(version)
+}
+
+pub fn verify_version<R: Read>(input: &mut R) -> RdbOk {
+    read_version(input)?;
+    Ok(())
 }
 
 pub fn read_blob<R: Read>(input: &mut R) -> RdbResult<Vec<u8>> {
+    read_blob_limited(input, &Limits::default())
+}
+
+/// LZF expands at most 3 bytes, a back reference, to 264 bytes
+const LZF_MAX_EXPANSION: u64 = 88;
+
+pub(crate) fn read_blob_limited<R: Read>(input: &mut R, limits: &Limits) -> RdbResult<Vec<u8>> {
     let (length, is_encoded) = read_length_with_encoding(input)?;
 
     if is_encoded {
         let result = match length {
             encoding::INT8 => helper::int_to_vec(input.read_i8()? as i32),
             encoding::INT16 => helper::int_to_vec(input.read_i16::<LittleEndian>()? as i32),
-            encoding::INT32 => helper::int_to_vec(input.read_i32::<LittleEndian>()? as i32),
+            encoding::INT32 => helper::int_to_vec(input.read_i32::<LittleEndian>()?),
             encoding::LZF => {
                 let compressed_length = read_length(input)?;
                 let real_length = read_length(input)?;
+                limits.check_string_size(compressed_length as u64)?;
+                limits.check_decompressed_size(real_length as u64)?;
+                if real_length as u64 > compressed_length as u64 * LZF_MAX_EXPANSION {
+                    return Err(other_error(format!(
+                        "Invalid length of compressed string: {} bytes from {}",
+                        real_length, compressed_length
+                    )));
+                }
+
                 let data = read_exact(input, compressed_length as usize)?;
-                lzf::decompress(&data, real_length as usize).unwrap()
+                lzf::decompress(&data, real_length as usize)
+                    .map_err(|err| other_error(format!("Decompressing string failed: {}", err)))?
             }
-            _ => panic!("Unknown encoding: {}", length),
+            _ => return Err(other_error(format!("Unknown encoding: {}", length))),
         };
 
         Ok(result)
     } else {
+        limits.check_string_size(length as u64)?;
         read_exact(input, length as usize)
     }
 }
 
+fn parse_score(score: &[u8]) -> RdbResult<f64> {
+    str::from_utf8(score)
+        .ok()
+        .and_then(|score| score.parse::<f64>().ok())
+        .ok_or_else(|| other_error("Invalid sorted set score"))
+}
+
 fn read_ziplist_metadata<T: Read>(input: &mut T) -> RdbResult<(u32, u32, u16)> {
     let zlbytes = input.read_u32::<LittleEndian>()?;
     let zltail = input.read_u32::<LittleEndian>()?;
@@ -128,112 +198,258 @@ fn read_ziplist_metadata<T: Read>(input: &mut T) -> RdbResult<(u32, u32, u16)> {
     Ok((zlbytes, zltail, zllen))
 }
 
+impl RdbParser<std::io::Empty, crate::formatter::Nil, crate::filter::Simple> {
+    pub fn builder() -> RdbParserBuilder {
+        RdbParserBuilder::new()
+    }
+}
+
 impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
     pub fn new(input: R, formatter: F, filter: L) -> RdbParser<R, F, L> {
+        RdbParser::with_limits(input, formatter, filter, Limits::default())
+    }
+
+    pub(crate) fn with_limits(
+        input: R,
+        formatter: F,
+        filter: L,
+        limits: Limits,
+    ) -> RdbParser<R, F, L> {
         RdbParser {
             input,
             formatter,
             filter,
             last_expiretime: None,
+            last_idle: None,
+            last_freq: None,
+            last_database: 0,
+            databases: HashMap::new(),
+            sources: HashMap::new(),
+            version: 0,
+            limits,
+            keys: 0,
+            aux: AuxInfo::default(),
+            aux_done: false,
         }
     }
 
-    pub fn parse(&mut self) -> RdbOk {
-        verify_magic(&mut self.input)?;
-        verify_version(&mut self.input)?;
+    pub(crate) fn map_databases(&mut self, databases: HashMap<u32, u32>) {
+        self.databases = databases;
+    }
 
-        self.formatter.start_rdb()?;
+    /// The aux fields read so far
+    pub fn aux_info(&self) -> &AuxInfo {
+        &self.aux
+    }
+
+    /// Returns the formatter, e.g. to get back its writer once parsing is done.
+    pub fn into_formatter(self) -> F {
+        self.formatter
+    }
+
+    pub fn parse(&mut self) -> RdbOk {
+        self.read_header()?;
 
-        let mut last_database: u32 = 0;
         loop {
             let next_op = self.input.read_u8()?;
 
-            match next_op {
-                op_code::SELECTDB => {
-                    last_database = unwrap_or_panic!(read_length(&mut self.input));
-                    if self.filter.matches_db(last_database) {
-                        self.formatter.start_database(last_database)?;
-                    }
-                }
-                op_code::EOF => {
-                    self.formatter.end_database(last_database)?;
-                    self.formatter.end_rdb()?;
-
-                    let mut checksum = Vec::new();
-                    let len = self.input.read_to_end(&mut checksum)?;
-                    if len > 0 {
-                        self.formatter.checksum(&checksum)?;
+            if next_op == op_code::EOF {
+                // Only the checksum is read, as AOF files continue with commands after it
+                let mut checksum = Vec::new();
+                (&mut self.input).take(8).read_to_end(&mut checksum)?;
+                self.end(&checksum)?;
+                break;
+            }
+
+            if self.parse_entry(next_op)? == Control::Stop {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_blob(&mut self) -> RdbResult<Vec<u8>> {
+        read_blob_limited(&mut self.input, &self.limits)
+    }
+
+    /// Adds the `count` elements of a quicklist node to `elements` and checks the total.
+    fn count_elements(&self, elements: &mut u64, count: u64) -> RdbOk {
+        *elements += count;
+        self.limits.check_collection_length(*elements)
+    }
+
+    fn read_collection_length(&mut self) -> RdbResult<u32> {
+        let length = read_length(&mut self.input)?;
+        self.limits.check_collection_length(length as u64)?;
+
+        Ok(length)
+    }
+
+    pub(crate) fn read_header(&mut self) -> RdbOk {
+        verify_magic(&mut self.input)?;
+        self.version = read_version_number(&mut self.input)?;
+        self.limits.check_version(self.version)?;
+
+        self.formatter.start_rdb()
+    }
+
+    fn end_aux_fields(&mut self) -> RdbOk {
+        if !self.aux_done {
+            self.aux_done = true;
+            self.formatter.aux_info(&self.aux)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn end(&mut self, checksum: &[u8]) -> RdbOk {
+        self.end_aux_fields()?;
+        self.formatter.end_database(self.last_database)?;
+        self.formatter.end_rdb()?;
+
+        if !checksum.is_empty() {
+            self.formatter.checksum(checksum)?;
+        }
+
+        Ok(())
+    }
+
+    /// Parses the payload of `next_op`, which must not be the EOF opcode.
+    ///
+    /// Returns `Control::Stop` if the formatter asked to stop parsing.
+    pub(crate) fn parse_entry(&mut self, next_op: u8) -> RdbResult<Control> {
+        if next_op != op_code::AUX {
+            self.end_aux_fields()?;
+        }
+
+        match next_op {
+            op_code::SELECTDB => {
+                let source = read_length(&mut self.input)?;
+                self.last_database = self.databases.get(&source).copied().unwrap_or(source);
+                if self.filter.matches_db(self.last_database) {
+                    if let Some(other) = self.sources.insert(self.last_database, source) {
+                        if other != source {
+                            return Err(other_error(format!(
+                                "Databases {} and {} are both mapped to database {}",
+                                other, source, self.last_database
+                            )));
+                        }
                     }
-                    break;
+                    self.formatter.start_database(self.last_database)?;
                 }
-                op_code::EXPIRETIME_MS => {
-                    let expiretime_ms = self.input.read_u64::<LittleEndian>()?;
-                    self.last_expiretime = Some(expiretime_ms);
-                }
-                op_code::EXPIRETIME => {
-                    let expiretime = self.input.read_u32::<BigEndian>()?;
-                    self.last_expiretime = Some(expiretime as u64 * 1000);
-                }
-                op_code::RESIZEDB => {
-                    let db_size = read_length(&mut self.input)?;
-                    let expires_size = read_length(&mut self.input)?;
+            }
+            op_code::EXPIRETIME_MS => {
+                let expiretime_ms = self.input.read_u64::<LittleEndian>()?;
+                self.last_expiretime = Some(expiretime_ms);
+            }
+            op_code::EXPIRETIME => {
+                let expiretime = self.input.read_u32::<LittleEndian>()?;
+                self.last_expiretime = Some(expiretime as u64 * 1000);
+            }
+            op_code::RESIZEDB => {
+                let db_size = read_length(&mut self.input)?;
+                let expires_size = read_length(&mut self.input)?;
 
-                    self.formatter.resizedb(db_size, expires_size)?;
-                }
-                op_code::AUX => {
-                    let auxkey = read_blob(&mut self.input)?;
-                    let auxval = read_blob(&mut self.input)?;
+                self.formatter.resizedb(db_size, expires_size)?;
+            }
+            op_code::AUX => {
+                let auxkey = self.read_blob()?;
+                let auxval = self.read_blob()?;
 
-                    self.formatter.aux_field(&auxkey, &auxval)?;
-                }
-                _ => {
-                    if self.filter.matches_db(last_database) {
-                        let key = read_blob(&mut self.input)?;
+                self.aux.update(&auxkey, &auxval);
+                self.formatter.aux_field(&auxkey, &auxval)?;
+            }
+            op_code::IDLE => {
+                self.last_idle = Some(read_length_u64(&mut self.input)?);
+            }
+            op_code::FREQ => {
+                self.last_freq = Some(self.input.read_u8()?);
+            }
+            op_code::MODULE_AUX => {
+                let mut capture = Capture::new(&mut self.input);
+                skip_nested(&mut capture, op_code::MODULE_AUX, &self.limits)?;
+                let raw = capture.data;
 
-                        if self.filter.matches_type(next_op) && self.filter.matches_key(&key) {
-                            self.read_type(&key, next_op)?;
-                        } else {
-                            self.skip_object(next_op)?;
+                self.formatter.module_aux(&raw)?;
+            }
+            op_code::FUNCTION2 => {
+                let code = self.read_blob()?;
+                self.formatter.function(&code)?;
+            }
+            op_code::FUNCTION_PRE_GA => {
+                return Err(other_error(
+                    "Functions in the format of Redis 7.0 release candidates are not supported",
+                ));
+            }
+            _ => {
+                self.keys += 1;
+                self.limits.check_keys(self.keys)?;
+                Type::try_from_encoding(next_op)?;
+
+                let mut control = Control::Continue;
+                if self.filter.matches_db(self.last_database) {
+                    let key = self.read_blob()?;
+
+                    if self.filter.matches_type(next_op) && self.filter.matches_key(&key) {
+                        if self.last_idle.is_some() || self.last_freq.is_some() {
+                            self.formatter
+                                .eviction_info(&key, self.last_idle, self.last_freq)?;
                         }
+                        control = self.read_type(&key, next_op)?;
                     } else {
-                        self.skip_key_and_object(next_op)?;
+                        skip_object(&mut self.input, next_op)?;
                     }
-
-                    self.last_expiretime = None;
+                } else {
+                    skip_blob(&mut self.input)?;
+                    skip_object(&mut self.input, next_op)?;
                 }
+
+                self.last_expiretime = None;
+                self.last_idle = None;
+                self.last_freq = None;
+                return Ok(control);
             }
         }
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn read_linked_list(&mut self, key: &[u8], typ: Type) -> RdbOk {
-        let mut len = read_length(&mut self.input)?;
+    fn read_linked_list(&mut self, key: &[u8], typ: Type) -> RdbResult<Control> {
+        let len = self.read_collection_length()?;
+
+        let mut control = match typ {
+            Type::List => self.formatter.start_list(
+                key,
+                len,
+                self.last_expiretime,
+                EncodingType::LinkedList,
+            )?,
+            Type::Set => self.formatter.start_set(
+                key,
+                len,
+                self.last_expiretime,
+                EncodingType::LinkedList,
+            )?,
+            _ => panic!("Unknown encoding type for linked list"),
+        };
 
-        match typ {
-            Type::List => {
-                self.formatter.start_list(
-                    key,
-                    len,
-                    self.last_expiretime,
-                    EncodingType::LinkedList,
-                )?;
-            }
-            Type::Set => {
-                self.formatter.start_set(
-                    key,
-                    len,
-                    self.last_expiretime,
-                    EncodingType::LinkedList,
-                )?;
+        for _ in 0..len {
+            match control {
+                Control::Continue => {
+                    let blob = self.read_blob()?;
+                    control = match typ {
+                        Type::List => self.formatter.list_element(key, &blob)?,
+                        _ => self.formatter.set_element(key, &blob)?,
+                    };
+                }
+                Control::SkipKey => skip_blob(&mut self.input)?,
+                Control::Stop => return Ok(Control::Stop),
             }
-            _ => panic!("Unknown encoding type for linked list"),
         }
 
-        while len > 0 {
-            let blob = read_blob(&mut self.input)?;
-            self.formatter.list_element(key, &blob)?;
-            len -= 1;
+        if control == Control::Stop {
+            return Ok(Control::Stop);
         }
 
         match typ {
@@ -242,91 +458,125 @@ impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
             _ => panic!("Unknown encoding type for linked list"),
         }
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn read_sorted_set_type_2(&mut self, key: &[u8]) -> RdbOk {
-        let mut set_items = unwrap_or_panic!(read_length(&mut self.input));
+    fn read_sorted_set_type_2(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let set_items = self.read_collection_length()?;
 
-        self.formatter.start_sorted_set(
+        let mut control = self.formatter.start_sorted_set(
             key,
             set_items,
             self.last_expiretime,
             EncodingType::Hashtable,
         )?;
 
-        while set_items > 0 {
-            let val = read_blob(&mut self.input)?;
-
-            let score = self.input.read_f64::<LittleEndian>()?;
+        for _ in 0..set_items {
+            match control {
+                Control::Continue => {
+                    let val = self.read_blob()?;
+                    let score = self.input.read_f64::<LittleEndian>()?;
 
-            self.formatter.sorted_set_element(key, score, &val)?;
+                    control = self.formatter.sorted_set_element(key, score, &val)?;
+                }
+                Control::SkipKey => {
+                    skip_blob(&mut self.input)?;
+                    helper::skip(&mut self.input, 8)?;
+                }
+                Control::Stop => return Ok(Control::Stop),
+            }
+        }
 
-            set_items -= 1;
+        if control == Control::Stop {
+            return Ok(Control::Stop);
         }
 
         self.formatter.end_sorted_set(key)?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn read_sorted_set(&mut self, key: &[u8]) -> RdbOk {
-        let mut set_items = unwrap_or_panic!(read_length(&mut self.input));
+    fn read_sorted_set(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let set_items = self.read_collection_length()?;
 
-        self.formatter.start_sorted_set(
+        let mut control = self.formatter.start_sorted_set(
             key,
             set_items,
             self.last_expiretime,
             EncodingType::Hashtable,
         )?;
 
-        while set_items > 0 {
-            let val = read_blob(&mut self.input)?;
-            let score_length = self.input.read_u8()?;
-            let score = match score_length {
-                253 => f64::NAN,
-                254 => f64::INFINITY,
-                255 => f64::NEG_INFINITY,
-                _ => {
-                    let tmp = read_exact(&mut self.input, score_length as usize)?;
-                    unsafe { str::from_utf8_unchecked(&tmp) }
-                        .parse::<f64>()
-                        .unwrap()
-                }
-            };
+        for _ in 0..set_items {
+            match control {
+                Control::Continue => {
+                    let val = self.read_blob()?;
+                    let score_length = self.input.read_u8()?;
+                    let score = match score_length {
+                        253 => f64::NAN,
+                        254 => f64::INFINITY,
+                        255 => f64::NEG_INFINITY,
+                        _ => {
+                            let tmp = read_exact(&mut self.input, score_length as usize)?;
+                            parse_score(&tmp)?
+                        }
+                    };
 
-            self.formatter.sorted_set_element(key, score, &val)?;
+                    control = self.formatter.sorted_set_element(key, score, &val)?;
+                }
+                Control::SkipKey => {
+                    skip_blob(&mut self.input)?;
+                    // Infinite and NaN scores have no digits
+                    let score_length = self.input.read_u8()?;
+                    if score_length < 253 {
+                        helper::skip(&mut self.input, score_length as u64)?;
+                    }
+                }
+                Control::Stop => return Ok(Control::Stop),
+            }
+        }
 
-            set_items -= 1;
+        if control == Control::Stop {
+            return Ok(Control::Stop);
         }
 
         self.formatter.end_sorted_set(key)?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn read_hash(&mut self, key: &[u8]) -> RdbOk {
-        let mut hash_items = read_length(&mut self.input)?;
+    fn read_hash(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let hash_items = self.read_collection_length()?;
 
-        self.formatter.start_hash(
+        let mut control = self.formatter.start_hash(
             key,
             hash_items,
             self.last_expiretime,
             EncodingType::Hashtable,
         )?;
 
-        while hash_items > 0 {
-            let field = read_blob(&mut self.input)?;
-            let val = read_blob(&mut self.input)?;
+        for _ in 0..hash_items {
+            match control {
+                Control::Continue => {
+                    let field = self.read_blob()?;
+                    let val = self.read_blob()?;
 
-            self.formatter.hash_element(key, &field, &val)?;
+                    control = self.formatter.hash_element(key, &field, &val)?;
+                }
+                Control::SkipKey => {
+                    skip_blob(&mut self.input)?;
+                    skip_blob(&mut self.input)?;
+                }
+                Control::Stop => return Ok(Control::Stop),
+            }
+        }
 
-            hash_items -= 1;
+        if control == Control::Stop {
+            return Ok(Control::Stop);
         }
 
         self.formatter.end_hash(key)?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn read_ziplist_entry<T: Read>(&mut self, ziplist: &mut T) -> RdbResult<ZiplistEntry> {
@@ -341,26 +591,21 @@ impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
             }
         }
 
-        let length: u64;
-        let number_value: i64;
-
         // 2. Read flag or number value
         let flag = ziplist.read_u8()?;
 
-        match (flag & 0xC0) >> 6 {
-            0 => length = (flag & 0x3F) as u64,
+        let length: u64 = match (flag & 0xC0) >> 6 {
+            0 => (flag & 0x3F) as u64,
             1 => {
                 let next_byte = ziplist.read_u8()?;
-                length = (((flag & 0x3F) as u64) << 8) | next_byte as u64;
-            }
-            2 => {
-                length = ziplist.read_u32::<BigEndian>()? as u64;
+                (((flag & 0x3F) as u64) << 8) | next_byte as u64
             }
+            2 => ziplist.read_u32::<BigEndian>()? as u64,
             _ => {
-                match (flag & 0xF0) >> 4 {
-                    0xC => number_value = ziplist.read_i16::<LittleEndian>()? as i64,
-                    0xD => number_value = ziplist.read_i32::<LittleEndian>()? as i64,
-                    0xE => number_value = ziplist.read_i64::<LittleEndian>()? as i64,
+                let number_value: i64 = match (flag & 0xF0) >> 4 {
+                    0xC => ziplist.read_i16::<LittleEndian>()? as i64,
+                    0xD => ziplist.read_i32::<LittleEndian>()? as i64,
+                    0xE => ziplist.read_i64::<LittleEndian>()?,
                     0xF => match flag & 0xF {
                         0 => {
                             let mut bytes = [0; 3];
@@ -376,25 +621,22 @@ impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
                                 ^ 48)
                                 >> 8;
 
-                            number_value = number as i64;
-                        }
-                        0xE => {
-                            number_value = ziplist.read_i8()? as i64;
-                        }
-                        _ => {
-                            number_value = (flag & 0xF) as i64 - 1;
+                            number as i64
                         }
+                        0xE => ziplist.read_i8()? as i64,
+                        _ => (flag & 0xF) as i64 - 1,
                     },
                     _ => {
-                        panic!("Flag not handled: {}", flag);
+                        return Err(other_error(format!("Flag not handled: {}", flag)));
                     }
-                }
+                };
 
                 return Ok(ZiplistEntry::Number(number_value));
             }
-        }
+        };
 
         // 3. Read value
+        self.limits.check_string_size(length)?;
         let rawval = read_exact(ziplist, length as usize)?;
         Ok(ZiplistEntry::String(rawval))
     }
@@ -407,14 +649,15 @@ impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
         }
     }
 
-    fn read_list_ziplist(&mut self, key: &[u8]) -> RdbOk {
-        let ziplist = read_blob(&mut self.input)?;
+    fn read_list_ziplist(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let ziplist = self.read_blob()?;
         let raw_length = ziplist.len() as u64;
 
         let mut reader = Cursor::new(ziplist);
         let (_zlbytes, _zltail, zllen) = read_ziplist_metadata(&mut reader)?;
+        self.limits.check_collection_length(zllen as u64)?;
 
-        self.formatter.start_list(
+        let mut control = self.formatter.start_list(
             key,
             zllen as u32,
             self.last_expiretime,
@@ -422,31 +665,46 @@ impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
         )?;
 
         for _ in 0..zllen {
+            if control != Control::Continue {
+                break;
+            }
+
             let entry = self.read_ziplist_entry_string(&mut reader)?;
-            self.formatter.list_element(key, &entry)?;
+            control = self.formatter.list_element(key, &entry)?;
         }
 
-        let last_byte = reader.read_u8()?;
-        if last_byte != 0xFF {
-            return Err(other_error("Invalid end byte of ziplist"));
+        match control {
+            Control::Continue => {
+                let last_byte = reader.read_u8()?;
+                if last_byte != 0xFF {
+                    return Err(other_error("Invalid end byte of ziplist"));
+                }
+            }
+            Control::SkipKey => {}
+            Control::Stop => return Ok(Control::Stop),
         }
 
         self.formatter.end_list(key)?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn read_hash_ziplist(&mut self, key: &[u8]) -> RdbOk {
-        let ziplist = read_blob(&mut self.input)?;
+    fn read_hash_ziplist(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let ziplist = self.read_blob()?;
         let raw_length = ziplist.len() as u64;
 
         let mut reader = Cursor::new(ziplist);
         let (_zlbytes, _zltail, zllen) = read_ziplist_metadata(&mut reader)?;
+        self.limits.check_collection_length(zllen as u64)?;
 
-        assert!(zllen % 2 == 0);
+        if zllen % 2 != 0 {
+            return Err(other_error(
+                "Odd number of ziplist entries for key-value pairs",
+            ));
+        }
         let zllen = zllen / 2;
 
-        self.formatter.start_hash(
+        let mut control = self.formatter.start_hash(
             key,
             zllen as u32,
             self.last_expiretime,
@@ -454,64 +712,95 @@ impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
         )?;
 
         for _ in 0..zllen {
+            if control != Control::Continue {
+                break;
+            }
+
             let field = self.read_ziplist_entry_string(&mut reader)?;
             let value = self.read_ziplist_entry_string(&mut reader)?;
-            self.formatter.hash_element(key, &field, &value)?;
+            control = self.formatter.hash_element(key, &field, &value)?;
         }
 
-        let last_byte = reader.read_u8()?;
-        if last_byte != 0xFF {
-            return Err(other_error("Invalid end byte of ziplist"));
+        match control {
+            Control::Continue => {
+                let last_byte = reader.read_u8()?;
+                if last_byte != 0xFF {
+                    return Err(other_error("Invalid end byte of ziplist"));
+                }
+            }
+            Control::SkipKey => {}
+            Control::Stop => return Ok(Control::Stop),
         }
 
         self.formatter.end_hash(key)?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn read_sortedset_ziplist(&mut self, key: &[u8]) -> RdbOk {
-        let ziplist = read_blob(&mut self.input)?;
+    fn read_sortedset_ziplist(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let ziplist = self.read_blob()?;
         let raw_length = ziplist.len() as u64;
 
         let mut reader = Cursor::new(ziplist);
         let (_zlbytes, _zltail, zllen) = read_ziplist_metadata(&mut reader)?;
+        self.limits.check_collection_length(zllen as u64)?;
 
-        self.formatter.start_sorted_set(
+        if zllen % 2 != 0 {
+            return Err(other_error(
+                "Odd number of ziplist entries for key-value pairs",
+            ));
+        }
+        let zllen = zllen / 2;
+
+        let mut control = self.formatter.start_sorted_set(
             key,
             zllen as u32,
             self.last_expiretime,
             EncodingType::Ziplist(raw_length),
         )?;
 
-        assert!(zllen % 2 == 0);
-        let zllen = zllen / 2;
-
         for _ in 0..zllen {
+            if control != Control::Continue {
+                break;
+            }
+
             let entry = self.read_ziplist_entry_string(&mut reader)?;
             let score = self.read_ziplist_entry_string(&mut reader)?;
-            let score = str::from_utf8(&score).unwrap().parse::<f64>().unwrap();
-            self.formatter.sorted_set_element(key, score, &entry)?;
+            let score = parse_score(&score)?;
+            control = self.formatter.sorted_set_element(key, score, &entry)?;
         }
 
-        let last_byte = reader.read_u8()?;
-        if last_byte != 0xFF {
-            return Err(other_error("Invalid end byte of ziplist"));
+        match control {
+            Control::Continue => {
+                let last_byte = reader.read_u8()?;
+                if last_byte != 0xFF {
+                    return Err(other_error("Invalid end byte of ziplist"));
+                }
+            }
+            Control::SkipKey => {}
+            Control::Stop => return Ok(Control::Stop),
         }
 
         self.formatter.end_sorted_set(key)?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn read_quicklist_ziplist(&mut self, key: &[u8]) -> RdbOk {
-        let ziplist = read_blob(&mut self.input)?;
+    fn read_quicklist_ziplist(&mut self, key: &[u8], elements: &mut u64) -> RdbResult<Control> {
+        let mut input = Counter::new(&mut self.input);
+        let ziplist = read_blob_limited(&mut input, &self.limits)?;
+        self.formatter.list_node(key, input.count)?;
 
         let mut reader = Cursor::new(ziplist);
         let (_zlbytes, _zltail, zllen) = read_ziplist_metadata(&mut reader)?;
+        self.count_elements(elements, zllen as u64)?;
 
         for _ in 0..zllen {
             let entry = self.read_ziplist_entry_string(&mut reader)?;
-            self.formatter.list_element(key, &entry)?;
+            let control = self.formatter.list_element(key, &entry)?;
+            if control != Control::Continue {
+                return Ok(control);
+            }
         }
 
         let last_byte = reader.read_u8()?;
@@ -519,22 +808,27 @@ impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
             return Err(other_error("Invalid end byte of ziplist (quicklist)"));
         }
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
     fn read_zipmap_entry<T: Read>(&mut self, next_byte: u8, zipmap: &mut T) -> RdbResult<Vec<u8>> {
-        let elem_len;
-        match next_byte {
-            253 => elem_len = zipmap.read_u32::<LittleEndian>().unwrap(),
-            254 | 255 => panic!("Invalid length value in zipmap: {}", next_byte),
-            _ => elem_len = next_byte as u32,
-        }
+        let elem_len = match next_byte {
+            253 => zipmap.read_u32::<LittleEndian>()?,
+            254 | 255 => {
+                return Err(other_error(format!(
+                    "Invalid length value in zipmap: {}",
+                    next_byte
+                )))
+            }
+            _ => next_byte as u32,
+        };
 
+        self.limits.check_string_size(elem_len as u64)?;
         read_exact(zipmap, elem_len as usize)
     }
 
-    fn read_hash_zipmap(&mut self, key: &[u8]) -> RdbOk {
-        let zipmap = read_blob(&mut self.input)?;
+    fn read_hash_zipmap(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let zipmap = self.read_blob()?;
         let raw_length = zipmap.len() as u64;
 
         let mut reader = Cursor::new(zipmap);
@@ -551,14 +845,14 @@ impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
             size = 0;
         }
 
-        self.formatter.start_hash(
+        let mut control = self.formatter.start_hash(
             key,
             size as u32,
             self.last_expiretime,
             EncodingType::Zipmap(raw_length),
         )?;
 
-        loop {
+        while control == Control::Continue {
             let next_byte = reader.read_u8()?;
 
             if next_byte == 0xFF {
@@ -571,7 +865,7 @@ impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
             let _free = reader.read_u8()?;
             let value = self.read_zipmap_entry(next_byte, &mut reader)?;
 
-            self.formatter.hash_element(key, &field, &value)?;
+            control = self.formatter.hash_element(key, &field, &value)?;
 
             if length > 0 {
                 length -= 1;
@@ -587,20 +881,25 @@ impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
             }
         }
 
+        if control == Control::Stop {
+            return Ok(Control::Stop);
+        }
+
         self.formatter.end_hash(key)?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn read_set_intset(&mut self, key: &[u8]) -> RdbOk {
-        let intset = read_blob(&mut self.input)?;
+    fn read_set_intset(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let intset = self.read_blob()?;
         let raw_length = intset.len() as u64;
 
         let mut reader = Cursor::new(intset);
         let byte_size = reader.read_u32::<LittleEndian>()?;
         let intset_length = reader.read_u32::<LittleEndian>()?;
+        self.limits.check_collection_length(intset_length as u64)?;
 
-        self.formatter.start_set(
+        let mut control = self.formatter.start_set(
             key,
             intset_length,
             self.last_expiretime,
@@ -608,124 +907,574 @@ impl<R: Read, F: Formatter, L: Filter> RdbParser<R, F, L> {
         )?;
 
         for _ in 0..intset_length {
+            if control != Control::Continue {
+                break;
+            }
+
             let val = match byte_size {
                 2 => reader.read_i16::<LittleEndian>()? as i64,
                 4 => reader.read_i32::<LittleEndian>()? as i64,
                 8 => reader.read_i64::<LittleEndian>()?,
-                _ => panic!("unhandled byte size in intset: {}", byte_size),
+                _ => {
+                    return Err(other_error(format!(
+                        "unhandled byte size in intset: {}",
+                        byte_size
+                    )))
+                }
             };
 
-            self.formatter
+            control = self
+                .formatter
                 .set_element(key, val.to_string().as_bytes())?;
         }
 
+        if control == Control::Stop {
+            return Ok(Control::Stop);
+        }
+
         self.formatter.end_set(key)?;
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn read_quicklist(&mut self, key: &[u8]) -> RdbOk {
-        let len = read_length(&mut self.input)?;
+    fn read_quicklist(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let len = self.read_collection_length()?;
 
-        self.formatter
-            .start_set(key, 0, self.last_expiretime, EncodingType::Quicklist)?;
+        let mut control =
+            self.formatter
+                .start_list(key, 0, self.last_expiretime, EncodingType::Quicklist)?;
+        // The limit applies to the elements, the length only counts the nodes
+        let mut elements = 0;
         for _ in 0..len {
-            self.read_quicklist_ziplist(key)?;
+            match control {
+                Control::Continue => control = self.read_quicklist_ziplist(key, &mut elements)?,
+                Control::SkipKey => skip_blob(&mut self.input)?,
+                Control::Stop => return Ok(Control::Stop),
+            }
         }
-        self.formatter.end_set(key)?;
 
-        Ok(())
+        if control == Control::Stop {
+            return Ok(Control::Stop);
+        }
+
+        self.formatter.end_list(key)?;
+
+        Ok(Control::Continue)
     }
 
-    fn read_type(&mut self, key: &[u8], value_type: u8) -> RdbOk {
-        match value_type {
-            encoding_type::STRING => {
-                let val = read_blob(&mut self.input)?;
-                self.formatter.set(key, &val, self.last_expiretime)?;
-            }
-            encoding_type::LIST => self.read_linked_list(key, Type::List)?,
-            encoding_type::SET => self.read_linked_list(key, Type::Set)?,
-            encoding_type::ZSET => self.read_sorted_set(key)?,
-            encoding_type::ZSET_2 => self.read_sorted_set_type_2(key)?,
-            encoding_type::HASH => self.read_hash(key)?,
-            encoding_type::HASH_ZIPMAP => self.read_hash_zipmap(key)?,
-            encoding_type::LIST_ZIPLIST => self.read_list_ziplist(key)?,
-            encoding_type::SET_INTSET => self.read_set_intset(key)?,
-            encoding_type::ZSET_ZIPLIST => self.read_sortedset_ziplist(key)?,
-            encoding_type::HASH_ZIPLIST => self.read_hash_ziplist(key)?,
-            encoding_type::LIST_QUICKLIST => self.read_quicklist(key)?,
-            _ => panic!("Value Type not implemented: {}", value_type),
-        };
+    /// Reads a listpack and decodes all its entries.
+    ///
+    /// Returns the entries and the size of the listpack.
+    fn read_listpack(&mut self) -> RdbResult<(Vec<Vec<u8>>, u64)> {
+        let listpack = self.read_blob()?;
+        self.decode_listpack(listpack)
+    }
 
-        Ok(())
+    fn decode_listpack(&self, listpack: Vec<u8>) -> RdbResult<(Vec<Vec<u8>>, u64)> {
+        let raw_length = listpack.len() as u64;
+
+        let mut reader = Cursor::new(listpack);
+        let _total_bytes = reader.read_u32::<LittleEndian>()?;
+        // The number of entries is only known up to 65534
+        let count = reader.read_u16::<LittleEndian>()?;
+
+        let mut entries = Vec::new();
+        loop {
+            let position = reader.position() as usize;
+            match reader.get_ref().get(position) {
+                Some(0xFF) => break,
+                Some(_) => {}
+                None => return Err(other_error("Missing end byte of listpack")),
+            }
+
+            entries.push(read_listpack_entry(&mut reader, &self.limits)?);
+            self.limits.check_collection_length(entries.len() as u64)?;
+        }
+
+        if count != u16::MAX && count as usize != entries.len() {
+            return Err(other_error(format!(
+                "Listpack contains {} entries instead of {}",
+                entries.len(),
+                count
+            )));
+        }
+
+        Ok((entries, raw_length))
     }
 
-    fn skip(&mut self, skip_bytes: usize) -> RdbResult<()> {
-        let mut buf = vec![0; skip_bytes];
-        self.input.read_exact(&mut buf)?;
+    fn read_list_listpack(
+        &mut self,
+        key: &[u8],
+        listpack: Vec<u8>,
+        elements: &mut u64,
+    ) -> RdbResult<Control> {
+        let (entries, _) = self.decode_listpack(listpack)?;
+        self.count_elements(elements, entries.len() as u64)?;
+
+        for entry in entries {
+            let control = self.formatter.list_element(key, &entry)?;
+            if control != Control::Continue {
+                return Ok(control);
+            }
+        }
 
-        Ok(())
+        Ok(Control::Continue)
     }
 
-    fn skip_blob(&mut self) -> RdbResult<()> {
-        let (len, is_encoded) = unwrap_or_panic!(read_length_with_encoding(&mut self.input));
-        let skip_bytes;
+    fn read_quicklist_2(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let len = self.read_collection_length()?;
 
-        if is_encoded {
-            skip_bytes = match len {
-                encoding::INT8 => 1,
-                encoding::INT16 => 2,
-                encoding::INT32 => 4,
-                encoding::LZF => {
-                    let compressed_length = unwrap_or_panic!(read_length(&mut self.input));
-                    let _real_length = unwrap_or_panic!(read_length(&mut self.input));
-                    compressed_length
+        let mut control =
+            self.formatter
+                .start_list(key, 0, self.last_expiretime, EncodingType::Quicklist)?;
+        // The limit applies to the elements, the length only counts the nodes
+        let mut elements = 0;
+        for _ in 0..len {
+            match control {
+                Control::Continue => {
+                    let mut input = Counter::new(&mut self.input);
+                    let container = read_length(&mut input)?;
+                    let node = read_blob_limited(&mut input, &self.limits)?;
+                    self.formatter.list_node(key, input.count)?;
+                    control = match container {
+                        quicklist_container::PLAIN => {
+                            self.count_elements(&mut elements, 1)?;
+                            self.formatter.list_element(key, &node)?
+                        }
+                        quicklist_container::PACKED => {
+                            self.read_list_listpack(key, node, &mut elements)?
+                        }
+                        _ => {
+                            return Err(other_error(format!(
+                                "Unknown quicklist container: {}",
+                                container
+                            )))
+                        }
+                    };
+                }
+                Control::SkipKey => {
+                    read_length(&mut self.input)?;
+                    skip_blob(&mut self.input)?;
                 }
-                _ => panic!("Unknown encoding: {}", len),
+                Control::Stop => return Ok(Control::Stop),
             }
-        } else {
-            skip_bytes = len;
         }
 
-        self.skip(skip_bytes as usize)
+        if control == Control::Stop {
+            return Ok(Control::Stop);
+        }
+
+        self.formatter.end_list(key)?;
+
+        Ok(Control::Continue)
+    }
+
+    fn read_hash_listpack(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let (entries, raw_length) = self.read_listpack()?;
+
+        if entries.len() % 2 != 0 {
+            return Err(other_error(
+                "Odd number of listpack entries for key-value pairs",
+            ));
+        }
+
+        let mut control = self.formatter.start_hash(
+            key,
+            (entries.len() / 2) as u32,
+            self.last_expiretime,
+            EncodingType::Listpack(raw_length),
+        )?;
+
+        for pair in entries.chunks(2) {
+            if control != Control::Continue {
+                break;
+            }
+
+            control = self.formatter.hash_element(key, &pair[0], &pair[1])?;
+        }
+
+        if control == Control::Stop {
+            return Ok(Control::Stop);
+        }
+
+        self.formatter.end_hash(key)?;
+
+        Ok(Control::Continue)
     }
 
-    fn skip_object(&mut self, enc_type: u8) -> RdbResult<()> {
-        let blobs_to_skip = match enc_type {
-            encoding_type::STRING
-            | encoding_type::HASH_ZIPMAP
-            | encoding_type::LIST_ZIPLIST
-            | encoding_type::SET_INTSET
-            | encoding_type::ZSET_ZIPLIST
-            | encoding_type::HASH_ZIPLIST => 1,
-            encoding_type::LIST | encoding_type::SET | encoding_type::LIST_QUICKLIST => {
-                unwrap_or_panic!(read_length(&mut self.input))
+    fn read_sortedset_listpack(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let (entries, raw_length) = self.read_listpack()?;
+
+        if entries.len() % 2 != 0 {
+            return Err(other_error(
+                "Odd number of listpack entries for member-score pairs",
+            ));
+        }
+
+        let mut control = self.formatter.start_sorted_set(
+            key,
+            (entries.len() / 2) as u32,
+            self.last_expiretime,
+            EncodingType::Listpack(raw_length),
+        )?;
+
+        for pair in entries.chunks(2) {
+            if control != Control::Continue {
+                break;
             }
-            encoding_type::ZSET | encoding_type::HASH => {
-                unwrap_or_panic!(read_length(&mut self.input)) * 2
+
+            let score = parse_score(&pair[1])?;
+            control = self.formatter.sorted_set_element(key, score, &pair[0])?;
+        }
+
+        if control == Control::Stop {
+            return Ok(Control::Stop);
+        }
+
+        self.formatter.end_sorted_set(key)?;
+
+        Ok(Control::Continue)
+    }
+
+    fn read_set_listpack(&mut self, key: &[u8]) -> RdbResult<Control> {
+        let (entries, raw_length) = self.read_listpack()?;
+
+        let mut control = self.formatter.start_set(
+            key,
+            entries.len() as u32,
+            self.last_expiretime,
+            EncodingType::Listpack(raw_length),
+        )?;
+
+        for entry in entries {
+            if control != Control::Continue {
+                break;
             }
-            encoding_type::ZSET_2 => {
-                let length = read_length(&mut self.input)?;
-                for _ in 0..length {
-                    self.skip_blob()?;
-                    self.skip(8)?;
+
+            control = self.formatter.set_element(key, &entry)?;
+        }
+
+        if control == Control::Stop {
+            return Ok(Control::Stop);
+        }
+
+        self.formatter.end_set(key)?;
+
+        Ok(Control::Continue)
+    }
+
+    /// Passes on values that are not decoded as they are stored.
+    fn read_raw_object(&mut self, key: &[u8], value_type: u8) -> RdbResult<Control> {
+        let mut capture = Capture::new(&mut self.input);
+        skip_nested(&mut capture, value_type, &self.limits)?;
+        let raw = capture.data;
+
+        match self
+            .formatter
+            .raw_object(key, value_type, &raw, self.last_expiretime)?
+        {
+            Control::Stop => Ok(Control::Stop),
+            _ => Ok(Control::Continue),
+        }
+    }
+
+    fn read_type(&mut self, key: &[u8], value_type: u8) -> RdbResult<Control> {
+        match value_type {
+            encoding_type::STRING => {
+                let val = self.read_blob()?;
+                match self.formatter.set(key, &val, self.last_expiretime)? {
+                    Control::Stop => Ok(Control::Stop),
+                    _ => Ok(Control::Continue),
                 }
+            }
+            encoding_type::LIST => self.read_linked_list(key, Type::List),
+            encoding_type::SET => self.read_linked_list(key, Type::Set),
+            encoding_type::ZSET => self.read_sorted_set(key),
+            encoding_type::ZSET_2 => self.read_sorted_set_type_2(key),
+            encoding_type::HASH => self.read_hash(key),
+            encoding_type::HASH_ZIPMAP => self.read_hash_zipmap(key),
+            encoding_type::LIST_ZIPLIST => self.read_list_ziplist(key),
+            encoding_type::SET_INTSET => self.read_set_intset(key),
+            encoding_type::ZSET_ZIPLIST => self.read_sortedset_ziplist(key),
+            encoding_type::HASH_ZIPLIST => self.read_hash_ziplist(key),
+            encoding_type::LIST_QUICKLIST => self.read_quicklist(key),
+            encoding_type::HASH_LISTPACK => self.read_hash_listpack(key),
+            encoding_type::ZSET_LISTPACK => self.read_sortedset_listpack(key),
+            encoding_type::LIST_QUICKLIST_2 => self.read_quicklist_2(key),
+            encoding_type::SET_LISTPACK => self.read_set_listpack(key),
+            encoding_type::STREAM_LISTPACKS
+            | encoding_type::STREAM_LISTPACKS_2
+            | encoding_type::STREAM_LISTPACKS_3
+            | encoding_type::MODULE_2 => self.read_raw_object(key, value_type),
+            _ => Err(other_error(format!(
+                "Value Type not implemented: {}",
+                value_type
+            ))),
+        }
+    }
+}
 
-                0
+/// How the value of an object is laid out after its type-specific header
+pub(crate) enum ObjectLayout {
+    /// A number of blobs
+    Blobs(u64),
+    /// A number of blobs, each followed by a binary double
+    ScoredBlobs(u64),
+    /// A number of blobs, each preceded by a length
+    Nodes(u64),
+    /// A nested structure that can only be skipped as a whole with `skip_nested`
+    Nested,
+}
+
+/// Reads the header of a blob and returns the number of bytes following it.
+pub(crate) fn read_blob_header<R: Read>(input: &mut R, limits: &Limits) -> RdbResult<u64> {
+    let (len, is_encoded) = read_length_with_encoding(input)?;
+    let payload_length = if is_encoded {
+        match len {
+            encoding::INT8 => 1,
+            encoding::INT16 => 2,
+            encoding::INT32 => 4,
+            encoding::LZF => {
+                let compressed_length = read_length(input)?;
+                let _real_length = read_length(input)?;
+                compressed_length
             }
-            _ => panic!("Unknown encoding type: {}", enc_type),
-        };
+            _ => return Err(other_error(format!("Unknown encoding: {}", len))),
+        }
+    } else {
+        len
+    };
+
+    limits.check_string_size(payload_length as u64)?;
+    Ok(payload_length as u64)
+}
 
-        for _ in 0..blobs_to_skip {
-            self.skip_blob()?
+pub(crate) fn read_object_layout<R: Read>(
+    input: &mut R,
+    enc_type: u8,
+    limits: &Limits,
+) -> RdbResult<ObjectLayout> {
+    let read_collection_length = |input: &mut R| -> RdbResult<u64> {
+        let length = read_length(input)? as u64;
+        limits.check_collection_length(length)?;
+        Ok(length)
+    };
+
+    let layout = match enc_type {
+        encoding_type::STRING
+        | encoding_type::HASH_ZIPMAP
+        | encoding_type::LIST_ZIPLIST
+        | encoding_type::SET_INTSET
+        | encoding_type::ZSET_ZIPLIST
+        | encoding_type::HASH_ZIPLIST
+        | encoding_type::HASH_LISTPACK
+        | encoding_type::ZSET_LISTPACK
+        | encoding_type::SET_LISTPACK => ObjectLayout::Blobs(1),
+        encoding_type::LIST | encoding_type::SET | encoding_type::LIST_QUICKLIST => {
+            ObjectLayout::Blobs(read_collection_length(input)?)
         }
+        encoding_type::ZSET | encoding_type::HASH => {
+            ObjectLayout::Blobs(read_collection_length(input)? * 2)
+        }
+        encoding_type::ZSET_2 => ObjectLayout::ScoredBlobs(read_collection_length(input)?),
+        encoding_type::LIST_QUICKLIST_2 => ObjectLayout::Nodes(read_collection_length(input)?),
+        encoding_type::STREAM_LISTPACKS
+        | encoding_type::STREAM_LISTPACKS_2
+        | encoding_type::STREAM_LISTPACKS_3
+        | encoding_type::MODULE_2 => ObjectLayout::Nested,
+        _ => return Err(other_error(format!("Unknown encoding type: {}", enc_type))),
+    };
+
+    Ok(layout)
+}
 
-        Ok(())
+pub fn skip_blob<R: Read>(input: &mut R) -> RdbOk {
+    let payload_length = read_blob_header(input, &Limits::default())?;
+    helper::skip(input, payload_length)
+}
+
+pub fn skip_object<R: Read>(input: &mut R, enc_type: u8) -> RdbOk {
+    match read_object_layout(input, enc_type, &Limits::default())? {
+        ObjectLayout::Blobs(count) => {
+            for _ in 0..count {
+                skip_blob(input)?;
+            }
+        }
+        ObjectLayout::ScoredBlobs(count) => {
+            for _ in 0..count {
+                skip_blob(input)?;
+                helper::skip(input, 8)?;
+            }
+        }
+        ObjectLayout::Nodes(count) => {
+            for _ in 0..count {
+                read_length_u64(input)?;
+                skip_blob(input)?;
+            }
+        }
+        ObjectLayout::Nested => skip_nested(input, enc_type, &Limits::default())?,
     }
 
-    fn skip_key_and_object(&mut self, enc_type: u8) -> RdbResult<()> {
-        self.skip_blob()?;
-        self.skip_object(enc_type)?;
-        Ok(())
+    Ok(())
+}
+
+fn skip_blob_limited<R: Read>(input: &mut R, limits: &Limits) -> RdbOk {
+    let payload_length = read_blob_header(input, limits)?;
+    helper::skip(input, payload_length)
+}
+
+fn skip_lengths<R: Read>(input: &mut R, count: usize) -> RdbOk {
+    for _ in 0..count {
+        read_length_u64(input)?;
+    }
+
+    Ok(())
+}
+
+fn read_count<R: Read>(input: &mut R, limits: &Limits) -> RdbResult<u64> {
+    let count = read_length_u64(input)?;
+    limits.check_collection_length(count)?;
+
+    Ok(count)
+}
+
+/// Skips values of the types with a nested structure, i.e. streams and module values,
+/// and the data of the module aux opcode.
+pub(crate) fn skip_nested<R: Read>(input: &mut R, value_type: u8, limits: &Limits) -> RdbOk {
+    match value_type {
+        encoding_type::STREAM_LISTPACKS
+        | encoding_type::STREAM_LISTPACKS_2
+        | encoding_type::STREAM_LISTPACKS_3 => skip_stream(input, value_type, limits),
+        encoding_type::MODULE_2 | op_code::MODULE_AUX => skip_module(input, limits),
+        _ => Err(other_error(format!(
+            "Unknown encoding type: {}",
+            value_type
+        ))),
+    }
+}
+
+fn skip_stream<R: Read>(input: &mut R, value_type: u8, limits: &Limits) -> RdbOk {
+    // Stream IDs, each followed by a listpack of entries
+    for _ in 0..read_count(input, limits)? {
+        skip_blob_limited(input, limits)?;
+        skip_blob_limited(input, limits)?;
+    }
+
+    // Length and last ID
+    skip_lengths(input, 3)?;
+    if value_type >= encoding_type::STREAM_LISTPACKS_2 {
+        // First ID, maximal deleted ID and the number of entries ever added
+        skip_lengths(input, 5)?;
+    }
+
+    for _ in 0..read_count(input, limits)? {
+        // Consumer group name and last delivered ID
+        skip_blob_limited(input, limits)?;
+        skip_lengths(input, 2)?;
+        if value_type >= encoding_type::STREAM_LISTPACKS_2 {
+            // Number of entries read
+            skip_lengths(input, 1)?;
+        }
+
+        // Pending entries: ID, delivery time and count
+        for _ in 0..read_count(input, limits)? {
+            helper::skip(input, 16 + 8)?;
+            read_length_u64(input)?;
+        }
+
+        for _ in 0..read_count(input, limits)? {
+            // Consumer name, seen time and active time
+            skip_blob_limited(input, limits)?;
+            helper::skip(input, 8)?;
+            if value_type >= encoding_type::STREAM_LISTPACKS_3 {
+                helper::skip(input, 8)?;
+            }
+
+            // IDs of the pending entries
+            let pending = read_count(input, limits)?;
+            helper::skip(input, pending * 16)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn skip_module<R: Read>(input: &mut R, limits: &Limits) -> RdbOk {
+    let _module_id = read_length_u64(input)?;
+
+    loop {
+        match read_length_u64(input)? {
+            module_op_code::EOF => return Ok(()),
+            module_op_code::SINT | module_op_code::UINT => {
+                read_length_u64(input)?;
+            }
+            module_op_code::FLOAT => helper::skip(input, 4)?,
+            module_op_code::DOUBLE => helper::skip(input, 8)?,
+            module_op_code::STRING => skip_blob_limited(input, limits)?,
+            op => return Err(other_error(format!("Unknown module opcode: {}", op))),
+        }
+    }
+}
+
+/// Returns the size of the field holding the length of a listpack entry,
+/// given the length of the entry.
+fn listpack_backlen_size(length: u64) -> u64 {
+    match length {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
     }
 }
+
+/// Reads a listpack entry. Numbers are returned as strings.
+fn read_listpack_entry<R: Read>(input: &mut R, limits: &Limits) -> RdbResult<Vec<u8>> {
+    let flag = input.read_u8()?;
+
+    let (number, length) = if flag & 0x80 == 0 {
+        ((flag & 0x7F) as i64, 1)
+    } else if flag & 0xC0 == 0x80 {
+        let length = (flag & 0x3F) as u64;
+        return read_listpack_string(input, length, 1, limits);
+    } else if flag & 0xE0 == 0xC0 {
+        let number = (((flag & 0x1F) as i64) << 8) | input.read_u8()? as i64;
+        let number = if number >= 1 << 12 {
+            number - (1 << 13)
+        } else {
+            number
+        };
+        (number, 2)
+    } else if flag & 0xF0 == 0xE0 {
+        let length = (((flag & 0x0F) as u64) << 8) | input.read_u8()? as u64;
+        return read_listpack_string(input, length, 2, limits);
+    } else {
+        match flag {
+            0xF0 => {
+                let length = input.read_u32::<LittleEndian>()? as u64;
+                return read_listpack_string(input, length, 5, limits);
+            }
+            0xF1 => (input.read_i16::<LittleEndian>()? as i64, 3),
+            0xF2 => (input.read_i24::<LittleEndian>()? as i64, 4),
+            0xF3 => (input.read_i32::<LittleEndian>()? as i64, 5),
+            0xF4 => (input.read_i64::<LittleEndian>()?, 9),
+            _ => return Err(other_error(format!("Unknown listpack encoding: {}", flag))),
+        }
+    };
+
+    helper::skip(input, listpack_backlen_size(length))?;
+    Ok(number.to_string().into_bytes())
+}
+
+fn read_listpack_string<R: Read>(
+    input: &mut R,
+    length: u64,
+    header_length: u64,
+    limits: &Limits,
+) -> RdbResult<Vec<u8>> {
+    limits.check_string_size(length)?;
+    let data = read_exact(input, length as usize)?;
+    helper::skip(input, listpack_backlen_size(header_length + length))?;
+
+    Ok(data)
+}
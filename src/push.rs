@@ -0,0 +1,305 @@
+//! Resumable, push-based parsing
+//!
+//! `PushParser` does no I/O on its own. Instead the caller hands it the RDB
+//! in chunks of arbitrary size via `feed`. Every entry that is completely
+//! available is parsed and passed to the formatter right away, incomplete
+//! data is kept until the next call.
+//!
+//! ```rust
+//! # use rdb::PushParser;
+//! let mut parser = PushParser::new(rdb::formatter::Nil::new(), rdb::filter::Simple::new());
+//! for chunk in b"REDIS0003\xfe\x00\x00\x03key\x05value\xff".chunks(3) {
+//!     parser.feed(chunk).unwrap();
+//! }
+//! parser.finish().unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::io::{Cursor, ErrorKind};
+
+use crate::builder::Limits;
+use crate::constants::op_code;
+use crate::filter::Filter;
+use crate::formatter::{Control, Formatter};
+use crate::parser::{
+    read_blob_header, read_length_u64, read_object_layout, skip_nested, ObjectLayout, RdbParser,
+};
+use crate::types::{AuxInfo, RdbError, RdbOk, RdbResult};
+
+/// Length of the magic string and the version number
+const HEADER_LENGTH: usize = 9;
+/// Length of the CRC64 checksum written since version 5
+const CHECKSUM_LENGTH: usize = 8;
+
+#[derive(Debug, PartialEq)]
+enum State {
+    Header,
+    Entries,
+    Checksum,
+    Done,
+}
+
+/// A part of an entry that has to be fully buffered before it can be checked
+enum Step {
+    Length,
+    Fixed(u64),
+    Blobs(u64),
+    ScoredBlobs(u64),
+    Nodes(u64),
+    /// Has to be skipped as a whole, so it is scanned from the start until it's complete.
+    /// Only used for rare types such as streams.
+    Nested(u8),
+    Object(u8),
+}
+
+/// Finds the end of the next entry without decoding it.
+///
+/// Progress is kept between calls at the granularity of steps: blob payloads are
+/// skipped without being looked at, and completed steps are never scanned again.
+/// A step that is still incomplete, such as an object header, a length or a nested
+/// value, is re-read from its start once more data is fed.
+#[derive(Default)]
+struct Scanner {
+    next_op: Option<u8>,
+    length: usize,
+    steps: Vec<Step>,
+}
+
+fn is_incomplete(err: &RdbError) -> bool {
+    match err {
+        RdbError::Io(err) => err.kind() == ErrorKind::UnexpectedEof,
+        _ => false,
+    }
+}
+
+impl Scanner {
+    /// Returns the opcode of the next entry, once it is completely buffered.
+    ///
+    /// Lengths are checked against `limits`, so corrupted data can't make the buffer grow unbounded.
+    fn scan(&mut self, data: &[u8], limits: &Limits) -> RdbResult<Option<u8>> {
+        let next_op = match self.next_op {
+            Some(next_op) => next_op,
+            None => {
+                let next_op = match data.first() {
+                    Some(&next_op) => next_op,
+                    None => return Ok(None),
+                };
+                self.next_op = Some(next_op);
+                self.length = 1;
+                self.steps = match next_op {
+                    op_code::SELECTDB => vec![Step::Length],
+                    op_code::EOF => vec![],
+                    op_code::EXPIRETIME_MS => vec![Step::Fixed(8)],
+                    op_code::EXPIRETIME => vec![Step::Fixed(4)],
+                    op_code::RESIZEDB => vec![Step::Length, Step::Length],
+                    op_code::AUX => vec![Step::Blobs(2)],
+                    op_code::IDLE => vec![Step::Length],
+                    op_code::FREQ => vec![Step::Fixed(1)],
+                    op_code::MODULE_AUX => vec![Step::Nested(next_op)],
+                    op_code::FUNCTION2 => vec![Step::Blobs(1)],
+                    // Rejected by the parser
+                    op_code::FUNCTION_PRE_GA => vec![],
+                    _ => vec![Step::Object(next_op), Step::Blobs(1)],
+                };
+                next_op
+            }
+        };
+
+        while let Some(step) = self.steps.last_mut() {
+            let mut input = Cursor::new(&data[self.length..]);
+            let result = match step {
+                Step::Blobs(0) | Step::ScoredBlobs(0) | Step::Nodes(0) => {
+                    self.steps.pop();
+                    continue;
+                }
+                Step::Length => read_length_u64(&mut input).map(|_| 0),
+                Step::Fixed(length) => Ok(*length),
+                Step::Blobs(_) => read_blob_header(&mut input, limits),
+                Step::ScoredBlobs(_) => {
+                    read_blob_header(&mut input, limits).map(|length| length + 8)
+                }
+                Step::Nodes(_) => {
+                    read_length_u64(&mut input).and_then(|_| read_blob_header(&mut input, limits))
+                }
+                Step::Nested(value_type) => skip_nested(&mut input, *value_type, limits).map(|_| 0),
+                Step::Object(enc_type) => {
+                    let layout = match read_object_layout(&mut input, *enc_type, limits) {
+                        Err(ref err) if is_incomplete(err) => return Ok(None),
+                        result => result?,
+                    };
+                    self.length += input.position() as usize;
+                    *step = match layout {
+                        ObjectLayout::Blobs(count) => Step::Blobs(count),
+                        ObjectLayout::ScoredBlobs(count) => Step::ScoredBlobs(count),
+                        ObjectLayout::Nodes(count) => Step::Nodes(count),
+                        ObjectLayout::Nested => Step::Nested(*enc_type),
+                    };
+                    continue;
+                }
+            };
+            let payload_length = match result {
+                Err(ref err) if is_incomplete(err) => return Ok(None),
+                result => result?,
+            };
+
+            let step_length = input.position() + payload_length;
+            if (data.len() - self.length) < step_length as usize {
+                return Ok(None);
+            }
+            self.length += step_length as usize;
+
+            match step {
+                Step::Blobs(count) | Step::ScoredBlobs(count) | Step::Nodes(count) => *count -= 1,
+                Step::Length | Step::Fixed(_) | Step::Nested(_) => {
+                    self.steps.pop();
+                }
+                Step::Object(_) => unreachable!(),
+            }
+        }
+
+        *self = Scanner::default();
+        Ok(Some(next_op))
+    }
+}
+
+/// Parses a RDB file from chunks of data handed to it as they arrive
+pub struct PushParser<F: Formatter, L: Filter> {
+    parser: RdbParser<Cursor<Vec<u8>>, F, L>,
+    scanner: Scanner,
+    state: State,
+}
+
+impl<F: Formatter, L: Filter> PushParser<F, L> {
+    pub fn new(formatter: F, filter: L) -> PushParser<F, L> {
+        PushParser::with_limits(formatter, filter, Limits::default())
+    }
+
+    pub(crate) fn with_limits(formatter: F, filter: L, limits: Limits) -> PushParser<F, L> {
+        PushParser {
+            parser: RdbParser::with_limits(Cursor::new(Vec::new()), formatter, filter, limits),
+            scanner: Scanner::default(),
+            state: State::Header,
+        }
+    }
+
+    pub(crate) fn map_databases(&mut self, databases: HashMap<u32, u32>) {
+        self.parser.map_databases(databases);
+    }
+
+    /// Appends `data` to the internal buffer and parses every entry that is now complete.
+    ///
+    /// Once parsing is done, further data is ignored.
+    pub fn feed(&mut self, data: &[u8]) -> RdbOk {
+        if self.state == State::Done {
+            return Ok(());
+        }
+
+        self.parser.input.get_mut().extend_from_slice(data);
+
+        while self.step()? {}
+
+        let input = &mut self.parser.input;
+        let consumed = input.position() as usize;
+        input.get_mut().drain(..consumed);
+        input.set_position(0);
+
+        Ok(())
+    }
+
+    /// Signals the end of the input.
+    ///
+    /// Fails if the RDB was not complete.
+    pub fn finish(&mut self) -> RdbOk {
+        match self.state {
+            State::Done => Ok(()),
+            // The checksum is optional for versions before 5 and not verified anyway.
+            State::Checksum => {
+                self.state = State::Done;
+                let checksum = self.remaining().to_vec();
+                self.parser.end(&checksum)
+            }
+            _ => Err(RdbError::Other(format!(
+                "Unexpected end of input, {} bytes left unparsed",
+                self.remaining().len()
+            ))),
+        }
+    }
+
+    /// Returns true once the EOF opcode and the checksum were parsed
+    /// or the formatter stopped parsing.
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// The aux fields read so far
+    pub fn aux_info(&self) -> &AuxInfo {
+        self.parser.aux_info()
+    }
+
+    /// Returns the formatter, e.g. to get back its writer once parsing is done.
+    pub fn into_formatter(self) -> F {
+        self.parser.into_formatter()
+    }
+
+    fn remaining(&self) -> &[u8] {
+        let input = &self.parser.input;
+        &input.get_ref()[input.position() as usize..]
+    }
+
+    /// Parses the next unit if it is completely buffered.
+    ///
+    /// Returns false if more input is required.
+    fn step(&mut self) -> RdbResult<bool> {
+        match self.state {
+            State::Header => {
+                if self.remaining().len() < HEADER_LENGTH {
+                    return Ok(false);
+                }
+
+                self.parser.read_header()?;
+                self.state = State::Entries;
+            }
+            State::Entries => {
+                // Only parse complete entries, so the formatter never sees half an entry.
+                let next_op = {
+                    let input = &self.parser.input;
+                    let data = &input.get_ref()[input.position() as usize..];
+                    match self.scanner.scan(data, &self.parser.limits)? {
+                        Some(next_op) => next_op,
+                        None => return Ok(false),
+                    }
+                };
+
+                self.parser
+                    .input
+                    .set_position(self.parser.input.position() + 1);
+                if next_op == op_code::EOF {
+                    if self.parser.version >= 5 {
+                        self.state = State::Checksum;
+                    } else {
+                        self.state = State::Done;
+                        self.parser.end(&[])?;
+                    }
+                } else if self.parser.parse_entry(next_op)? == Control::Stop {
+                    self.state = State::Done;
+                }
+            }
+            State::Checksum => {
+                if self.remaining().len() < CHECKSUM_LENGTH {
+                    return Ok(false);
+                }
+
+                let checksum = self.remaining()[..CHECKSUM_LENGTH].to_vec();
+                let position = self.parser.input.position();
+                self.parser
+                    .input
+                    .set_position(position + CHECKSUM_LENGTH as u64);
+                self.state = State::Done;
+                self.parser.end(&checksum)?;
+            }
+            State::Done => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
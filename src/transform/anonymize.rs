@@ -0,0 +1,192 @@
+use regex::bytes::Regex;
+use siphasher::sip::SipHasher24;
+use std::borrow::Cow;
+use std::hash::Hasher;
+
+use super::{Part, Transform};
+use crate::types::RdbResult;
+
+/// Replaces values, and parts of keys matched by rules, with data of the same shape
+///
+/// Every byte is replaced by one of the same class: digits by digits, lowercase by
+/// lowercase and uppercase by uppercase letters, and bytes outside of ASCII by other
+/// bytes outside of ASCII. Other characters, such as separators, are kept. Lengths and
+/// integers therefore stay the same, and so do the encodings the values are stored with.
+///
+/// The replacement is derived from a secret with a keyed hash. The same input always
+/// gets the same replacement, and different inputs get different ones, so keys stay
+/// unique and sets keep their cardinality. Keys and set members are always replaced
+/// this way. Other values can be replaced with random data instead, which does not
+/// reveal which values are equal.
+///
+/// Streams and module values cannot be anonymized and are left out.
+pub struct Anonymize {
+    hasher: SipHasher24,
+    key_rules: Vec<Regex>,
+    hash_fields: bool,
+    random_values: bool,
+    /// Number of values replaced with random data so far
+    counter: u64,
+}
+
+impl Anonymize {
+    pub fn new(secret: &[u8]) -> Anonymize {
+        let mut key = [0; 16];
+        for (index, half) in key.chunks_mut(8).enumerate() {
+            let mut hasher = SipHasher24::new_with_keys(index as u64, 0);
+            hasher.write(secret);
+            half.copy_from_slice(&hasher.finish().to_le_bytes());
+        }
+
+        Anonymize {
+            hasher: SipHasher24::new_with_key(&key),
+            key_rules: Vec::new(),
+            hash_fields: false,
+            random_values: false,
+            counter: 0,
+        }
+    }
+
+    /// Replaces the parts of keys matched by `pattern`: every capture group,
+    /// or the whole match for patterns without groups.
+    pub fn key_segments(&mut self, pattern: Regex) {
+        self.key_rules.push(pattern);
+    }
+
+    /// Replaces the fields of hashes as well, not only their values.
+    pub fn set_hash_fields(&mut self, hash_fields: bool) {
+        self.hash_fields = hash_fields;
+    }
+
+    /// Replaces strings, list elements and hash values with random data derived from
+    /// the secret, instead of the same replacement for equal values.
+    pub fn set_random_values(&mut self, random_values: bool) {
+        self.random_values = random_values;
+    }
+
+    /// Returns the replacement of `data`, with `salt` making it differ between calls.
+    fn replace(&self, data: &[u8], salt: u64) -> Vec<u8> {
+        // Replacing can turn integers close to the limits of 64 bits into numbers that
+        // are too large, and such numbers into integers. Replacing again until it is an
+        // integer exactly when the input is one is still reversible and keeps values
+        // apart: the original value is reached again otherwise.
+        let integer = is_integer(data);
+        let mut replaced = self.shuffle(data, salt);
+        while is_integer(&replaced) != integer {
+            replaced = self.shuffle(&replaced, salt);
+        }
+        replaced
+    }
+
+    fn shuffle(&self, data: &[u8], salt: u64) -> Vec<u8> {
+        // The range of replacements of every byte. Replacing keeps them all the same.
+        let mut previous_digit = false;
+        let classes: Vec<(u8, u8)> = data
+            .iter()
+            .map(|&byte| {
+                let class = match byte {
+                    // A leading zero is kept, so integers stay integers
+                    b'0' if !previous_digit => (b'0', 1),
+                    b'0'..=b'9' if previous_digit => (b'0', 10),
+                    b'1'..=b'9' => (b'1', 9),
+                    b'a'..=b'z' => (b'a', 26),
+                    b'A'..=b'Z' => (b'A', 26),
+                    0x80..=0xff => (0x80, 128),
+                    _ => (byte, 1),
+                };
+                previous_digit = byte.is_ascii_digit();
+                class
+            })
+            .collect();
+
+        // Every byte is shifted depending on the bytes before it, once from the front and
+        // once from the back, so each byte of the result depends on the whole input.
+        // Shifting stays reversible with the secret, so different inputs cannot collide.
+        let mut data = data.to_vec();
+        for backwards in [false, true] {
+            let mut hasher = self.hasher;
+            hasher.write_u64(salt);
+            hasher.write_usize(data.len());
+            hasher.write_u8(backwards as u8);
+
+            let mut shift = |index: usize| {
+                let (first, size) = classes[index];
+                let offset = hasher.clone().finish() % size as u64;
+                hasher.write_u8(data[index]);
+                data[index] = first + ((data[index] - first) as u64 + offset) as u8 % size;
+            };
+            if backwards {
+                (0..classes.len()).rev().for_each(&mut shift);
+            } else {
+                (0..classes.len()).for_each(&mut shift);
+            }
+        }
+
+        data
+    }
+
+    fn replace_key(&self, key: &[u8]) -> Vec<u8> {
+        let mut key = key.to_vec();
+
+        for pattern in &self.key_rules {
+            let ranges: Vec<(usize, usize)> = match pattern.captures(&key) {
+                Some(captures) if captures.len() > 1 => captures
+                    .iter()
+                    .skip(1)
+                    .flatten()
+                    .map(|group| (group.start(), group.end()))
+                    .collect(),
+                Some(captures) => {
+                    let found = captures.get(0).unwrap();
+                    vec![(found.start(), found.end())]
+                }
+                None => continue,
+            };
+
+            for (start, end) in ranges {
+                let replaced = self.replace(&key[start..end], 0);
+                key[start..end].copy_from_slice(&replaced);
+            }
+        }
+
+        key
+    }
+}
+
+/// Whether `data` is stored as an integer by Redis
+fn is_integer(data: &[u8]) -> bool {
+    std::str::from_utf8(data)
+        .ok()
+        .and_then(|data| {
+            data.parse::<i64>()
+                .ok()
+                .map(|number| number.to_string() == data)
+        })
+        .unwrap_or(false)
+}
+
+impl Transform for Anonymize {
+    fn key(
+        &mut self,
+        key: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Option<(Vec<u8>, Option<u64>)>> {
+        Ok(Some((self.replace_key(key), expiry)))
+    }
+
+    fn value<'a>(&mut self, part: Part, value: &'a [u8]) -> RdbResult<Cow<'a, [u8]>> {
+        Ok(match part {
+            Part::Field if !self.hash_fields => Cow::Borrowed(value),
+            Part::Field | Part::Member => Cow::Owned(self.replace(value, 0)),
+            Part::Value if self.random_values => {
+                self.counter += 1;
+                Cow::Owned(self.replace(value, self.counter))
+            }
+            Part::Value => Cow::Owned(self.replace(value, 0)),
+        })
+    }
+
+    fn keeps_raw_values(&self) -> bool {
+        false
+    }
+}
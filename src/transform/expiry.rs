@@ -0,0 +1,121 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::Transform;
+use crate::types::{AuxInfo, RdbError, RdbResult};
+
+/// The point in time expiries are compared with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reference {
+    /// The time the file is read
+    Now,
+    /// The time the file was written, from the `ctime` aux field
+    Ctime,
+    /// A Unix time in milliseconds
+    At(u64),
+}
+
+/// How expiries are passed on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Adjust {
+    /// Unchanged
+    Keep,
+    /// Moved by the time passed since the reference time, so that keys have the same
+    /// TTL left as they had at the reference time. With `Reference::Ctime`, this undoes
+    /// the age of the snapshot.
+    Shift,
+    /// As the TTL in milliseconds at the reference time, instead of a Unix time.
+    /// Only meaningful for formatters that print the expiry, as the RDB and RESP
+    /// formatters expect Unix times.
+    Ttl,
+}
+
+/// Rewrites expiries relative to a reference time, optionally leaving out expired keys
+pub struct Expiry {
+    reference: Reference,
+    drop_expired: bool,
+    adjust: Adjust,
+    now: u64,
+    /// The reference time in milliseconds, once known
+    time: Option<u64>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl Expiry {
+    pub fn new(reference: Reference) -> Expiry {
+        let now = now();
+        let time = match reference {
+            Reference::Now => Some(now),
+            Reference::Ctime => None,
+            Reference::At(time) => Some(time),
+        };
+
+        Expiry {
+            reference,
+            drop_expired: false,
+            adjust: Adjust::Keep,
+            now,
+            time,
+        }
+    }
+
+    /// Leaves out keys that already expired at the reference time.
+    pub fn set_drop_expired(&mut self, drop_expired: bool) {
+        self.drop_expired = drop_expired;
+    }
+
+    pub fn set_adjust(&mut self, adjust: Adjust) {
+        self.adjust = adjust;
+    }
+}
+
+impl Transform for Expiry {
+    fn aux_info(&mut self, info: &AuxInfo) -> RdbResult<()> {
+        if self.reference == Reference::Ctime {
+            match info.ctime {
+                Some(ctime) => self.time = Some(ctime * 1000),
+                None => {
+                    return Err(RdbError::Other(
+                        "The file has no ctime aux field to use as the reference time".into(),
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn key(
+        &mut self,
+        key: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Option<(Vec<u8>, Option<u64>)>> {
+        let (expiry, time) = match (expiry, self.time) {
+            (Some(expiry), Some(time)) => (expiry, time),
+            // Without an expiry there is nothing to change
+            (None, _) => return Ok(Some((key.to_vec(), None))),
+            (Some(_), None) => {
+                return Err(RdbError::Other(
+                    "The reference time is not known before the first key".into(),
+                ))
+            }
+        };
+
+        if self.drop_expired && expiry <= time {
+            return Ok(None);
+        }
+
+        let expiry = match self.adjust {
+            Adjust::Keep => expiry,
+            Adjust::Shift => (expiry as i128 + self.now as i128 - time as i128).max(0) as u64,
+            Adjust::Ttl => expiry.saturating_sub(time),
+        };
+
+        Ok(Some((key.to_vec(), Some(expiry))))
+    }
+}
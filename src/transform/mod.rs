@@ -0,0 +1,362 @@
+//! Changing keys on their way from the parser to a formatter
+//!
+//! A `Transform` decides for every key what name and expiry it gets, or drops it,
+//! and can replace its values.
+//! Two transforms are combined by passing them as a tuple.
+//! `Transformed` applies a transform in front of any formatter, so the same rules work
+//! for JSON, RESP or RDB output:
+//!
+//! ```rust,no_run
+//! # use std::io::BufReader;
+//! # use std::fs::File;
+//! use rdb::transform::{Rename, Transformed};
+//!
+//! let mut rename = Rename::new();
+//! rename.strip_prefix(b"prod:");
+//! rename.add_prefix(b"stg:");
+//!
+//! let file = BufReader::new(File::open("dump.rdb").unwrap());
+//! let formatter = Transformed::new(rdb::formatter::JSON::new(), rename);
+//! rdb::parse(file, formatter, rdb::filter::Simple::new()).unwrap();
+//! ```
+
+use std::borrow::Cow;
+
+use crate::formatter::{Control, Formatter};
+use crate::types::{AuxInfo, EncodingType, RdbResult};
+
+pub use self::anonymize::Anonymize;
+pub use self::expiry::Expiry;
+pub use self::rename::Rename;
+
+pub mod anonymize;
+pub mod expiry;
+pub mod rename;
+
+/// What a value passed to `Transform::value` is part of
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Part {
+    /// A string, a list element or the value of a hash field
+    Value,
+    /// The field of a hash
+    Field,
+    /// The member of a set or sorted set
+    Member,
+}
+
+pub trait Transform {
+    /// Called with the aux fields of the file, before the first key.
+    fn aux_info(&mut self, _info: &AuxInfo) -> RdbResult<()> {
+        Ok(())
+    }
+
+    /// Called once per key, before its value.
+    /// Returns the key and expiry to pass on, or `None` to leave out the key and its value.
+    fn key(&mut self, key: &[u8], expiry: Option<u64>)
+        -> RdbResult<Option<(Vec<u8>, Option<u64>)>>;
+
+    /// Called for every value of the keys passed on. Returns the value to pass on instead.
+    fn value<'a>(&mut self, _part: Part, value: &'a [u8]) -> RdbResult<Cow<'a, [u8]>> {
+        Ok(Cow::Borrowed(value))
+    }
+
+    /// Whether keys with values that are not decoded, namely streams and module values,
+    /// are passed on. Their values cannot be transformed.
+    fn keeps_raw_values(&self) -> bool {
+        true
+    }
+}
+
+/// `None` passes on everything unchanged
+impl<T: Transform> Transform for Option<T> {
+    fn aux_info(&mut self, info: &AuxInfo) -> RdbResult<()> {
+        match self {
+            Some(transform) => transform.aux_info(info),
+            None => Ok(()),
+        }
+    }
+
+    fn key(
+        &mut self,
+        key: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Option<(Vec<u8>, Option<u64>)>> {
+        match self {
+            Some(transform) => transform.key(key, expiry),
+            None => Ok(Some((key.to_vec(), expiry))),
+        }
+    }
+
+    fn value<'a>(&mut self, part: Part, value: &'a [u8]) -> RdbResult<Cow<'a, [u8]>> {
+        match self {
+            Some(transform) => transform.value(part, value),
+            None => Ok(Cow::Borrowed(value)),
+        }
+    }
+
+    fn keeps_raw_values(&self) -> bool {
+        self.as_ref()
+            .is_none_or(|transform| transform.keeps_raw_values())
+    }
+}
+
+/// Applies the first transform, then the second one to what is left
+impl<A: Transform, B: Transform> Transform for (A, B) {
+    fn aux_info(&mut self, info: &AuxInfo) -> RdbResult<()> {
+        self.0.aux_info(info)?;
+        self.1.aux_info(info)
+    }
+
+    fn key(
+        &mut self,
+        key: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Option<(Vec<u8>, Option<u64>)>> {
+        match self.0.key(key, expiry)? {
+            Some((key, expiry)) => self.1.key(&key, expiry),
+            None => Ok(None),
+        }
+    }
+
+    fn value<'a>(&mut self, part: Part, value: &'a [u8]) -> RdbResult<Cow<'a, [u8]>> {
+        match self.0.value(part, value)? {
+            Cow::Borrowed(value) => self.1.value(part, value),
+            Cow::Owned(value) => Ok(Cow::Owned(self.1.value(part, &value)?.into_owned())),
+        }
+    }
+
+    fn keeps_raw_values(&self) -> bool {
+        self.0.keeps_raw_values() && self.1.keeps_raw_values()
+    }
+}
+
+/// A formatter applying a `Transform` before passing everything on to another formatter
+pub struct Transformed<F: Formatter, T: Transform> {
+    inner: F,
+    transform: T,
+    /// The new name of the key being read, `None` if it is left out
+    current: Option<Vec<u8>>,
+    /// LRU and LFU information of the next key
+    eviction: Option<(Option<u64>, Option<u8>)>,
+}
+
+impl<F: Formatter, T: Transform> Transformed<F, T> {
+    pub fn new(inner: F, transform: T) -> Transformed<F, T> {
+        Transformed {
+            inner,
+            transform,
+            current: None,
+            eviction: None,
+        }
+    }
+
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+
+    /// Transforms the key about to be read. Returns `None` if it is left out.
+    fn begin(&mut self, key: &[u8], expiry: Option<u64>) -> RdbResult<Option<Option<u64>>> {
+        let eviction = self.eviction.take();
+        self.current = None;
+
+        let (key, expiry) = match self.transform.key(key, expiry)? {
+            Some(transformed) => transformed,
+            None => return Ok(None),
+        };
+        if let Some((idle, freq)) = eviction {
+            self.inner.eviction_info(&key, idle, freq)?;
+        }
+        self.current = Some(key);
+
+        Ok(Some(expiry))
+    }
+}
+
+impl<F: Formatter, T: Transform> Formatter for Transformed<F, T> {
+    fn start_rdb(&mut self) -> RdbResult<()> {
+        self.inner.start_rdb()
+    }
+    fn end_rdb(&mut self) -> RdbResult<()> {
+        self.inner.end_rdb()
+    }
+    fn checksum(&mut self, checksum: &[u8]) -> RdbResult<()> {
+        self.inner.checksum(checksum)
+    }
+
+    fn start_database(&mut self, db_index: u32) -> RdbResult<()> {
+        self.inner.start_database(db_index)
+    }
+    fn end_database(&mut self, db_index: u32) -> RdbResult<()> {
+        self.inner.end_database(db_index)
+    }
+
+    fn resizedb(&mut self, db_size: u32, expires_size: u32) -> RdbResult<()> {
+        self.inner.resizedb(db_size, expires_size)
+    }
+    fn aux_field(&mut self, key: &[u8], value: &[u8]) -> RdbResult<()> {
+        self.inner.aux_field(key, value)
+    }
+    fn aux_info(&mut self, info: &AuxInfo) -> RdbResult<()> {
+        self.transform.aux_info(info)?;
+        self.inner.aux_info(info)
+    }
+
+    fn function(&mut self, code: &[u8]) -> RdbResult<()> {
+        self.inner.function(code)
+    }
+    fn module_aux(&mut self, raw: &[u8]) -> RdbResult<()> {
+        self.inner.module_aux(raw)
+    }
+
+    fn eviction_info(&mut self, _key: &[u8], idle: Option<u64>, freq: Option<u8>) -> RdbResult<()> {
+        // Passed on once the new name of the key is known
+        self.eviction = Some((idle, freq));
+        Ok(())
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<Control> {
+        match (self.begin(key, expiry)?, &self.current) {
+            (Some(expiry), Some(key)) => {
+                let value = self.transform.value(Part::Value, value)?;
+                self.inner.set(key, &value, expiry)
+            }
+            _ => Ok(Control::Continue),
+        }
+    }
+
+    fn raw_object(
+        &mut self,
+        key: &[u8],
+        value_type: u8,
+        raw: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        if !self.transform.keeps_raw_values() {
+            self.eviction = None;
+            return Ok(Control::Continue);
+        }
+        match (self.begin(key, expiry)?, &self.current) {
+            (Some(expiry), Some(key)) => self.inner.raw_object(key, value_type, raw, expiry),
+            _ => Ok(Control::Continue),
+        }
+    }
+
+    fn start_hash(
+        &mut self,
+        key: &[u8],
+        length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        match (self.begin(key, expiry)?, &self.current) {
+            (Some(expiry), Some(key)) => self.inner.start_hash(key, length, expiry, info),
+            _ => Ok(Control::SkipKey),
+        }
+    }
+    fn end_hash(&mut self, _key: &[u8]) -> RdbResult<()> {
+        match self.current.take() {
+            Some(key) => self.inner.end_hash(&key),
+            None => Ok(()),
+        }
+    }
+    fn hash_element(&mut self, _key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
+        match self.current {
+            Some(ref key) => {
+                let field = self.transform.value(Part::Field, field)?;
+                let value = self.transform.value(Part::Value, value)?;
+                self.inner.hash_element(key, &field, &value)
+            }
+            None => Ok(Control::SkipKey),
+        }
+    }
+
+    fn start_set(
+        &mut self,
+        key: &[u8],
+        cardinality: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        match (self.begin(key, expiry)?, &self.current) {
+            (Some(expiry), Some(key)) => self.inner.start_set(key, cardinality, expiry, info),
+            _ => Ok(Control::SkipKey),
+        }
+    }
+    fn end_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        match self.current.take() {
+            Some(key) => self.inner.end_set(&key),
+            None => Ok(()),
+        }
+    }
+    fn set_element(&mut self, _key: &[u8], member: &[u8]) -> RdbResult<Control> {
+        match self.current {
+            Some(ref key) => {
+                let member = self.transform.value(Part::Member, member)?;
+                self.inner.set_element(key, &member)
+            }
+            None => Ok(Control::SkipKey),
+        }
+    }
+
+    fn start_list(
+        &mut self,
+        key: &[u8],
+        length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        match (self.begin(key, expiry)?, &self.current) {
+            (Some(expiry), Some(key)) => self.inner.start_list(key, length, expiry, info),
+            _ => Ok(Control::SkipKey),
+        }
+    }
+    fn end_list(&mut self, _key: &[u8]) -> RdbResult<()> {
+        match self.current.take() {
+            Some(key) => self.inner.end_list(&key),
+            None => Ok(()),
+        }
+    }
+    fn list_node(&mut self, _key: &[u8], size: u64) -> RdbResult<()> {
+        match self.current {
+            Some(ref key) => self.inner.list_node(key, size),
+            None => Ok(()),
+        }
+    }
+    fn list_element(&mut self, _key: &[u8], value: &[u8]) -> RdbResult<Control> {
+        match self.current {
+            Some(ref key) => {
+                let value = self.transform.value(Part::Value, value)?;
+                self.inner.list_element(key, &value)
+            }
+            None => Ok(Control::SkipKey),
+        }
+    }
+
+    fn start_sorted_set(
+        &mut self,
+        key: &[u8],
+        length: u32,
+        expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        match (self.begin(key, expiry)?, &self.current) {
+            (Some(expiry), Some(key)) => self.inner.start_sorted_set(key, length, expiry, info),
+            _ => Ok(Control::SkipKey),
+        }
+    }
+    fn end_sorted_set(&mut self, _key: &[u8]) -> RdbResult<()> {
+        match self.current.take() {
+            Some(key) => self.inner.end_sorted_set(&key),
+            None => Ok(()),
+        }
+    }
+    fn sorted_set_element(&mut self, _key: &[u8], score: f64, member: &[u8]) -> RdbResult<Control> {
+        match self.current {
+            Some(ref key) => {
+                let member = self.transform.value(Part::Member, member)?;
+                self.inner.sorted_set_element(key, score, &member)
+            }
+            None => Ok(Control::SkipKey),
+        }
+    }
+}
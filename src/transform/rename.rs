@@ -0,0 +1,121 @@
+use regex::bytes::Regex;
+use std::collections::HashMap;
+
+use super::Transform;
+use crate::cluster::hash_tag;
+use crate::types::{RdbError, RdbResult};
+
+enum Rule {
+    StripPrefix(Vec<u8>),
+    AddPrefix(Vec<u8>),
+    Replace(Regex, Vec<u8>),
+    HashTag(Regex),
+}
+
+/// Renames keys by applying rules in the order they were added
+///
+/// Keys sharing a hash tag are stored in the same Redis Cluster slot. Renaming must not
+/// split them up, so all keys with the same hash tag have to end up with the same new one.
+/// Otherwise renaming fails.
+#[derive(Default)]
+pub struct Rename {
+    rules: Vec<Rule>,
+    /// The new hash tag of every hash tag seen so far
+    tags: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Rename {
+    pub fn new() -> Rename {
+        Rename::default()
+    }
+
+    /// Removes `prefix` from keys starting with it.
+    pub fn strip_prefix(&mut self, prefix: &[u8]) {
+        self.rules.push(Rule::StripPrefix(prefix.to_vec()));
+    }
+
+    pub fn add_prefix(&mut self, prefix: &[u8]) {
+        self.rules.push(Rule::AddPrefix(prefix.to_vec()));
+    }
+
+    /// Replaces the first match of `pattern`. `replacement` can refer to capture groups,
+    /// e.g. `$1` or `${name}`.
+    pub fn replace(&mut self, pattern: Regex, replacement: &[u8]) {
+        self.rules
+            .push(Rule::Replace(pattern, replacement.to_vec()));
+    }
+
+    /// Puts the first match of `pattern` into braces, making it the hash tag of the key.
+    /// Keys that already have a hash tag are not changed.
+    pub fn hash_tag(&mut self, pattern: Regex) {
+        self.rules.push(Rule::HashTag(pattern));
+    }
+
+    /// Returns the new name of `key`.
+    pub fn apply(&self, key: &[u8]) -> Vec<u8> {
+        let mut key = key.to_vec();
+
+        for rule in &self.rules {
+            key = match rule {
+                Rule::StripPrefix(prefix) => match key.strip_prefix(&prefix[..]) {
+                    Some(rest) => rest.to_vec(),
+                    None => key,
+                },
+                Rule::AddPrefix(prefix) => [&prefix[..], &key[..]].concat(),
+                Rule::Replace(pattern, replacement) => {
+                    pattern.replace(&key, &replacement[..]).into_owned()
+                }
+                Rule::HashTag(pattern) => match pattern.find(&key) {
+                    Some(found) if !has_hash_tag(&key) => [
+                        &key[..found.start()],
+                        b"{",
+                        found.as_bytes(),
+                        b"}",
+                        &key[found.end()..],
+                    ]
+                    .concat(),
+                    _ => key,
+                },
+            };
+        }
+
+        key
+    }
+}
+
+fn has_hash_tag(key: &[u8]) -> bool {
+    hash_tag(key).len() < key.len()
+}
+
+impl Transform for Rename {
+    fn key(
+        &mut self,
+        key: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Option<(Vec<u8>, Option<u64>)>> {
+        if self.rules.is_empty() {
+            return Ok(Some((key.to_vec(), expiry)));
+        }
+
+        let renamed = self.apply(key);
+
+        if has_hash_tag(key) {
+            let tag = hash_tag(key);
+            let new_tag = hash_tag(&renamed);
+            let expected = self
+                .tags
+                .entry(tag.to_vec())
+                .or_insert_with(|| new_tag.to_vec());
+
+            if &expected[..] != new_tag {
+                return Err(RdbError::Other(format!(
+                    "Renaming {} would move it away from the other keys with the hash tag {}",
+                    String::from_utf8_lossy(key),
+                    String::from_utf8_lossy(tag)
+                )));
+            }
+        }
+
+        Ok(Some((renamed, expiry)))
+    }
+}
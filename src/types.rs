@@ -1,4 +1,7 @@
+use std::fmt;
 use std::io::Error as IoError;
+use std::str::{self, FromStr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
 use crate::constants::encoding_type;
@@ -11,41 +14,200 @@ pub enum ZiplistEntry {
 
 pub type RdbResult<T> = Result<T, RdbError>;
 
+/// A resource limit that can be configured with `RdbParserBuilder`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    StringSize,
+    CollectionLength,
+    DecompressedSize,
+    Keys,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Limit::StringSize => "string size",
+            Limit::CollectionLength => "collection length",
+            Limit::DecompressedSize => "decompressed size",
+            Limit::Keys => "number of keys",
+        };
+        f.write_str(name)
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum RdbError {
     #[error("I/O Error: {0}")]
     Io(#[from] IoError),
 
+    #[error("Version {version} RDB files are not supported. Supported versions are {min}-{max}")]
+    UnsupportedVersion { version: u32, min: u32, max: u32 },
+
+    #[error("Maximum {limit} of {max} exceeded: {value}")]
+    LimitExceeded { limit: Limit, value: u64, max: u64 },
+
+    #[error("{feature} can't be stored in version {version} RDB files, version {required} or later is required")]
+    UnsupportedByVersion {
+        feature: &'static str,
+        version: u32,
+        required: u32,
+    },
+
+    #[error("Key {} exists more than once in database {database}", String::from_utf8_lossy(.key))]
+    DuplicateKey { database: u32, key: Vec<u8> },
+
     #[error("{0}")]
     Other(String),
 }
 
 pub type RdbOk = RdbResult<()>;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Type {
     String,
     List,
     Set,
     SortedSet,
     Hash,
+    Stream,
+    Module,
 }
 
 impl Type {
+    /// The type of an object with the encoding `enc_type`.
+    ///
+    /// Panics on unknown encodings, the parser rejects them before they are passed on.
     pub fn from_encoding(enc_type: u8) -> Type {
-        match enc_type {
+        match Type::try_from_encoding(enc_type) {
+            Ok(typ) => typ,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    pub fn try_from_encoding(enc_type: u8) -> RdbResult<Type> {
+        let typ = match enc_type {
             encoding_type::STRING => Type::String,
-            encoding_type::HASH | encoding_type::HASH_ZIPMAP | encoding_type::HASH_ZIPLIST => {
-                Type::Hash
+            encoding_type::HASH
+            | encoding_type::HASH_ZIPMAP
+            | encoding_type::HASH_ZIPLIST
+            | encoding_type::HASH_LISTPACK => Type::Hash,
+            encoding_type::LIST
+            | encoding_type::LIST_ZIPLIST
+            | encoding_type::LIST_QUICKLIST
+            | encoding_type::LIST_QUICKLIST_2 => Type::List,
+            encoding_type::SET | encoding_type::SET_INTSET | encoding_type::SET_LISTPACK => {
+                Type::Set
             }
-            encoding_type::LIST | encoding_type::LIST_ZIPLIST => Type::List,
-            encoding_type::SET | encoding_type::SET_INTSET => Type::Set,
-            encoding_type::ZSET | encoding_type::ZSET_ZIPLIST => Type::SortedSet,
-            _ => panic!("Unknown encoding type: {}", enc_type),
+            encoding_type::ZSET
+            | encoding_type::ZSET_2
+            | encoding_type::ZSET_ZIPLIST
+            | encoding_type::ZSET_LISTPACK => Type::SortedSet,
+            encoding_type::STREAM_LISTPACKS
+            | encoding_type::STREAM_LISTPACKS_2
+            | encoding_type::STREAM_LISTPACKS_3 => Type::Stream,
+            encoding_type::MODULE | encoding_type::MODULE_2 => Type::Module,
+            _ => {
+                return Err(RdbError::Other(format!(
+                    "Unknown encoding type: {}",
+                    enc_type
+                )))
+            }
+        };
+
+        Ok(typ)
+    }
+}
+
+/// Well-known aux fields, decoded as they are parsed
+///
+/// Fields that are unknown or can't be decoded are kept in `other`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuxInfo {
+    /// `redis-ver`: Version of the Redis server that wrote the file
+    pub redis_ver: Option<String>,
+    /// `redis-bits`: Architecture of the Redis server, 32 or 64 bit
+    pub redis_bits: Option<u32>,
+    /// `ctime`: Creation time of the file, in seconds since the epoch
+    pub ctime: Option<u64>,
+    /// `used-mem`: Memory used by the Redis server when the file was written
+    pub used_mem: Option<u64>,
+    /// `repl-id`: Replication ID of the server
+    pub repl_id: Option<String>,
+    /// `repl-offset`: Replication offset of the server
+    pub repl_offset: Option<i64>,
+    /// `repl-stream-db`: Database currently selected in the replication stream
+    pub repl_stream_db: Option<i64>,
+    /// `aof-base` (or `aof-preamble` before Redis 7): Whether the file is the base of an AOF
+    pub aof_base: Option<bool>,
+    /// `lua`: Scripts stored along with the data
+    pub lua_scripts: Vec<Vec<u8>>,
+    pub other: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+fn parse_value<T: FromStr>(value: &[u8]) -> Option<T> {
+    str::from_utf8(value).ok()?.parse().ok()
+}
+
+fn parse_string(value: &[u8]) -> Option<String> {
+    String::from_utf8(value.to_vec()).ok()
+}
+
+impl AuxInfo {
+    /// Takes note of the aux field `key`
+    pub fn update(&mut self, key: &[u8], value: &[u8]) {
+        let known = match key {
+            b"redis-ver" => parse_string(value).map(|v| self.redis_ver = Some(v)),
+            b"redis-bits" => parse_value(value).map(|v| self.redis_bits = Some(v)),
+            b"ctime" => parse_value(value).map(|v| self.ctime = Some(v)),
+            b"used-mem" => parse_value(value).map(|v| self.used_mem = Some(v)),
+            b"repl-id" => parse_string(value).map(|v| self.repl_id = Some(v)),
+            b"repl-offset" => parse_value(value).map(|v| self.repl_offset = Some(v)),
+            b"repl-stream-db" => parse_value(value).map(|v| self.repl_stream_db = Some(v)),
+            b"aof-base" | b"aof-preamble" => {
+                parse_value::<u8>(value).map(|v| self.aof_base = Some(v != 0))
+            }
+            b"lua" => {
+                self.lua_scripts.push(value.to_vec());
+                Some(())
+            }
+            _ => None,
+        };
+
+        if known.is_none() {
+            self.other.push((key.to_vec(), value.to_vec()));
+        }
+    }
+
+    /// Creation time of the file, if it was recorded
+    pub fn created_at(&self) -> Option<SystemTime> {
+        self.ctime
+            .map(|ctime| UNIX_EPOCH + Duration::from_secs(ctime))
+    }
+}
+
+/// The complete value of a key
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(Vec<u8>),
+    List(Vec<Vec<u8>>),
+    Set(Vec<Vec<u8>>),
+    SortedSet(Vec<(f64, Vec<u8>)>),
+    Hash(Vec<(Vec<u8>, Vec<u8>)>),
+}
+
+impl Value {
+    pub fn typ(&self) -> Type {
+        match self {
+            Value::String(_) => Type::String,
+            Value::List(_) => Type::List,
+            Value::Set(_) => Type::Set,
+            Value::SortedSet(_) => Type::SortedSet,
+            Value::Hash(_) => Type::Hash,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EncodingType {
     String,
     LinkedList,
@@ -54,5 +216,6 @@ pub enum EncodingType {
     Intset(u64),
     Ziplist(u64),
     Zipmap(u64),
+    Listpack(u64),
     Quicklist,
 }
@@ -0,0 +1,618 @@
+//! Writing RDB files
+//!
+//! `RdbWriter` emits the opcodes and values of a RDB file one by one
+//! and appends the CRC64 checksum at the end.
+//! Features a version does not know about, such as aux fields before version 7,
+//! are left out.
+//!
+//! Like Redis, small values are stored in compact encodings such as intsets and ziplists,
+//! integers are stored as numbers and long strings are compressed. `Encodings` controls
+//! when this happens.
+//!
+//! ```rust
+//! # use rdb::types::Value;
+//! # use rdb::writer::RdbWriter;
+//! let mut writer = RdbWriter::new(Vec::new(), 8).unwrap();
+//! writer.write_header().unwrap();
+//! writer.write_select_db(0).unwrap();
+//! writer.write_object(b"key", &Value::String(b"value".to_vec()), None).unwrap();
+//! writer.write_eof().unwrap();
+//! let rdb = writer.into_inner();
+//! # assert!(rdb.starts_with(b"REDIS0008"));
+//! ```
+
+use std::io::Write;
+
+use crate::compact::{self, Listpack, Packed, Ziplist};
+use crate::constants::{constant, encoding, encoding_type, op_code, quicklist_container, version};
+use crate::crc64::crc64;
+use crate::types::{RdbError, RdbOk, RdbResult, Value};
+
+/// Strings up to this length are checked for integers
+const MAX_INTEGER_LENGTH: usize = 11;
+/// Strings longer than this are compressed, as in Redis
+const MIN_COMPRESS_LENGTH: usize = 20;
+
+/// When to use the compact encodings
+///
+/// The fields correspond to the settings of the same name in redis.conf,
+/// the defaults are the ones of Redis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Encodings {
+    pub hash_max_listpack_entries: usize,
+    pub hash_max_listpack_value: usize,
+    pub zset_max_listpack_entries: usize,
+    pub zset_max_listpack_value: usize,
+    pub set_max_intset_entries: usize,
+    /// Listpacks holding sets are used since version 11
+    pub set_max_listpack_entries: usize,
+    pub set_max_listpack_value: usize,
+    /// Size of a ziplist holding list elements. Positive values are the number of entries,
+    /// -1 to -5 stand for 4, 8, 16, 32 and 64 kb.
+    /// Lists that fit into one ziplist are stored as such before version 7,
+    /// later lists are split into ziplists of this size.
+    pub list_max_listpack_size: i64,
+    /// LZF-compress strings longer than 20 bytes
+    pub rdb_compression: bool,
+}
+
+impl Default for Encodings {
+    fn default() -> Self {
+        Encodings {
+            hash_max_listpack_entries: 128,
+            hash_max_listpack_value: 64,
+            zset_max_listpack_entries: 128,
+            zset_max_listpack_value: 64,
+            set_max_intset_entries: 512,
+            set_max_listpack_entries: 128,
+            set_max_listpack_value: 64,
+            list_max_listpack_size: -2,
+            rdb_compression: true,
+        }
+    }
+}
+
+impl Encodings {
+    /// Only use the plain encodings and don't compress.
+    /// Integers are still stored as numbers.
+    pub fn plain() -> Encodings {
+        Encodings {
+            hash_max_listpack_entries: 0,
+            hash_max_listpack_value: 0,
+            zset_max_listpack_entries: 0,
+            zset_max_listpack_value: 0,
+            set_max_intset_entries: 0,
+            set_max_listpack_entries: 0,
+            set_max_listpack_value: 0,
+            list_max_listpack_size: 0,
+            rdb_compression: false,
+        }
+    }
+
+    /// Checks if an entry of `length` bytes can be added to a node of a list.
+    fn list_node_fits<P: Packed>(&self, node: &P, length: usize) -> bool {
+        match self.list_max_listpack_size {
+            0 => false,
+            size if size > 0 => node.len() < size as usize,
+            size => {
+                let max_size = 4096 << ((-size).min(5) - 1);
+                node.is_empty() || node.size_with(length) <= max_size
+            }
+        }
+    }
+}
+
+pub struct RdbWriter<W: Write> {
+    out: W,
+    version: u32,
+    encodings: Encodings,
+    crc: u64,
+}
+
+impl<W: Write> RdbWriter<W> {
+    /// Creates a writer for RDB files of the given version, using the default encodings.
+    pub fn new(out: W, version: u32) -> RdbResult<RdbWriter<W>> {
+        RdbWriter::with_encodings(out, version, Encodings::default())
+    }
+
+    pub fn with_encodings(out: W, version: u32, encodings: Encodings) -> RdbResult<RdbWriter<W>> {
+        if !(version::SUPPORTED_MINIMUM..=version::SUPPORTED_MAXIMUM).contains(&version) {
+            return Err(RdbError::UnsupportedVersion {
+                version,
+                min: version::SUPPORTED_MINIMUM,
+                max: version::SUPPORTED_MAXIMUM,
+            });
+        }
+
+        Ok(RdbWriter {
+            out,
+            version,
+            encodings,
+            crc: 0,
+        })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Writes `data` and updates the checksum.
+    pub(crate) fn write_raw(&mut self, data: &[u8]) -> RdbOk {
+        self.out.write_all(data)?;
+        self.crc = crc64(self.crc, data);
+
+        Ok(())
+    }
+
+    pub(crate) fn write_u8(&mut self, value: u8) -> RdbOk {
+        self.write_raw(&[value])
+    }
+
+    pub(crate) fn write_length(&mut self, length: u64) -> RdbOk {
+        if length < 1 << 6 {
+            self.write_u8(length as u8)
+        } else if length < 1 << 14 {
+            self.write_raw(&[
+                (constant::RDB_14BITLEN << 6) | (length >> 8) as u8,
+                length as u8,
+            ])
+        } else if length <= u32::MAX as u64 {
+            self.write_u8(constant::RDB_32BITLEN)?;
+            self.write_raw(&(length as u32).to_be_bytes())
+        } else {
+            self.write_u8(constant::RDB_64BITLEN)?;
+            self.write_raw(&length.to_be_bytes())
+        }
+    }
+
+    /// Writes a string, as an integer or compressed if possible.
+    pub(crate) fn write_blob(&mut self, data: &[u8]) -> RdbOk {
+        if data.len() <= MAX_INTEGER_LENGTH {
+            if let Some(value) = compact::parse_integer(data) {
+                return self.write_integer(value, data);
+            }
+        }
+
+        if self.encodings.rdb_compression && data.len() > MIN_COMPRESS_LENGTH {
+            // Only worth it if at least 4 bytes are saved
+            match lzf::compress(data) {
+                Ok(compressed) if compressed.len() <= data.len() - 4 => {
+                    self.write_u8((constant::RDB_ENCVAL << 6) | encoding::LZF as u8)?;
+                    self.write_length(compressed.len() as u64)?;
+                    self.write_length(data.len() as u64)?;
+                    return self.write_raw(&compressed);
+                }
+                _ => {}
+            }
+        }
+
+        self.write_raw_blob(data)
+    }
+
+    fn write_raw_blob(&mut self, data: &[u8]) -> RdbOk {
+        self.write_length(data.len() as u64)?;
+        self.write_raw(data)
+    }
+
+    fn write_integer(&mut self, value: i64, data: &[u8]) -> RdbOk {
+        let header = constant::RDB_ENCVAL << 6;
+        let bytes = value.to_le_bytes();
+
+        if (i8::MIN as i64..=i8::MAX as i64).contains(&value) {
+            self.write_u8(header | encoding::INT8 as u8)?;
+            self.write_raw(&bytes[..1])
+        } else if (i16::MIN as i64..=i16::MAX as i64).contains(&value) {
+            self.write_u8(header | encoding::INT16 as u8)?;
+            self.write_raw(&bytes[..2])
+        } else if (i32::MIN as i64..=i32::MAX as i64).contains(&value) {
+            self.write_u8(header | encoding::INT32 as u8)?;
+            self.write_raw(&bytes[..4])
+        } else {
+            self.write_raw_blob(data)
+        }
+    }
+
+    /// Writes a score as a string, as sorted sets did before version 8.
+    fn write_string_score(&mut self, score: f64) -> RdbOk {
+        if score.is_nan() {
+            self.write_u8(253)
+        } else if score.is_infinite() {
+            self.write_u8(if score > 0.0 { 254 } else { 255 })
+        } else {
+            let score = compact::format_score(score);
+            self.write_u8(score.len() as u8)?;
+            self.write_raw(score.as_bytes())
+        }
+    }
+
+    /// Fails if `feature` is not available in the version being written.
+    fn require(&self, feature: &'static str, required: u32) -> RdbOk {
+        if self.version < required {
+            return Err(RdbError::UnsupportedByVersion {
+                feature,
+                version: self.version,
+                required,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Writes the magic string and the version number.
+    pub fn write_header(&mut self) -> RdbOk {
+        let header = format!("{}{:04}", constant::RDB_MAGIC, self.version);
+        self.write_raw(header.as_bytes())
+    }
+
+    /// Writes an aux field. Versions before 7 have no aux fields, so nothing is written.
+    pub fn write_aux(&mut self, key: &[u8], value: &[u8]) -> RdbOk {
+        if self.version < 7 {
+            return Ok(());
+        }
+
+        self.write_u8(op_code::AUX)?;
+        self.write_blob(key)?;
+        self.write_blob(value)
+    }
+
+    pub fn write_select_db(&mut self, db_index: u32) -> RdbOk {
+        self.write_u8(op_code::SELECTDB)?;
+        self.write_length(db_index as u64)
+    }
+
+    /// Writes the size hints of the current database. Versions before 7 have no hints,
+    /// so nothing is written.
+    pub fn write_resizedb(&mut self, db_size: u32, expires_size: u32) -> RdbOk {
+        if self.version < 7 {
+            return Ok(());
+        }
+
+        self.write_u8(op_code::RESIZEDB)?;
+        self.write_length(db_size as u64)?;
+        self.write_length(expires_size as u64)
+    }
+
+    /// Writes the expiry of the following key, in milliseconds since the epoch.
+    ///
+    /// Versions before 3 only store seconds.
+    pub fn write_expiry(&mut self, expiry: u64) -> RdbOk {
+        if self.version >= 3 {
+            self.write_u8(op_code::EXPIRETIME_MS)?;
+            self.write_raw(&expiry.to_le_bytes())
+        } else {
+            self.write_u8(op_code::EXPIRETIME)?;
+            self.write_raw(&((expiry / 1000) as u32).to_le_bytes())
+        }
+    }
+
+    /// Writes the idle time in seconds of the following key, a hint for LRU eviction.
+    /// Versions before 9 have no eviction info, so nothing is written.
+    pub fn write_idle(&mut self, idle: u64) -> RdbOk {
+        if self.version < 9 {
+            return Ok(());
+        }
+
+        self.write_u8(op_code::IDLE)?;
+        self.write_length(idle)
+    }
+
+    /// Writes the logarithmic access frequency of the following key, a hint for LFU
+    /// eviction. Versions before 9 have no eviction info, so nothing is written.
+    pub fn write_freq(&mut self, freq: u8) -> RdbOk {
+        if self.version < 9 {
+            return Ok(());
+        }
+
+        self.write_u8(op_code::FREQ)?;
+        self.write_u8(freq)
+    }
+
+    /// Writes a key with its value and optional expiry.
+    pub fn write_object(&mut self, key: &[u8], value: &Value, expiry: Option<u64>) -> RdbOk {
+        if let Some(expiry) = expiry {
+            self.write_expiry(expiry)?;
+        }
+
+        match value {
+            Value::String(value) => {
+                self.write_u8(encoding_type::STRING)?;
+                self.write_blob(key)?;
+                self.write_blob(value)
+            }
+            Value::List(elements) => self.write_list(key, elements),
+            Value::Set(members) => self.write_set(key, members),
+            Value::SortedSet(elements) => self.write_sorted_set(key, elements),
+            Value::Hash(pairs) => self.write_hash(key, pairs),
+        }
+    }
+
+    /// Writes the type, key and a single blob holding the compactly encoded value.
+    fn write_compact(&mut self, enc_type: u8, key: &[u8], blob: &[u8]) -> RdbOk {
+        self.write_u8(enc_type)?;
+        self.write_blob(key)?;
+        self.write_blob(blob)
+    }
+
+    /// Splits the elements of a list into nodes of a quicklist.
+    fn pack_list<P: Packed>(&self, elements: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        let mut nodes = vec![];
+        let mut node = P::new(self.version);
+        for element in elements {
+            if !node.is_empty() && !self.encodings.list_node_fits(&node, element.len()) {
+                nodes.push(node.finish());
+                node = P::new(self.version);
+            }
+            node.push(element);
+        }
+        if !node.is_empty() {
+            nodes.push(node.finish());
+        }
+
+        nodes
+    }
+
+    fn write_list(&mut self, key: &[u8], elements: &[Vec<u8>]) -> RdbOk {
+        let compact = self.encodings.list_max_listpack_size != 0;
+
+        if compact && self.version >= 10 {
+            let nodes = self.pack_list::<Listpack>(elements);
+
+            self.write_u8(encoding_type::LIST_QUICKLIST_2)?;
+            self.write_blob(key)?;
+            self.write_length(nodes.len() as u64)?;
+            for node in nodes {
+                self.write_length(quicklist_container::PACKED as u64)?;
+                self.write_blob(&node)?;
+            }
+            return Ok(());
+        }
+
+        if compact && self.version >= 7 {
+            let nodes = self.pack_list::<Ziplist>(elements);
+
+            self.write_u8(encoding_type::LIST_QUICKLIST)?;
+            self.write_blob(key)?;
+            self.write_length(nodes.len() as u64)?;
+            for node in nodes {
+                self.write_blob(&node)?;
+            }
+            return Ok(());
+        }
+
+        if compact && self.version >= 2 {
+            let nodes = self.pack_list::<Ziplist>(elements);
+            if nodes.len() == 1 {
+                return self.write_compact(encoding_type::LIST_ZIPLIST, key, &nodes[0]);
+            }
+        }
+
+        self.write_u8(encoding_type::LIST)?;
+        self.write_blob(key)?;
+        self.write_length(elements.len() as u64)?;
+        for element in elements {
+            self.write_blob(element)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_set(&mut self, key: &[u8], members: &[Vec<u8>]) -> RdbOk {
+        if self.version >= 2 && members.len() <= self.encodings.set_max_intset_entries {
+            if let Some(intset) = compact::intset(members) {
+                return self.write_compact(encoding_type::SET_INTSET, key, &intset);
+            }
+        }
+
+        let max_value = self.encodings.set_max_listpack_value;
+        if self.version >= 11
+            && members.len() <= self.encodings.set_max_listpack_entries
+            && members.iter().all(|member| member.len() <= max_value)
+        {
+            let listpack = pack::<Listpack>(self.version, members.iter().map(|member| &member[..]));
+            return self.write_compact(encoding_type::SET_LISTPACK, key, &listpack);
+        }
+
+        self.write_u8(encoding_type::SET)?;
+        self.write_blob(key)?;
+        self.write_length(members.len() as u64)?;
+        for member in members {
+            self.write_blob(member)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_sorted_set(&mut self, key: &[u8], elements: &[(f64, Vec<u8>)]) -> RdbOk {
+        let compact = self.version >= 2
+            && elements.len() <= self.encodings.zset_max_listpack_entries
+            && elements.iter().all(|(score, member)| {
+                !score.is_nan() && member.len() <= self.encodings.zset_max_listpack_value
+            });
+
+        if compact {
+            // Redis expects the elements ordered by score, then by member
+            let mut elements: Vec<&(f64, Vec<u8>)> = elements.iter().collect();
+            elements.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap().then_with(|| a.1.cmp(&b.1)));
+
+            let scores: Vec<String> = elements
+                .iter()
+                .map(|(score, _)| compact::format_score(*score))
+                .collect();
+            let entries = elements
+                .iter()
+                .zip(&scores)
+                .flat_map(|((_, member), score)| vec![&member[..], score.as_bytes()]);
+
+            return if self.version >= 10 {
+                let listpack = pack::<Listpack>(self.version, entries);
+                self.write_compact(encoding_type::ZSET_LISTPACK, key, &listpack)
+            } else {
+                let ziplist = pack::<Ziplist>(self.version, entries);
+                self.write_compact(encoding_type::ZSET_ZIPLIST, key, &ziplist)
+            };
+        }
+
+        let binary_scores = self.version >= 8;
+        let enc_type = if binary_scores {
+            encoding_type::ZSET_2
+        } else {
+            encoding_type::ZSET
+        };
+        self.write_u8(enc_type)?;
+        self.write_blob(key)?;
+        self.write_length(elements.len() as u64)?;
+        for (score, member) in elements {
+            self.write_blob(member)?;
+            if binary_scores {
+                self.write_raw(&score.to_le_bytes())?;
+            } else {
+                self.write_string_score(*score)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_hash(&mut self, key: &[u8], pairs: &[(Vec<u8>, Vec<u8>)]) -> RdbOk {
+        let max_value = self.encodings.hash_max_listpack_value;
+        let compact = pairs.len() <= self.encodings.hash_max_listpack_entries
+            && pairs
+                .iter()
+                .all(|(field, value)| field.len() <= max_value && value.len() <= max_value);
+
+        let entries = pairs
+            .iter()
+            .flat_map(|(field, value)| vec![&field[..], &value[..]]);
+        if compact && self.version >= 10 {
+            let listpack = pack::<Listpack>(self.version, entries);
+            return self.write_compact(encoding_type::HASH_LISTPACK, key, &listpack);
+        }
+        if compact && self.version >= 4 {
+            let ziplist = pack::<Ziplist>(self.version, entries);
+            return self.write_compact(encoding_type::HASH_ZIPLIST, key, &ziplist);
+        }
+        if compact && self.version >= 2 && pairs.len() < 254 {
+            return self.write_compact(encoding_type::HASH_ZIPMAP, key, &compact::zipmap(pairs));
+        }
+
+        self.write_u8(encoding_type::HASH)?;
+        self.write_blob(key)?;
+        self.write_length(pairs.len() as u64)?;
+        for (field, value) in pairs {
+            self.write_blob(field)?;
+            self.write_blob(value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a value that was not decoded, as stored in the file after the key.
+    ///
+    /// Fails if the version being written doesn't know the type.
+    pub fn write_raw_object(
+        &mut self,
+        key: &[u8],
+        value_type: u8,
+        raw: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbOk {
+        let (feature, required) = match value_type {
+            encoding_type::MODULE_2 => ("Module values", 8),
+            encoding_type::STREAM_LISTPACKS => ("Streams", 9),
+            encoding_type::STREAM_LISTPACKS_2 => ("Streams written by Redis 7.0", 10),
+            encoding_type::STREAM_LISTPACKS_3 => ("Streams written by Redis 7.2", 11),
+            _ => {
+                return Err(RdbError::Other(format!(
+                    "Values of type {} can't be copied",
+                    value_type
+                )))
+            }
+        };
+        self.require(feature, required)?;
+
+        if let Some(expiry) = expiry {
+            self.write_expiry(expiry)?;
+        }
+        self.write_u8(value_type)?;
+        self.write_blob(key)?;
+        self.write_raw(raw)
+    }
+
+    /// Writes a library of Redis functions.
+    pub fn write_function(&mut self, code: &[u8]) -> RdbOk {
+        self.require("Functions", 10)?;
+
+        self.write_u8(op_code::FUNCTION2)?;
+        self.write_blob(code)
+    }
+
+    /// Writes module data not tied to a key, as stored in the file after the opcode.
+    pub fn write_module_aux(&mut self, raw: &[u8]) -> RdbOk {
+        self.require("Module aux data", 9)?;
+
+        self.write_u8(op_code::MODULE_AUX)?;
+        self.write_raw(raw)
+    }
+
+    /// Writes the EOF opcode followed by the checksum (since version 5) and flushes the output.
+    pub fn write_eof(&mut self) -> RdbOk {
+        self.write_u8(op_code::EOF)?;
+        if self.version >= 5 {
+            let checksum = self.crc.to_le_bytes();
+            self.out.write_all(&checksum)?;
+        }
+        self.out.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Serializes `value` as a DUMP payload, as used by the DUMP and RESTORE commands:
+/// the value as stored in a RDB file of `version`, followed by the version and a checksum.
+pub fn dump_payload(value: &Value, version: u32) -> RdbResult<Vec<u8>> {
+    let mut writer = RdbWriter::new(Vec::new(), version)?;
+    writer.write_object(b"", value, None)?;
+
+    Ok(finish_payload(writer.into_inner(), version))
+}
+
+/// Serializes a value that was not decoded as a DUMP payload, see `dump_payload`.
+///
+/// Fails if `version` doesn't know the type.
+pub fn dump_raw_payload(value_type: u8, raw: &[u8], version: u32) -> RdbResult<Vec<u8>> {
+    let mut writer = RdbWriter::new(Vec::new(), version)?;
+    writer.write_raw_object(b"", value_type, raw, None)?;
+
+    Ok(finish_payload(writer.into_inner(), version))
+}
+
+/// Turns an object written with an empty key into a DUMP payload.
+fn finish_payload(object: Vec<u8>, version: u32) -> Vec<u8> {
+    // The empty key is the single zero byte after the type
+    let mut payload = Vec::with_capacity(object.len() + 9);
+    payload.push(object[0]);
+    payload.extend_from_slice(&object[2..]);
+    payload.extend_from_slice(&(version as u16).to_le_bytes());
+
+    let checksum = crc64(0, &payload);
+    payload.extend_from_slice(&checksum.to_le_bytes());
+
+    payload
+}
+
+fn pack<'a, P: Packed>(version: u32, entries: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut packed = P::new(version);
+    for entry in entries {
+        packed.push(entry);
+    }
+
+    packed.finish()
+}
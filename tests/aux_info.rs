@@ -0,0 +1,93 @@
+mod common;
+
+use common::read_dump;
+use rdb::filter::Simple;
+use rdb::formatter::{Control, Formatter, Nil};
+use rdb::types::{AuxInfo, EncodingType, RdbResult};
+use rdb::RdbParser;
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn test_aux_info_after_parsing() {
+    let data = read_dump("quicklist_with_multiple_nodes.rdb");
+    let mut parser = RdbParser::new(&data[..], Nil::new(), Simple::new());
+    parser.parse().unwrap();
+
+    let info = parser.aux_info();
+    assert_eq!(Some("2.9.999"), info.redis_ver.as_deref());
+    assert_eq!(Some(64), info.redis_bits);
+    assert_eq!(Some(1420740379), info.ctime);
+    assert_eq!(Some(508200), info.used_mem);
+    assert_eq!(None, info.repl_id);
+    assert_eq!(
+        Some(UNIX_EPOCH + Duration::from_secs(1420740379)),
+        info.created_at()
+    );
+    assert!(info.other.is_empty());
+}
+
+#[test]
+fn test_update() {
+    let mut info = AuxInfo::default();
+    info.update(b"repl-id", b"0123456789abcdef0123456789abcdef01234567");
+    info.update(b"repl-offset", b"1234");
+    info.update(b"repl-stream-db", b"0");
+    info.update(b"aof-preamble", b"1");
+    info.update(b"ctime", b"yesterday");
+    info.update(b"lua", b"return 1");
+
+    assert_eq!(Some(1234), info.repl_offset);
+    assert_eq!(Some(0), info.repl_stream_db);
+    assert_eq!(Some(true), info.aof_base);
+    assert_eq!(None, info.ctime);
+    assert_eq!(vec![b"return 1".to_vec()], info.lua_scripts);
+    assert_eq!(vec![(b"ctime".to_vec(), b"yesterday".to_vec())], info.other);
+}
+
+/// Records the source Redis version and stops at the first key
+#[derive(Default)]
+struct SourceVersion {
+    redis_ver: Option<String>,
+    calls: u32,
+    seen_database: bool,
+}
+
+impl Formatter for &mut SourceVersion {
+    fn aux_info(&mut self, info: &AuxInfo) -> RdbResult<()> {
+        assert!(!self.seen_database);
+        self.redis_ver = info.redis_ver.clone();
+        self.calls += 1;
+        Ok(())
+    }
+
+    fn start_database(&mut self, _db_index: u32) -> RdbResult<()> {
+        self.seen_database = true;
+        Ok(())
+    }
+
+    fn start_set(
+        &mut self,
+        _key: &[u8],
+        _cardinality: u32,
+        _expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        Ok(Control::Stop)
+    }
+}
+
+#[test]
+fn test_aux_info_callback() {
+    let data = read_dump("quicklist_with_multiple_nodes.rdb");
+    let mut formatter = SourceVersion::default();
+    rdb::parse(&data[..], &mut formatter, Simple::new()).unwrap();
+    assert_eq!(Some("2.9.999".to_string()), formatter.redis_ver);
+    assert_eq!(1, formatter.calls);
+
+    // Old files without aux fields still get the callback
+    let data = read_dump("empty_database.rdb");
+    let mut formatter = SourceVersion::default();
+    rdb::parse(&data[..], &mut formatter, Simple::new()).unwrap();
+    assert_eq!(None, formatter.redis_ver);
+    assert_eq!(1, formatter.calls);
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::{key_events, read_dump, Recorder};
+use rdb::cluster::{hash_tag, key_slot, Split};
+use rdb::filter::Simple;
+
+#[test]
+fn test_key_slot() {
+    assert_eq!(12739, key_slot(b"123456789"));
+    assert_eq!(12182, key_slot(b"foo"));
+    assert_eq!(5061, key_slot(b"bar"));
+    assert_eq!(
+        key_slot(b"{user1000}.following"),
+        key_slot(b"{user1000}.followers")
+    );
+
+    assert_eq!(b"user1000", hash_tag(b"{user1000}.following"));
+    assert_eq!(b"foo{}{bar}", hash_tag(b"foo{}{bar}"));
+    assert_eq!(b"{bar", hash_tag(b"foo{{bar}}zap"));
+    assert_eq!(b"bar", hash_tag(b"foo{bar}{zap}"));
+    assert_eq!(b"foo{bar", hash_tag(b"foo{bar"));
+}
+
+/// The keys of `data` and the database they are in
+fn keys(data: &[u8]) -> Vec<String> {
+    let recorder = Recorder::new();
+    rdb::parse(data, recorder.clone(), Simple::new()).unwrap();
+    recorder
+        .events()
+        .into_iter()
+        .filter(|event| event.starts_with("start_database") || event.starts_with("set "))
+        .collect()
+}
+
+#[test]
+fn test_split() {
+    let data = read_dump("parser_filters.rdb");
+
+    let mut split = Split::new(vec![Vec::new(), Vec::new()]);
+    split.assign(0..=8191, 0);
+    split.assign(8192..=16383, 1);
+    let outputs = split.split(&data[..]).unwrap();
+
+    let mut expected = key_events(&data);
+    let mut events: Vec<String> = outputs.iter().flat_map(|out| key_events(out)).collect();
+    // Every output has its own database and end events
+    expected.retain(|event| !event.contains("_database") && !event.contains("_rdb"));
+    events.retain(|event| !event.contains("_database") && !event.contains("_rdb"));
+    expected.sort();
+    events.sort();
+    assert_eq!(expected, events);
+
+    for (index, output) in outputs.iter().enumerate() {
+        for event in keys(output)
+            .iter()
+            .filter(|event| event.starts_with("set "))
+        {
+            let key = event.split(' ').nth(1).unwrap();
+            assert_eq!(index, key_slot(key.as_bytes()) as usize / 8192, "{}", key);
+        }
+    }
+
+    let mut split = Split::new(vec![Vec::new()]);
+    split.assign(0..=100, 0);
+    assert!(split.split(&data[..]).is_err());
+}
+
+#[test]
+fn test_split_writes_database_0() {
+    let data = read_dump("multiple_databases.rdb");
+
+    let mut split = Split::new(vec![Vec::new()]);
+    split.assign(0..=16383, 0);
+    split.set_database(2);
+    let outputs = split.split(&data[..]).unwrap();
+
+    assert_eq!(
+        vec!["start_database 0", "set key_in_second_database second None"],
+        keys(&outputs[0])
+    );
+}
+
+#[test]
+fn test_split_maps_databases() {
+    let data = read_dump("multiple_databases.rdb");
+
+    let mut split = Split::new(vec![Vec::new()]);
+    split.assign(0..=16383, 0);
+    split.map_database(0, 1);
+    split.map_database(2, 0);
+    let outputs = split.split(&data[..]).unwrap();
+
+    assert_eq!(
+        vec!["start_database 0", "set key_in_second_database second None"],
+        keys(&outputs[0])
+    );
+}
@@ -0,0 +1,366 @@
+#![allow(dead_code)]
+
+use rdb::filter::Simple;
+use rdb::formatter::{Control, Formatter};
+use rdb::types::{EncodingType, RdbResult, Value};
+use rdb::RdbWriter;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Read;
+use std::rc::Rc;
+
+pub fn read_dump(name: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    File::open(format!("tests/dumps/{}", name))
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+pub fn dump_names() -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir("tests/dumps")
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.ends_with(".rdb"))
+        .collect();
+    names.sort();
+    names
+}
+
+/// A stream with one consumer group, as written by Redis 5 or 6
+pub fn stream() -> Vec<u8> {
+    let id_ms = [0x81, 0, 0, 0x01, 0x8b, 0xcf, 0xe5, 0x68, 0x00];
+    let mut raw = vec![];
+    // One listpack
+    raw.push(1);
+    raw.push(16);
+    raw.extend_from_slice(&[0; 16]);
+    raw.extend_from_slice(b"\x03abc");
+    // Length and last ID
+    raw.push(1);
+    raw.extend_from_slice(&id_ms);
+    raw.push(0);
+    // Consumer group with one pending entry
+    raw.extend_from_slice(b"\x01\x01g");
+    raw.extend_from_slice(&id_ms);
+    raw.push(0);
+    raw.push(1);
+    raw.extend_from_slice(&[7; 16 + 8]);
+    raw.push(1);
+    // Consumer with the pending entry
+    raw.extend_from_slice(b"\x01\x01c");
+    raw.extend_from_slice(&[8; 8]);
+    raw.push(1);
+    raw.extend_from_slice(&[7; 16]);
+    raw
+}
+
+pub fn rdb_with_stream() -> Vec<u8> {
+    let mut writer = RdbWriter::new(Vec::new(), 9).unwrap();
+    writer.write_header().unwrap();
+    writer.write_select_db(0).unwrap();
+    writer
+        .write_raw_object(b"stream", 15, &stream(), Some(1_000))
+        .unwrap();
+    writer
+        .write_object(b"key", &Value::String(b"value".to_vec()), None)
+        .unwrap();
+    writer.write_eof().unwrap();
+    writer.into_inner()
+}
+
+/// A version 9 file with a key idle for 300 seconds, a key accessed with a
+/// frequency of 5 and a key without eviction info
+pub fn rdb_with_eviction_info() -> Vec<u8> {
+    let mut data = b"REDIS0009\xfe\x00".to_vec();
+    data.extend_from_slice(b"\xf8\x41\x2c\x00\x01a\x01x");
+    data.extend_from_slice(b"\xf9\x05\x00\x01b\x01y");
+    data.extend_from_slice(b"\x00\x01c\x01z\xff");
+    data.extend_from_slice(&[0; 8]);
+    data
+}
+
+/// Records every callback as a line of text
+///
+/// Clones share the recorded events, so a clone can be handed to the parser.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    events: Rc<RefCell<Vec<String>>>,
+    rules: Vec<(String, Control)>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    pub fn events(&self) -> Vec<String> {
+        self.events.borrow().clone()
+    }
+
+    /// Answers every key-level callback whose event starts with `prefix` with `control`.
+    pub fn on(mut self, prefix: &str, control: Control) -> Recorder {
+        self.rules.push((prefix.into(), control));
+        self
+    }
+
+    fn push(&mut self, event: String) -> RdbResult<()> {
+        self.events.borrow_mut().push(event);
+        Ok(())
+    }
+
+    fn push_key(&mut self, event: String) -> RdbResult<Control> {
+        let control = self
+            .rules
+            .iter()
+            .find(|(prefix, _)| event.starts_with(prefix.as_str()))
+            .map(|&(_, control)| control)
+            .unwrap_or(Control::Continue);
+        self.push(event)?;
+        Ok(control)
+    }
+}
+
+fn s(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).into_owned()
+}
+
+impl Formatter for Recorder {
+    fn start_rdb(&mut self) -> RdbResult<()> {
+        self.push("start_rdb".into())
+    }
+    fn end_rdb(&mut self) -> RdbResult<()> {
+        self.push("end_rdb".into())
+    }
+    fn checksum(&mut self, checksum: &[u8]) -> RdbResult<()> {
+        self.push(format!("checksum {:?}", checksum))
+    }
+    fn start_database(&mut self, db_index: u32) -> RdbResult<()> {
+        self.push(format!("start_database {}", db_index))
+    }
+    fn end_database(&mut self, db_index: u32) -> RdbResult<()> {
+        self.push(format!("end_database {}", db_index))
+    }
+    fn resizedb(&mut self, db_size: u32, expires_size: u32) -> RdbResult<()> {
+        self.push(format!("resizedb {} {}", db_size, expires_size))
+    }
+    fn aux_field(&mut self, key: &[u8], value: &[u8]) -> RdbResult<()> {
+        self.push(format!("aux {} {}", s(key), s(value)))
+    }
+    fn set(&mut self, key: &[u8], value: &[u8], expiry: Option<u64>) -> RdbResult<Control> {
+        self.push_key(format!("set {} {} {:?}", s(key), s(value), expiry))
+    }
+    fn function(&mut self, code: &[u8]) -> RdbResult<()> {
+        self.push(format!("function {}", s(code)))
+    }
+    fn module_aux(&mut self, raw: &[u8]) -> RdbResult<()> {
+        self.push(format!("module_aux {:?}", raw))
+    }
+    fn eviction_info(&mut self, key: &[u8], idle: Option<u64>, freq: Option<u8>) -> RdbResult<()> {
+        self.push(format!("eviction_info {} {:?} {:?}", s(key), idle, freq))
+    }
+    fn raw_object(
+        &mut self,
+        key: &[u8],
+        value_type: u8,
+        raw: &[u8],
+        expiry: Option<u64>,
+    ) -> RdbResult<Control> {
+        self.push_key(format!(
+            "raw_object {} {} {:?} {:?}",
+            s(key),
+            value_type,
+            raw,
+            expiry
+        ))
+    }
+    fn start_hash(
+        &mut self,
+        key: &[u8],
+        length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.push_key(format!("start_hash {} {} {:?}", s(key), length, expiry))
+    }
+    fn end_hash(&mut self, key: &[u8]) -> RdbResult<()> {
+        self.push(format!("end_hash {}", s(key)))
+    }
+    fn hash_element(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> RdbResult<Control> {
+        self.push_key(format!("hash_element {} {} {}", s(key), s(field), s(value)))
+    }
+    fn start_set(
+        &mut self,
+        key: &[u8],
+        cardinality: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.push_key(format!("start_set {} {} {:?}", s(key), cardinality, expiry))
+    }
+    fn end_set(&mut self, key: &[u8]) -> RdbResult<()> {
+        self.push(format!("end_set {}", s(key)))
+    }
+    fn set_element(&mut self, key: &[u8], member: &[u8]) -> RdbResult<Control> {
+        self.push_key(format!("set_element {} {}", s(key), s(member)))
+    }
+    fn start_list(
+        &mut self,
+        key: &[u8],
+        length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.push_key(format!("start_list {} {} {:?}", s(key), length, expiry))
+    }
+    fn end_list(&mut self, key: &[u8]) -> RdbResult<()> {
+        self.push(format!("end_list {}", s(key)))
+    }
+    fn list_element(&mut self, key: &[u8], value: &[u8]) -> RdbResult<Control> {
+        self.push_key(format!("list_element {} {}", s(key), s(value)))
+    }
+    fn start_sorted_set(
+        &mut self,
+        key: &[u8],
+        length: u32,
+        expiry: Option<u64>,
+        _info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.push_key(format!(
+            "start_sorted_set {} {} {:?}",
+            s(key),
+            length,
+            expiry
+        ))
+    }
+    fn end_sorted_set(&mut self, key: &[u8]) -> RdbResult<()> {
+        self.push(format!("end_sorted_set {}", s(key)))
+    }
+    fn sorted_set_element(&mut self, key: &[u8], score: f64, member: &[u8]) -> RdbResult<Control> {
+        self.push_key(format!(
+            "sorted_set_element {} {} {}",
+            s(key),
+            score,
+            s(member)
+        ))
+    }
+}
+
+/// Events describing the keys and values, leaving out metadata and structure
+/// that depends on the encoding
+pub fn key_events(data: &[u8]) -> Vec<String> {
+    let recorder = Recorder::new();
+    rdb::parse(data, recorder.clone(), Simple::new()).unwrap();
+    recorder
+        .events()
+        .into_iter()
+        .filter(|event| {
+            !event.starts_with("checksum")
+                && !event.starts_with("aux")
+                && !event.starts_with("resizedb")
+        })
+        .map(|event| {
+            // Lengths of the original encoding, e.g. of quicklists, are not preserved.
+            let is_start = ["start_hash", "start_set", "start_list", "start_sorted_set"]
+                .iter()
+                .any(|prefix| event.starts_with(prefix));
+            if is_start {
+                let parts: Vec<&str> = event.rsplitn(3, ' ').collect();
+                format!("{} {}", parts[2], parts[0])
+            } else {
+                event
+            }
+        })
+        .collect()
+}
+
+/// Records the encoding of every key
+#[derive(Default)]
+pub struct Encodings(Vec<String>);
+
+impl Encodings {
+    fn push(&mut self, key: &[u8], info: EncodingType) -> RdbResult<Control> {
+        let info = format!("{:?}", info);
+        // Leave out the size in bytes
+        let name = info.split('(').next().unwrap();
+        self.0
+            .push(format!("{} {}", String::from_utf8_lossy(key), name));
+        Ok(Control::Continue)
+    }
+}
+
+impl Formatter for &mut Encodings {
+    fn start_hash(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        _expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.push(key, info)
+    }
+    fn start_set(
+        &mut self,
+        key: &[u8],
+        _cardinality: u32,
+        _expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.push(key, info)
+    }
+    fn start_list(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        _expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.push(key, info)
+    }
+    fn start_sorted_set(
+        &mut self,
+        key: &[u8],
+        _length: u32,
+        _expiry: Option<u64>,
+        info: EncodingType,
+    ) -> RdbResult<Control> {
+        self.push(key, info)
+    }
+}
+
+pub fn encodings_of(data: &[u8]) -> Vec<String> {
+    let mut encodings = Encodings::default();
+    rdb::parse(data, &mut encodings, Simple::new()).unwrap();
+    encodings.0
+}
+
+/// The encoding byte of every entry of a ziplist
+pub fn ziplist_headers(ziplist: &[u8]) -> Vec<u8> {
+    let mut headers = vec![];
+    let mut position = 10;
+    while ziplist[position] != 0xff {
+        position += if ziplist[position] == 0xfe { 5 } else { 1 };
+        let header = ziplist[position];
+        headers.push(header);
+        position += match header {
+            0xc0 => 3,
+            0xd0 => 5,
+            0xe0 => 9,
+            0xf0 => 4,
+            0xfe => 2,
+            0xf1..=0xfd => 1,
+            _ => match header >> 6 {
+                0 => 1 + (header & 0x3f) as usize,
+                1 => 2 + (((header & 0x3f) as usize) << 8 | ziplist[position + 1] as usize),
+                _ => {
+                    let length = ziplist[position + 1..position + 5]
+                        .iter()
+                        .fold(0, |length, &byte| length << 8 | byte as usize);
+                    5 + length
+                }
+            },
+        };
+    }
+    headers
+}
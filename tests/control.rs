@@ -0,0 +1,119 @@
+mod common;
+
+use common::{read_dump, Recorder};
+use rdb::filter::Simple;
+use rdb::formatter::Control;
+use rdb::types::Value;
+use rdb::{Encodings, RdbWriter};
+
+fn parse(name: &str, recorder: Recorder) -> Vec<String> {
+    let data = read_dump(name);
+    rdb::parse(&data[..], recorder.clone(), Simple::new()).unwrap();
+    recorder.events()
+}
+
+fn count(events: &[String], prefix: &str) -> usize {
+    events.iter().filter(|e| e.starts_with(prefix)).count()
+}
+
+#[test]
+fn test_skip_key_from_start() {
+    let events = parse(
+        "dictionary.rdb",
+        Recorder::new().on("start_hash", Control::SkipKey),
+    );
+
+    assert_eq!(0, count(&events, "hash_element"));
+    assert_eq!(1, count(&events, "end_hash force_dictionary"));
+    assert_eq!("end_rdb", events[events.len() - 1]);
+}
+
+#[test]
+fn test_skip_key_after_element() {
+    let events = parse(
+        "linkedlist.rdb",
+        Recorder::new().on("list_element", Control::SkipKey),
+    );
+    assert_eq!(1, count(&events, "list_element"));
+    assert_eq!(1, count(&events, "end_list"));
+
+    let events = parse(
+        "quicklist_with_multiple_nodes.rdb",
+        Recorder::new().on("list_element", Control::SkipKey),
+    );
+    assert_eq!(1, count(&events, "list_element"));
+    assert_eq!("end_rdb", events[events.len() - 2]);
+
+    let events = parse(
+        "regular_sorted_set.rdb",
+        Recorder::new().on("sorted_set_element", Control::SkipKey),
+    );
+    assert_eq!(1, count(&events, "sorted_set_element"));
+    assert_eq!(1, count(&events, "end_sorted_set"));
+}
+
+#[test]
+fn test_skip_sorted_set_with_string_scores() {
+    let mut writer = RdbWriter::with_encodings(Vec::new(), 3, Encodings::plain()).unwrap();
+    writer.write_header().unwrap();
+    writer.write_select_db(0).unwrap();
+    let value = Value::SortedSet(vec![(1.5, b"a".to_vec()), (f64::INFINITY, b"b".to_vec())]);
+    writer.write_object(b"scores", &value, None).unwrap();
+    let value = Value::String(b"value".to_vec());
+    writer.write_object(b"next", &value, None).unwrap();
+    writer.write_eof().unwrap();
+    let data = writer.into_inner();
+
+    let recorder = Recorder::new().on("start_sorted_set", Control::SkipKey);
+    rdb::parse(&data[..], recorder.clone(), Simple::new()).unwrap();
+    let events = recorder.events();
+    assert_eq!(0, count(&events, "sorted_set_element"));
+    assert_eq!(1, count(&events, "end_sorted_set scores"));
+    assert_eq!(1, count(&events, "set next value"));
+}
+
+#[test]
+fn test_skip_key_continues_with_next_key() {
+    let all = parse("parser_filters.rdb", Recorder::new());
+    let events = parse(
+        "parser_filters.rdb",
+        Recorder::new().on("start_hash h1", Control::SkipKey),
+    );
+
+    assert_eq!(0, count(&events, "hash_element h1 "));
+    assert_eq!(
+        count(&all, "hash_element h3 "),
+        count(&events, "hash_element h3 ")
+    );
+    assert_eq!(all.len() - count(&all, "hash_element h1 "), events.len());
+}
+
+#[test]
+fn test_stop() {
+    let events = parse(
+        "parser_filters.rdb",
+        Recorder::new().on("set k3 ", Control::Stop),
+    );
+
+    assert_eq!("set k3 wwwwwwww None", events[events.len() - 1]);
+    assert_eq!(0, count(&events, "end_rdb"));
+
+    let events = parse(
+        "dictionary.rdb",
+        Recorder::new().on("hash_element", Control::Stop),
+    );
+    assert_eq!(1, count(&events, "hash_element"));
+    assert_eq!(0, count(&events, "end_hash"));
+}
+
+#[test]
+fn test_stop_push_parser() {
+    let data = read_dump("parser_filters.rdb");
+    let recorder = Recorder::new().on("set k3 ", Control::Stop);
+    let mut parser = rdb::PushParser::new(recorder.clone(), Simple::new());
+
+    parser.feed(&data).unwrap();
+    assert!(parser.is_done());
+    parser.finish().unwrap();
+    assert_eq!("set k3 wwwwwwww None", recorder.events().last().unwrap());
+}
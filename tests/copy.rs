@@ -0,0 +1,77 @@
+mod common;
+
+use common::{dump_names, encodings_of, key_events, read_dump, Recorder};
+use rdb::filter::Simple;
+use rdb::types::Type;
+use regex::bytes::Regex;
+
+fn copy(data: &[u8], filter: Simple) -> Vec<u8> {
+    let mut out = Vec::new();
+    rdb::copy(data, &mut out, filter).unwrap();
+    out
+}
+
+/// The events of parsing `data` with `filter`, without the metadata of the file
+fn filtered_events(data: &[u8], filter: Simple) -> Vec<String> {
+    let recorder = Recorder::new();
+    rdb::parse(data, recorder.clone(), filter).unwrap();
+    recorder
+        .events()
+        .into_iter()
+        .filter(|event| {
+            !event.starts_with("checksum")
+                && !event.starts_with("aux")
+                && !event.starts_with("resizedb")
+        })
+        .collect()
+}
+
+#[test]
+fn test_copy_dumps() {
+    for name in dump_names() {
+        let data = read_dump(&name);
+        let copied = copy(&data, Simple::new());
+
+        assert_eq!(&data[..9], &copied[..9], "{}", name);
+        assert_eq!(key_events(&data), key_events(&copied), "{}", name);
+        // The values are not re-encoded
+        assert_eq!(encodings_of(&data), encodings_of(&copied), "{}", name);
+    }
+
+    // Without RESIZEDB hints to leave out, the copy is identical, including the checksum.
+    let data = read_dump("rdb_version_5_with_checksum.rdb");
+    assert_eq!(data, copy(&data, Simple::new()));
+}
+
+#[test]
+fn test_copy_filtered() {
+    let filters = || {
+        let mut keys = Simple::new();
+        keys.add_keys(Regex::new("^l1").unwrap());
+
+        let mut types = Simple::new();
+        types.add_type(Type::Hash);
+        types.add_type(Type::String);
+
+        let mut databases = Simple::new();
+        databases.add_database(2);
+
+        vec![
+            ("parser_filters.rdb", keys),
+            ("parser_filters.rdb", types),
+            ("multiple_databases.rdb", databases),
+        ]
+    };
+
+    for ((name, filter), (_, expected)) in filters().into_iter().zip(filters()) {
+        let data = read_dump(name);
+        let copied = copy(&data, filter);
+
+        assert_eq!(
+            filtered_events(&data, expected),
+            filtered_events(&copied, Simple::new()),
+            "{}",
+            name
+        );
+    }
+}
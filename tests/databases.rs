@@ -0,0 +1,54 @@
+mod common;
+
+use common::{read_dump, Recorder};
+use rdb::filter::Simple;
+use rdb::RdbParser;
+
+fn databases(events: &[String]) -> Vec<&str> {
+    events
+        .iter()
+        .filter(|event| event.starts_with("start_database") || event.starts_with("end_database"))
+        .map(|event| &event[..])
+        .collect()
+}
+
+#[test]
+fn test_map_database() {
+    let data = read_dump("multiple_databases.rdb");
+
+    // The filter sees the mapped index
+    let mut filter = Simple::new();
+    filter.add_database(5);
+    let recorder = Recorder::new();
+    RdbParser::builder()
+        .map_database(2, 5)
+        .build(&data[..], recorder.clone(), filter)
+        .parse()
+        .unwrap();
+    assert_eq!(
+        vec!["start_database 5", "end_database 5"],
+        databases(&recorder.events())
+    );
+
+    let recorder = Recorder::new();
+    let mut parser = RdbParser::builder()
+        .map_database(0, 1)
+        .map_database(2, 0)
+        .build_push(recorder.clone(), Simple::new());
+    parser.feed(&data).unwrap();
+    parser.finish().unwrap();
+    assert_eq!(
+        vec!["start_database 1", "start_database 0", "end_database 0"],
+        databases(&recorder.events())
+    );
+
+    let err = RdbParser::builder()
+        .map_database(2, 0)
+        .build(&data[..], Recorder::new(), Simple::new())
+        .parse()
+        .unwrap_err();
+    assert_eq!(
+        "Databases 0 and 2 are both mapped to database 0",
+        err.to_string()
+    );
+}
@@ -0,0 +1,269 @@
+mod common;
+
+use common::{dump_names, rdb_with_stream, read_dump};
+use rdb::filter::Simple;
+use rdb::formatter::biggest::Report;
+use rdb::formatter::protocol::Restore;
+use rdb::formatter::typed_json::Binary;
+use rdb::formatter::{Biggest, Memory, Plain, Protocol, TypedJSON, JSON, NDJSON};
+use rdb::types::Value;
+use rdb::{Encodings, PushParser, RdbParser, RdbWriter};
+
+#[test]
+fn test_json_with_writer() {
+    for name in dump_names() {
+        let data = read_dump(&name);
+        let mut parser = RdbParser::new(&data[..], JSON::with_writer(Vec::new()), Simple::new());
+        parser.parse().unwrap();
+        let json = parser.into_formatter().into_inner().unwrap();
+
+        let path = format!("tests/dumps/json/{}.json", name.trim_end_matches(".rdb"));
+        let expected = std::fs::read(path).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&expected).trim_end(),
+            String::from_utf8_lossy(&json).trim_end(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_text_formatters_with_writer() {
+    let data = read_dump("multiple_databases.rdb");
+
+    let mut parser = PushParser::new(Plain::with_writer(Vec::new()), Simple::new());
+    parser.feed(&data).unwrap();
+    parser.finish().unwrap();
+    let plain = parser.into_formatter().into_inner().unwrap();
+    assert_eq!(
+        "db=0 key_in_zeroth_database -> zero\ndb=2 key_in_second_database -> second\n",
+        String::from_utf8_lossy(&plain)
+            .split("checksum")
+            .next()
+            .unwrap()
+    );
+
+    let mut parser = RdbParser::new(&data[..], Protocol::with_writer(Vec::new()), Simple::new());
+    parser.parse().unwrap();
+    let protocol = parser.into_formatter().into_inner().unwrap();
+    assert!(protocol.starts_with(b"*2\r\n$6\r\nSELECT\r\n$1\r\n0\r\n*3\r\n$3\r\nSET\r\n"));
+}
+
+#[test]
+fn test_protocol_stream_needs_restore() {
+    let data = rdb_with_stream();
+
+    let mut parser = RdbParser::new(&data[..], Protocol::with_writer(Vec::new()), Simple::new());
+    let err = parser.parse().unwrap_err();
+    assert!(err.to_string().contains("Key stream"));
+
+    let mut formatter = Protocol::with_writer(Vec::new());
+    formatter.set_restore(Restore::default());
+    let mut parser = RdbParser::new(&data[..], formatter, Simple::new());
+    parser.parse().unwrap();
+    let protocol = parser.into_formatter().into_inner().unwrap();
+    assert!(protocol
+        .starts_with(b"*2\r\n$6\r\nSELECT\r\n$1\r\n0\r\n*5\r\n$7\r\nRESTORE\r\n$6\r\nstream\r\n"));
+}
+
+fn typed_json(data: &[u8], binary: Binary) -> String {
+    let mut formatter = TypedJSON::with_writer(Vec::new());
+    formatter.set_binary(binary);
+    let mut parser = RdbParser::new(data, formatter, Simple::new());
+    parser.parse().unwrap();
+    String::from_utf8(parser.into_formatter().into_inner().unwrap()).unwrap()
+}
+
+#[test]
+fn test_typed_json() {
+    let mut writer = RdbWriter::new(Vec::new(), 9).unwrap();
+    writer.write_header().unwrap();
+    writer.write_select_db(2).unwrap();
+    let value = Value::String(b"\xff\\x".to_vec());
+    writer
+        .write_object(b"bin\x80", &value, Some(1700000000000))
+        .unwrap();
+    let value = Value::SortedSet(vec![(1.5, b"a".to_vec()), (f64::INFINITY, b"b".to_vec())]);
+    writer.write_object(b"zset", &value, None).unwrap();
+    writer.write_eof().unwrap();
+    let data = writer.into_inner();
+
+    assert_eq!(
+        concat!(
+            "[\n",
+            r#"{"db":2,"key":"bin\\x80","type":"string","encoding":"string","expiry":1700000000000,"binary":"escape","value":"\\xff\\\\x"},"#,
+            "\n",
+            r#"{"db":2,"key":"zset","type":"sortedset","encoding":"ziplist","expiry":null,"binary":"escape","value":{"a":1.5,"b":"inf"}}"#,
+            "\n]\n"
+        ),
+        typed_json(&data, Binary::Escape)
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&typed_json(&data, Binary::Base64)).unwrap();
+    assert_eq!("YmlugA==", json[0]["key"]);
+    assert_eq!("/1x4", json[0]["value"]);
+    assert_eq!(1.5, json[1]["value"]["YQ=="]);
+}
+
+#[test]
+fn test_ndjson() {
+    let mut writer = RdbWriter::new(Vec::new(), 9).unwrap();
+    writer.write_header().unwrap();
+    writer.write_select_db(1).unwrap();
+    let value = Value::List(vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+    writer.write_object(b"list", &value, Some(1500)).unwrap();
+    let value = Value::Hash(vec![(b"f".to_vec(), b"\xff".to_vec())]);
+    writer.write_object(b"hash", &value, None).unwrap();
+    writer.write_eof().unwrap();
+    let data = writer.into_inner();
+
+    let ndjson = |elements_per_line: Option<u32>| {
+        let mut formatter = NDJSON::with_writer(Vec::new());
+        formatter.set_time(1000);
+        if let Some(elements) = elements_per_line {
+            formatter.set_elements_per_line(elements);
+        }
+        let mut parser = RdbParser::new(&data[..], formatter, Simple::new());
+        parser.parse().unwrap();
+        String::from_utf8(parser.into_formatter().into_inner().unwrap()).unwrap()
+    };
+
+    assert_eq!(
+        concat!(
+            r#"{"db":1,"key":"list","type":"list","ttl":500,"value":["a","b","c"]}"#,
+            "\n",
+            r#"{"db":1,"key":"hash","type":"hash","ttl":null,"value":{"f":"\\xff"}}"#,
+            "\n"
+        ),
+        ndjson(None)
+    );
+    assert_eq!(
+        concat!(
+            r#"{"db":1,"key":"list","type":"list","ttl":500,"value":["a","b"]}"#,
+            "\n",
+            r#"{"db":1,"key":"list","type":"list","ttl":500,"value":["c"]}"#,
+            "\n",
+            r#"{"db":1,"key":"hash","type":"hash","ttl":null,"value":{"f":"\\xff"}}"#,
+            "\n"
+        ),
+        ndjson(Some(2))
+    );
+}
+
+#[test]
+fn test_memory() {
+    let mut writer = RdbWriter::new(Vec::new(), 11).unwrap();
+    writer.write_header().unwrap();
+    writer.write_select_db(0).unwrap();
+    let value = Value::String(b"value".to_vec());
+    writer.write_object(b"name", &value, Some(1500)).unwrap();
+    writer
+        .write_object(b"a,\"b\"", &Value::String(b"12345".to_vec()), None)
+        .unwrap();
+    let value = Value::Hash(vec![(b"field".to_vec(), b"v".to_vec())]);
+    writer.write_object(b"hash", &value, None).unwrap();
+    writer.write_eof().unwrap();
+    let data = writer.into_inner();
+
+    let mut parser = RdbParser::new(&data[..], Memory::with_writer(Vec::new()), Simple::new());
+    parser.parse().unwrap();
+    let csv = String::from_utf8(parser.into_formatter().into_inner().unwrap()).unwrap();
+    let rows: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(
+        "database,type,key,size_in_bytes,encoding,num_elements,len_largest_element,expiry",
+        rows[0]
+    );
+    // Dictionary entries, the key and the value embedded in its object, integers are
+    // stored in the object
+    assert_eq!("0,string,name,104,string,5,5,1500", rows[1]);
+    assert_eq!("0,string,\"a,\"\"b\"\"\",56,string,5,5,", rows[2]);
+
+    let hash: Vec<&str> = rows[3].split(',').collect();
+    assert_eq!(vec!["0", "hash", "hash"], hash[..3]);
+    assert_eq!(vec!["listpack", "1", "5", ""], hash[4..]);
+}
+
+#[test]
+fn test_biggest() {
+    let mut writer = RdbWriter::new(Vec::new(), 11).unwrap();
+    writer.write_header().unwrap();
+    writer.write_select_db(0).unwrap();
+    for (key, length) in [("small", 1), ("large", 3), ("medium", 2), ("tie", 2)] {
+        let elements = (0..length)
+            .map(|i| vec![b'a' + i; i as usize + 1])
+            .collect();
+        writer
+            .write_object(key.as_bytes(), &Value::List(elements), None)
+            .unwrap();
+    }
+    writer.write_select_db(1).unwrap();
+    let value = Value::String(b"value".to_vec());
+    writer.write_object(b"name", &value, None).unwrap();
+    writer.write_eof().unwrap();
+    let data = writer.into_inner();
+
+    let mut formatter = Biggest::with_writer(Vec::new());
+    formatter.set_report(Report::Json);
+    formatter.set_top(2);
+    let mut parser = RdbParser::new(&data[..], formatter, Simple::new());
+    parser.parse().unwrap();
+    let json = parser.into_formatter().into_inner().unwrap();
+    let rankings: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+    // Per database, type and metric, the earlier of keys of the same size first
+    assert_eq!(6, rankings.as_array().unwrap().len());
+    let by_elements = &rankings[2];
+    assert_eq!(0, by_elements["db"]);
+    assert_eq!("list", by_elements["type"]);
+    assert_eq!("elements", by_elements["by"]);
+    let keys: Vec<&str> = by_elements["keys"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|key| key["key"].as_str().unwrap())
+        .collect();
+    assert_eq!(vec!["large", "medium"], keys);
+    assert_eq!("ccc", by_elements["keys"][0]["largest_element"]);
+    assert_eq!(3, by_elements["keys"][0]["largest_size"]);
+
+    assert_eq!(1, rankings[3]["db"]);
+    assert_eq!("string", rankings[3]["type"]);
+}
+
+#[test]
+fn test_biggest_quicklist_size() {
+    let encodings = Encodings {
+        list_max_listpack_size: 2,
+        ..Encodings::default()
+    };
+    let elements: Vec<Vec<u8>> = (0..5).map(|i| vec![b'a' + i; 40]).collect();
+    for version in [7, 11] {
+        let write = |with_list: bool| {
+            let mut writer =
+                RdbWriter::with_encodings(Vec::new(), version, encodings.clone()).unwrap();
+            writer.write_header().unwrap();
+            writer.write_select_db(0).unwrap();
+            if with_list {
+                let value = Value::List(elements.clone());
+                writer.write_object(b"list", &value, None).unwrap();
+            }
+            writer.write_eof().unwrap();
+            writer.into_inner()
+        };
+        let data = write(true);
+
+        let mut formatter = Biggest::with_writer(Vec::new());
+        formatter.set_report(Report::Json);
+        let mut parser = RdbParser::new(&data[..], formatter, Simple::new());
+        parser.parse().unwrap();
+        let json = parser.into_formatter().into_inner().unwrap();
+        let rankings: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+        // The compressed nodes, as written
+        let key = &rankings[0]["keys"][0];
+        assert_eq!("quicklist", key["encoding"]);
+        assert_eq!((data.len() - write(false).len()) as u64, key["serialized"]);
+    }
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::encodings_of;
+use rdb::generate::{Generator, Size};
+use rdb::Type;
+use std::io::Cursor;
+
+fn generate(generator: &Generator) -> Vec<u8> {
+    let mut out = Vec::new();
+    generator.generate(&mut out).unwrap();
+    out
+}
+
+#[test]
+fn test_generate_is_deterministic() {
+    let mut generator = Generator::new(7);
+    generator.add_keys(Type::String, 100);
+    generator.add_keys(Type::Hash, 20);
+    generator.set_elements(Size::Exponential { mean: 10, max: 300 });
+
+    let data = generate(&generator);
+    assert_eq!(data, generate(&generator));
+    assert_ne!(data, generate(&Generator::new(8)));
+
+    // Streams and module values can't be generated
+    generator.add_keys(Type::Stream, 1);
+    assert!(generator.generate(Vec::new()).is_err());
+}
+
+#[test]
+fn test_generate_databases_and_expiries() {
+    let mut generator = Generator::new(1);
+    generator.add_keys(Type::String, 600);
+    generator.add_keys(Type::List, 300);
+    generator.add_keys(Type::SortedSet, 100);
+    generator.add_database(0);
+    generator.add_database(5);
+    generator.set_expiry_ratio(0.5);
+    generator.set_version(9);
+
+    let summary = rdb::inspect(Cursor::new(generate(&generator))).unwrap();
+    assert_eq!(9, summary.version);
+    assert_eq!(Some(1_700_000_000), summary.aux.ctime);
+
+    let indexes: Vec<u32> = summary.databases.iter().map(|db| db.index).collect();
+    assert_eq!(vec![0, 5], indexes);
+    for db in &summary.databases {
+        assert_eq!(500, db.keys);
+        assert_eq!(Some(500), db.size_hint);
+        assert_eq!(Some(db.expires as u32), db.expires_hint);
+        assert!(db.expires > 200 && db.expires < 300, "{}", db.expires);
+    }
+}
+
+#[test]
+fn test_generate_encodings() {
+    let mut generator = Generator::new(3);
+    generator.add_keys(Type::Set, 10);
+    generator.set_elements(Size::Fixed(20));
+    generator.set_value_size(Size::Uniform(1, 6));
+    generator.set_integer_ratio(1.0);
+
+    let encodings = encodings_of(&generate(&generator));
+    assert_eq!(10, encodings.len());
+    assert!(
+        encodings.iter().all(|e| e.ends_with(" Intset")),
+        "{:?}",
+        encodings
+    );
+
+    generator.set_encodings(rdb::Encodings::plain());
+    let encodings = encodings_of(&generate(&generator));
+    assert!(
+        encodings.iter().all(|e| !e.ends_with(" Intset")),
+        "{:?}",
+        encodings
+    );
+}
@@ -0,0 +1,282 @@
+mod common;
+
+use common::key_events;
+use rdb::filter::Simple;
+use rdb::formatter::typed_json::Binary;
+use rdb::formatter::TypedJSON;
+use rdb::{RdbParser, RdbWriter, Value};
+
+fn import(json: &str) -> rdb::RdbResult<Vec<u8>> {
+    let mut out = Vec::new();
+    rdb::import::json(json.as_bytes(), &mut out, 9)?;
+    Ok(out)
+}
+
+#[test]
+fn test_import_formatter_shape() {
+    let out = import(r#"[{"name":"value","queue":["a","b"]},{"user:1":{"name":"Jane"}}]"#).unwrap();
+
+    assert_eq!(
+        vec![
+            "start_rdb",
+            "start_database 0",
+            "set name value None",
+            "start_list queue None",
+            "list_element queue a",
+            "list_element queue b",
+            "end_list queue",
+            "start_database 1",
+            "start_hash user:1 None",
+            "hash_element user:1 name Jane",
+            "end_hash user:1",
+            "end_database 1",
+            "end_rdb",
+        ],
+        key_events(&out)
+    );
+}
+
+#[test]
+fn test_import_typed_shape() {
+    let out = import(
+        r#"[
+            {"db": 2, "key": "name", "type": "string", "value": "value", "expiry": 1700000000000},
+            {"key": "tags", "type": "set", "value": ["1", "2"]},
+            {"key": "scores", "type": "sortedset", "value": {"jane": 1.5, "joe": "2"}}
+        ]"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec![
+            "start_rdb",
+            "start_database 0",
+            "start_sorted_set scores None",
+            "sorted_set_element scores 1.5 jane",
+            "sorted_set_element scores 2 joe",
+            "end_sorted_set scores",
+            "start_set tags None",
+            "set_element tags 1",
+            "set_element tags 2",
+            "end_set tags",
+            "start_database 2",
+            "set name value Some(1700000000000)",
+            "end_database 2",
+            "end_rdb",
+        ],
+        key_events(&out)
+    );
+
+    let summary = rdb::inspect(std::io::Cursor::new(out)).unwrap();
+    assert_eq!(Some(1), summary.databases[1].expires_hint);
+}
+
+fn typed_json(data: &[u8], binary: Binary) -> Vec<u8> {
+    let mut formatter = TypedJSON::with_writer(Vec::new());
+    formatter.set_binary(binary);
+    let mut parser = RdbParser::new(data, formatter, Simple::new());
+    parser.parse().unwrap();
+    parser.into_formatter().into_inner().unwrap()
+}
+
+#[test]
+fn test_import_typed_json_round_trip() {
+    let mut writer = RdbWriter::new(Vec::new(), 9).unwrap();
+    writer.write_header().unwrap();
+    writer.write_select_db(0).unwrap();
+    let value = Value::String(b"\xff\\x41".to_vec());
+    writer
+        .write_object(b"bin\x80\\", &value, Some(1700000000000))
+        .unwrap();
+    let value = Value::Hash(vec![(b"f\\".to_vec(), b"\xfe".to_vec())]);
+    writer.write_object(b"hash", &value, None).unwrap();
+    let value = Value::SortedSet(vec![(1.5, b"\x80\\".to_vec())]);
+    writer.write_object(b"zset", &value, None).unwrap();
+    writer.write_eof().unwrap();
+    let data = writer.into_inner();
+
+    for &binary in &[Binary::Escape, Binary::Base64] {
+        let mut imported = Vec::new();
+        rdb::import::json(&typed_json(&data, binary)[..], &mut imported, 9).unwrap();
+        assert_eq!(
+            typed_json(&data, Binary::Base64),
+            typed_json(&imported, Binary::Base64),
+            "{:?}",
+            binary
+        );
+    }
+}
+
+#[test]
+fn test_import_invalid() {
+    assert!(import("{}").is_err());
+    assert!(import(r#"[{"key": "a", "type": "stream", "value": []}]"#).is_err());
+    assert!(import(r#"[{"key": "a", "type": "sortedset", "value": {"a": "x"}}]"#).is_err());
+    assert!(import(r#"[{"nested": [["a"]]}]"#).is_err());
+}
+
+fn replay(input: &[u8]) -> rdb::RdbResult<Vec<String>> {
+    let mut replay = rdb::import::resp::Replay::new();
+    replay.set_time(1_000_000);
+    replay.read(input)?;
+
+    let mut out = Vec::new();
+    replay.into_keyspace().write(&mut out, 9)?;
+    Ok(key_events(&out))
+}
+
+#[test]
+fn test_import_resp_commands() {
+    let events = replay(
+        b"*3\r\n$3\r\nSET\r\n$3\r\nnum\r\n$1\r\n1\r\n\
+          INCRBY num 41\r\n\
+          RPUSH list a b c\r\nLPOP list\r\n\
+          SADD set x y\r\nSREM set x y\r\n\
+          ZADD zset 2 b 1 a\r\nZADD zset XX 5 c\r\n\
+          HSET hash f 1\r\nEXPIRE hash 10\r\n\
+          SET gone 1\r\nDEL gone\r\n\
+          SET expired 1 PXAT 999999\r\nSET expires 1 PXAT 1000000\r\n\
+          SELECT 3\r\nSET other 1 PX 500\r\n\
+          SELECT 4\r\nSET flushed 1\r\nFLUSHDB\r\n\
+          *3\r\n$3\r\nSET\r\n$10\r\nincomp",
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec![
+            "start_rdb",
+            "start_database 0",
+            "set expires 1 Some(1000000)",
+            "start_hash hash Some(1010000)",
+            "hash_element hash f 1",
+            "end_hash hash",
+            "start_list list None",
+            "list_element list b",
+            "list_element list c",
+            "end_list list",
+            "set num 42 None",
+            "start_sorted_set zset None",
+            "sorted_set_element zset 1 a",
+            "sorted_set_element zset 2 b",
+            "end_sorted_set zset",
+            "start_database 3",
+            "set other 1 Some(1000500)",
+            "end_database 3",
+            "end_rdb",
+        ],
+        events
+    );
+
+    assert!(replay(b"SET a 1\r\nRPUSH a b\r\n").is_err());
+    assert!(replay(b"EVAL script 0\r\n").is_err());
+    assert!(replay(b"DECRBY num -9223372036854775808\r\n").is_err());
+}
+
+#[test]
+fn test_import_resp_expiries() {
+    let events = replay(
+        b"SET a 1\r\nEXPIRE a -1\r\n\
+          SET b 1\r\nPEXPIREAT b 1000000\r\n\
+          SET c 1\r\nEXPIREAT c 1001\r\n\
+          SET d 1\r\nEXPIRE d 100 NX\r\nEXPIRE d -1 NX\r\n\
+          SETEX e 2 1\r\nSET f 1 EXAT 1002\r\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec![
+            "start_rdb",
+            "start_database 0",
+            "set c 1 Some(1001000)",
+            "set d 1 Some(1100000)",
+            "set e 1 Some(1002000)",
+            "set f 1 Some(1002000)",
+            "end_database 0",
+            "end_rdb",
+        ],
+        events
+    );
+
+    assert!(replay(b"SET a 1\r\nEXPIRE a 9223372036854775807\r\n").is_err());
+    assert!(replay(b"SET a 1\r\nPEXPIRE a 9223372036854775807\r\n").is_err());
+    assert!(replay(b"SET a 1 EXAT 9223372036854775807\r\n").is_err());
+    assert!(replay(b"SET a 1 EX 0\r\n").is_err());
+    assert!(replay(b"SETEX a -1 1\r\n").is_err());
+}
+
+#[test]
+fn test_import_resp_moving_and_removing() {
+    let events = replay(
+        b"RPUSH list a b c b a\r\nLSET list -1 z\r\nLREM list -1 b\r\n\
+          LINSERT list AFTER a y\r\nLMOVE list other LEFT RIGHT\r\nRPOPLPUSH list other\r\n\
+          SADD set x y\r\nSMOVE set moved x\r\nSMOVE set moved missing\r\n\
+          ZADD zset 1 a 2 b 3 c 4 d 5 e 6 f\r\nZPOPMIN zset\r\nZPOPMAX zset 2\r\n\
+          ZREMRANGEBYSCORE zset (2 3\r\nZREMRANGEBYRANK zset -1 -1\r\n\
+          SET s 1\r\nCOPY s copied\r\nCOPY s s2 DB 1\r\nMOVE copied 2\r\n\
+          SELECT 2\r\nSET swapped 1\r\nSWAPDB 2 3\r\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec![
+            "start_rdb",
+            "start_database 0",
+            "start_list list None",
+            "list_element list y",
+            "list_element list b",
+            "list_element list c",
+            "end_list list",
+            "start_set moved None",
+            "set_element moved x",
+            "end_set moved",
+            "start_list other None",
+            "list_element other z",
+            "list_element other a",
+            "end_list other",
+            "set s 1 None",
+            "start_set set None",
+            "set_element set y",
+            "end_set set",
+            "start_sorted_set zset None",
+            "sorted_set_element zset 2 b",
+            "end_sorted_set zset",
+            "start_database 1",
+            "set s2 1 None",
+            "start_database 3",
+            "set copied 1 None",
+            "set swapped 1 None",
+            "end_database 3",
+            "end_rdb",
+        ],
+        events
+    );
+
+    assert!(replay(b"LSET missing 0 a\r\n").is_err());
+    assert!(replay(b"RPUSH list a\r\nLSET list 1 b\r\n").is_err());
+}
+
+#[test]
+fn test_import_aof_with_preamble() {
+    let mut aof = Vec::new();
+    rdb::import::json(
+        &br#"[{"key": "from_rdb", "type": "list", "value": ["a"]}]"#[..],
+        &mut aof,
+        9,
+    )
+    .unwrap();
+    aof.extend_from_slice(b"*3\r\n$5\r\nRPUSH\r\n$8\r\nfrom_rdb\r\n$1\r\nb\r\n");
+
+    assert_eq!(
+        vec![
+            "start_rdb",
+            "start_database 0",
+            "start_list from_rdb None",
+            "list_element from_rdb a",
+            "list_element from_rdb b",
+            "end_list from_rdb",
+            "end_database 0",
+            "end_rdb",
+        ],
+        replay(&aof).unwrap()
+    );
+}
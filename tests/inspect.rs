@@ -0,0 +1,66 @@
+mod common;
+
+use common::read_dump;
+use rdb::inspect::DatabaseSummary;
+use std::io::Cursor;
+
+#[test]
+fn test_inspect_aux_and_databases() {
+    let summary =
+        rdb::inspect(Cursor::new(read_dump("quicklist_with_multiple_nodes.rdb"))).unwrap();
+    assert_eq!(7, summary.version);
+    assert_eq!(Some("2.9.999"), summary.aux.redis_ver.as_deref());
+    assert_eq!(Some(1420740379), summary.aux.ctime);
+    assert_eq!(1, summary.databases.len());
+    assert_eq!(1, summary.databases[0].keys);
+
+    let summary = rdb::inspect(Cursor::new(read_dump("multiple_databases.rdb"))).unwrap();
+    let indexes: Vec<_> = summary.databases.iter().map(|db| db.index).collect();
+    assert_eq!(vec![0, 2], indexes);
+
+    let summary = rdb::inspect(Cursor::new(read_dump("keys_with_expiry.rdb"))).unwrap();
+    assert_eq!(1, summary.databases[0].expires);
+}
+
+#[test]
+fn test_inspect_skips_all_encodings() {
+    for name in common::dump_names() {
+        let data = read_dump(&name);
+        let summary = rdb::inspect(Cursor::new(&data[..])).unwrap();
+
+        let recorder = common::Recorder::new();
+        rdb::parse(&data[..], recorder.clone(), rdb::filter::Simple::new()).unwrap();
+        let keys = recorder
+            .events()
+            .iter()
+            .filter(|e| e.starts_with("set ") || e.starts_with("start_"))
+            .filter(|e| !e.starts_with("start_rdb") && !e.starts_with("start_database"))
+            .count() as u64;
+
+        assert_eq!(
+            keys,
+            summary.databases.iter().map(|db| db.keys).sum::<u64>(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_inspect_resizedb_hints() {
+    let data = b"REDIS0008\xfa\x09redis-ver\x055.0.0\xfe\x00\xfb\x02\x01\
+                 \xfc\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01a\x011\x00\x01b\x012\xff";
+    let summary = rdb::inspect(Cursor::new(&data[..])).unwrap();
+
+    assert_eq!(Some("5.0.0"), summary.aux.redis_ver.as_deref());
+    assert_eq!(
+        vec![DatabaseSummary {
+            index: 0,
+            size_hint: Some(2),
+            expires_hint: Some(1),
+            keys: 2,
+            expires: 1,
+        }],
+        summary.databases
+    );
+}
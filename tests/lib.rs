@@ -1,3 +1,5 @@
+#![allow(clippy::assertions_on_constants)]
+
 extern crate rdb;
 use rdb::parser::{
     read_blob, read_length, read_length_with_encoding, verify_magic, verify_version,
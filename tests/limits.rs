@@ -0,0 +1,130 @@
+mod common;
+
+use common::read_dump;
+use rdb::filter::Simple;
+use rdb::formatter::Nil;
+use rdb::{Limit, RdbError, RdbParser, RdbParserBuilder};
+
+fn parse(builder: RdbParserBuilder, name: &str) -> rdb::RdbOk {
+    let data = read_dump(name);
+    builder.build(&data[..], Nil::new(), Simple::new()).parse()
+}
+
+fn exceeded(result: rdb::RdbOk) -> Limit {
+    match result {
+        Err(RdbError::LimitExceeded { limit, .. }) => limit,
+        other => panic!("Expected a limit violation, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_no_limits_by_default() {
+    parse(RdbParser::builder(), "dictionary.rdb").unwrap();
+    parse(RdbParser::builder(), "easily_compressible_string_key.rdb").unwrap();
+}
+
+#[test]
+fn test_string_size() {
+    let builder = RdbParser::builder().max_string_size(100);
+    assert_eq!(
+        Limit::StringSize,
+        exceeded(parse(builder, "uncompressible_string_keys.rdb"))
+    );
+}
+
+#[test]
+fn test_decompressed_size() {
+    let builder = RdbParser::builder().max_decompressed_size(100);
+    assert_eq!(
+        Limit::DecompressedSize,
+        exceeded(parse(builder, "easily_compressible_string_key.rdb"))
+    );
+}
+
+#[test]
+fn test_collection_length() {
+    let builder = RdbParser::builder().max_collection_length(999);
+    assert_eq!(
+        Limit::CollectionLength,
+        exceeded(parse(builder, "dictionary.rdb"))
+    );
+
+    let builder = RdbParser::builder().max_collection_length(1000);
+    parse(builder, "dictionary.rdb").unwrap();
+}
+
+#[test]
+fn test_keys() {
+    let builder = RdbParser::builder().max_keys(1);
+    assert_eq!(
+        Limit::Keys,
+        exceeded(parse(builder, "multiple_databases.rdb"))
+    );
+}
+
+#[test]
+fn test_versions() {
+    let builder = RdbParser::builder().versions(6, 8);
+    match parse(builder, "multiple_databases.rdb") {
+        Err(RdbError::UnsupportedVersion { version, min, max }) => {
+            assert_eq!((3, 6, 8), (version, min, max))
+        }
+        other => panic!("Expected an unsupported version, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_corrupted_length_does_not_allocate() {
+    let data = b"REDIS0003\xfe\x00\x00\x03key\x80\xff\xff\xff\xff";
+    let result = RdbParser::builder()
+        .max_string_size(1024)
+        .build(&data[..], Nil::new(), Simple::new())
+        .parse();
+    assert_eq!(Limit::StringSize, exceeded(result));
+
+    let mut parser = RdbParser::builder()
+        .max_string_size(1024)
+        .build_push(Nil::new(), Simple::new());
+    assert_eq!(Limit::StringSize, exceeded(parser.feed(data)));
+}
+
+#[test]
+fn test_corrupted_length_without_limits() {
+    let data = b"REDIS0003\xfe\x00\x00\x03key\x80\xff\xff\xff\xff\xff";
+    let result = RdbParser::builder()
+        .build(&data[..], Nil::new(), Simple::new())
+        .parse();
+    assert!(result.is_err());
+
+    // 2 compressed bytes claiming 4 GB
+    let data = b"REDIS0003\xfe\x00\x00\x03key\xc3\x02\x80\xff\xff\xff\xff\x00a\xff";
+    let result = RdbParser::builder()
+        .build(&data[..], Nil::new(), Simple::new())
+        .parse();
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_quicklist_counts_elements() {
+    let builder = RdbParser::builder().max_collection_length(2);
+    assert_eq!(
+        Limit::CollectionLength,
+        exceeded(parse(builder, "quicklist_with_one_node.rdb"))
+    );
+}
+
+#[test]
+fn test_unknown_type() {
+    let data = b"REDIS0003\xfe\x00\x42\x03key\x05value\xff";
+    let result = RdbParser::builder()
+        .build(&data[..], Nil::new(), Simple::new())
+        .parse();
+    assert!(result.is_err());
+
+    let mut filter = Simple::new();
+    filter.add_type(rdb::Type::String);
+    let result = RdbParser::builder()
+        .build(&data[..], Nil::new(), filter)
+        .parse();
+    assert!(result.is_err());
+}
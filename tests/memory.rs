@@ -0,0 +1,45 @@
+use rdb::memory::{Allocator, Model};
+use rdb::types::EncodingType;
+use rdb::Type;
+
+#[test]
+fn test_allocations() {
+    let mut model = Model::new();
+    assert_eq!(8, model.allocation(5));
+    assert_eq!(32, model.allocation(24));
+    assert_eq!(160, model.allocation(129));
+    assert_eq!(10240, model.allocation(8193));
+
+    model.set_allocator(Allocator::Exact);
+    assert_eq!(24, model.allocation(24));
+}
+
+#[test]
+fn test_redis_versions() {
+    let mut model = Model::new();
+    // Shared integer, embedded string and SDS string with a 8 bit header
+    assert_eq!(0, model.string(b"42"));
+    assert_eq!(32, model.string(b"value"));
+    assert_eq!(16 + 64, model.string(&[b'a'; 45]));
+
+    // Before 3.2, SDS headers have 8 bytes and strings of up to 39 bytes are embedded
+    model.set_redis_version("3.0.7").unwrap();
+    assert_eq!(16 + 64, model.string(&[b'a'; 40]));
+    assert_eq!(16, model.sds(4));
+    assert!(model.set_redis_version("unstable").is_err());
+}
+
+#[test]
+fn test_collections() {
+    let model = Model::new();
+    let mut set = model.collection(Type::Set, EncodingType::Hashtable);
+    set.add(&[b"member"]);
+    set.add(&[b"other"]);
+    // Object, dictionary, 4 buckets and entries without values
+    assert_eq!(16 + 64 + 32 + 2 * (16 + 8), set.size());
+
+    let mut hash = model.collection(Type::Hash, EncodingType::Listpack(30));
+    hash.add(&[b"field", b"value"]);
+    assert_eq!(16 + 32, hash.size());
+    assert_eq!(1, hash.len());
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use common::{read_dump, Recorder};
+use rdb::filter::Simple;
+use rdb::merge::{Conflict, Merge};
+use rdb::{RdbError, RdbWriter, Value};
+use std::io::Cursor;
+
+/// A RDB file written at `ctime` with the given strings in database `db`
+fn snapshot(ctime: &[u8], db: u32, strings: &[(&str, &str, Option<u64>)]) -> Vec<u8> {
+    let mut writer = RdbWriter::new(Vec::new(), 9).unwrap();
+    writer.write_header().unwrap();
+    writer.write_aux(b"ctime", ctime).unwrap();
+    writer.write_select_db(db).unwrap();
+    writer.write_resizedb(100, 100).unwrap();
+    for (key, value, expiry) in strings {
+        writer
+            .write_object(
+                key.as_bytes(),
+                &Value::String(value.as_bytes().to_vec()),
+                *expiry,
+            )
+            .unwrap();
+    }
+    writer.write_eof().unwrap();
+    writer.into_inner()
+}
+
+fn merge(merge: &Merge, inputs: &[Vec<u8>]) -> Result<Vec<u8>, RdbError> {
+    let mut out = Vec::new();
+    merge.merge(inputs.iter().map(Cursor::new).collect(), &mut out)?;
+    Ok(out)
+}
+
+fn string_events(data: &[u8]) -> Vec<String> {
+    let recorder = Recorder::new();
+    rdb::parse(data, recorder.clone(), Simple::new()).unwrap();
+    recorder
+        .events()
+        .into_iter()
+        .filter(|event| event.starts_with("set ") || event.starts_with("start_database"))
+        .collect()
+}
+
+#[test]
+fn test_conflicts() {
+    let inputs = [
+        snapshot(b"1700000000", 0, &[("a", "new", None), ("b", "1", None)]),
+        snapshot(
+            b"1600000000",
+            0,
+            &[("a", "old", Some(1_000)), ("c", "2", None)],
+        ),
+    ];
+    let expected = |a: &str| {
+        let mut events = ["start_database 0", a, "set b 1 None", "set c 2 None"];
+        events.sort_unstable();
+        events
+            .iter()
+            .map(|event| event.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    for (conflict, a) in &[
+        (Conflict::FirstWins, "set a new None"),
+        (Conflict::LastWins, "set a old Some(1000)"),
+        (Conflict::NewestByCtime, "set a new None"),
+    ] {
+        let mut policy = Merge::new();
+        policy.set_conflict(*conflict);
+
+        let mut events = string_events(&merge(&policy, &inputs).unwrap());
+        events.sort_unstable();
+        assert_eq!(expected(a), events, "{:?}", conflict);
+    }
+
+    let mut policy = Merge::new();
+    policy.set_conflict(Conflict::Error);
+    match merge(&policy, &inputs) {
+        Err(RdbError::DuplicateKey { database: 0, key }) => assert_eq!(b"a".to_vec(), key),
+        result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_database_mapping_and_hints() {
+    // Keys in database 0 and 2, with a key of database 2 that also exists in database 0
+    let inputs = [
+        read_dump("multiple_databases.rdb"),
+        snapshot(
+            b"1700000000",
+            2,
+            &[
+                ("key_in_zeroth_database", "dup", Some(1_000)),
+                ("x", "y", None),
+            ],
+        ),
+    ];
+
+    let summary = rdb::inspect(Cursor::new(merge(&Merge::new(), &inputs).unwrap())).unwrap();
+    let hints: Vec<_> = summary
+        .databases
+        .iter()
+        .map(|db| (db.index, db.size_hint, db.keys, db.expires_hint, db.expires))
+        .collect();
+    assert_eq!(
+        vec![(0, Some(1), 1, Some(0), 0), (2, Some(3), 3, Some(1), 1)],
+        hints
+    );
+
+    let mut mapped = Merge::new();
+    mapped.map_database(2, 0);
+    let merged = merge(&mapped, &inputs).unwrap();
+    let summary = rdb::inspect(Cursor::new(&merged)).unwrap();
+    assert_eq!(1, summary.databases.len());
+    assert_eq!(Some(3), summary.databases[0].size_hint);
+    assert_eq!(3, summary.databases[0].keys);
+    assert!(string_events(&merged).contains(&"set key_in_zeroth_database zero None".to_string()));
+}
+
+#[test]
+fn test_duplicates_within_one_input() {
+    // Database 1 is folded into database 0, which already holds the key
+    let mut writer = RdbWriter::new(Vec::new(), 9).unwrap();
+    writer.write_header().unwrap();
+    writer.write_select_db(0).unwrap();
+    let value = Value::String(b"first".to_vec());
+    writer.write_object(b"a", &value, None).unwrap();
+    writer.write_select_db(1).unwrap();
+    let value = Value::String(b"second".to_vec());
+    writer.write_object(b"a", &value, Some(1_000)).unwrap();
+    writer.write_eof().unwrap();
+    let inputs = [writer.into_inner()];
+
+    for (conflict, a, expires) in &[
+        (Conflict::FirstWins, "set a first None", 0),
+        (Conflict::LastWins, "set a second Some(1000)", 1),
+        (Conflict::NewestByCtime, "set a first None", 0),
+    ] {
+        let mut policy = Merge::new();
+        policy.set_conflict(*conflict);
+        policy.map_database(1, 0);
+        let merged = merge(&policy, &inputs).unwrap();
+
+        assert_eq!(
+            vec!["start_database 0", a],
+            string_events(&merged),
+            "{:?}",
+            conflict
+        );
+        let summary = rdb::inspect(Cursor::new(&merged)).unwrap();
+        assert_eq!(Some(1), summary.databases[0].size_hint);
+        assert_eq!(Some(*expires), summary.databases[0].expires_hint);
+    }
+
+    let mut policy = Merge::new();
+    policy.set_conflict(Conflict::Error);
+    policy.map_database(1, 0);
+    match merge(&policy, &inputs) {
+        Err(RdbError::DuplicateKey { database: 0, key }) => assert_eq!(b"a".to_vec(), key),
+        result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+    }
+}
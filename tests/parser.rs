@@ -0,0 +1,69 @@
+mod common;
+
+use common::{rdb_with_eviction_info, read_dump, Recorder};
+use rdb::filter::Simple;
+
+fn events(name: &str) -> Vec<String> {
+    let recorder = Recorder::new();
+    rdb::parse(&read_dump(name)[..], recorder.clone(), Simple::new()).unwrap();
+    recorder.events()
+}
+
+#[test]
+fn test_linked_list_set_elements() {
+    let events = events("regular_set.rdb");
+    assert!(events.contains(&"start_set regular_set 6 None".to_string()));
+    assert!(events.contains(&"set_element regular_set alpha".to_string()));
+    assert!(!events.iter().any(|event| event.starts_with("list_element")));
+}
+
+#[test]
+fn test_quicklist_is_a_list() {
+    let events = events("quicklist_with_one_node.rdb");
+    assert!(events.iter().any(|event| event.starts_with("start_list ")));
+    assert!(events.iter().any(|event| event.starts_with("end_list ")));
+    assert!(!events
+        .iter()
+        .any(|event| event.starts_with("start_set") || event.starts_with("end_set")));
+}
+
+#[test]
+fn test_expiry_in_seconds() {
+    // Redis writes expiries in seconds as 32 bit little endian integers
+    let events = events("expiry_in_seconds.rdb");
+    assert!(events.contains(
+        &"set expires_s_precision 2022-12-25 10:11:12 UTC Some(1671963072000)".to_string()
+    ));
+}
+
+#[test]
+fn test_length_in_64_bits() {
+    let events = events("length_in_64_bits.rdb");
+    assert!(events.contains(&"set length_in_64_bits value None".to_string()));
+}
+
+#[test]
+fn test_eviction_info() {
+    let recorder = Recorder::new();
+    rdb::parse(
+        &rdb_with_eviction_info()[..],
+        recorder.clone(),
+        Simple::new(),
+    )
+    .unwrap();
+    let events: Vec<String> = recorder
+        .events()
+        .into_iter()
+        .filter(|event| event.starts_with("eviction_info") || event.starts_with("set"))
+        .collect();
+    assert_eq!(
+        vec![
+            "eviction_info a Some(300) None",
+            "set a x None",
+            "eviction_info b None Some(5)",
+            "set b y None",
+            "set c z None",
+        ],
+        events
+    );
+}
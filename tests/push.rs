@@ -0,0 +1,61 @@
+mod common;
+
+use common::{dump_names, read_dump, Recorder};
+use rdb::filter::Simple;
+use rdb::PushParser;
+
+fn parse_blocking(data: &[u8]) -> Vec<String> {
+    let recorder = Recorder::new();
+    rdb::parse(data, recorder.clone(), Simple::new()).unwrap();
+    recorder.events()
+}
+
+fn parse_chunked(data: &[u8], chunk_size: usize) -> Vec<String> {
+    let recorder = Recorder::new();
+    let mut parser = PushParser::new(recorder.clone(), Simple::new());
+    for chunk in data.chunks(chunk_size) {
+        parser.feed(chunk).unwrap();
+    }
+    parser.finish().unwrap();
+    assert!(parser.is_done());
+    recorder.events()
+}
+
+#[test]
+fn test_chunked_matches_blocking() {
+    for name in dump_names() {
+        let data = read_dump(&name);
+        let expected = parse_blocking(&data);
+
+        for &chunk_size in &[1, 7, 4096] {
+            assert_eq!(expected, parse_chunked(&data, chunk_size), "{}", name);
+        }
+    }
+}
+
+#[test]
+fn test_events_emitted_before_end_of_input() {
+    let data = read_dump("multiple_databases.rdb");
+    let recorder = Recorder::new();
+    let mut parser = PushParser::new(recorder.clone(), Simple::new());
+
+    parser.feed(&data[..data.len() - 4]).unwrap();
+    let events = recorder.events();
+    assert_eq!("set key_in_zeroth_database zero None", events[2]);
+    assert_eq!("start_database 2", events[3]);
+    assert_eq!(4, events.len());
+
+    parser.feed(&data[data.len() - 4..]).unwrap();
+    parser.finish().unwrap();
+    assert!(recorder.events().contains(&"end_rdb".to_string()));
+}
+
+#[test]
+fn test_finish_on_truncated_input() {
+    let data = read_dump("ziplist_that_compresses_easily.rdb");
+    let mut parser = PushParser::new(Recorder::new(), Simple::new());
+
+    parser.feed(&data[..data.len() / 2]).unwrap();
+    assert!(!parser.is_done());
+    assert!(parser.finish().is_err());
+}
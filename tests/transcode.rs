@@ -0,0 +1,140 @@
+mod common;
+
+use common::{
+    dump_names, encodings_of, key_events, rdb_with_stream, read_dump, stream, ziplist_headers,
+    Recorder,
+};
+use rdb::filter::Simple;
+use rdb::{PushParser, RdbError};
+use std::io::Cursor;
+
+fn transcode(data: &[u8], version: u32) -> Result<Vec<u8>, RdbError> {
+    let mut out = Vec::new();
+    rdb::transcode(data, &mut out, version)?;
+    Ok(out)
+}
+
+#[test]
+fn test_transcode_dumps() {
+    for name in dump_names() {
+        let data = read_dump(&name);
+        let expected = key_events(&data);
+
+        for &version in &[3, 6, 9, 10, 11] {
+            let transcoded = transcode(&data, version).unwrap();
+            assert_eq!(expected, key_events(&transcoded), "{} v{}", name, version);
+
+            // And back down from the newest version
+            let downgraded = transcode(&transcode(&transcoded, 11).unwrap(), 6).unwrap();
+            assert_eq!(expected, key_events(&downgraded), "{} v{}", name, version);
+        }
+    }
+}
+
+#[test]
+fn test_listpacks() {
+    let data = read_dump("parser_filters.rdb");
+    let ziplists = encodings_of(&transcode(&data, 9).unwrap());
+    let listpacks = encodings_of(&transcode(&data, 11).unwrap());
+
+    assert!(ziplists
+        .iter()
+        .any(|encoding| encoding.ends_with("Ziplist")));
+    assert!(!ziplists
+        .iter()
+        .any(|encoding| encoding.ends_with("Listpack")));
+    assert!(listpacks
+        .iter()
+        .any(|encoding| encoding.ends_with("Listpack")));
+    assert!(!listpacks
+        .iter()
+        .any(|encoding| encoding.ends_with("Ziplist")));
+
+    // Small sets of strings are only stored as listpacks since version 11
+    let data = read_dump("regular_set.rdb");
+    assert_eq!(
+        vec!["regular_set LinkedList"],
+        encodings_of(&transcode(&data, 10).unwrap())
+    );
+    assert_eq!(
+        vec!["regular_set Listpack"],
+        encodings_of(&transcode(&data, 11).unwrap())
+    );
+}
+
+#[test]
+fn test_ziplist_integers_before_version_6() {
+    let data = read_dump("ziplist_with_integers.rdb");
+    let key = b"ziplist_with_integers";
+
+    for &version in &[3, 5] {
+        let transcoded = transcode(&data, version).unwrap();
+        // Header, database selector, type and key, then the length of the ziplist in 14 bits
+        let start = 9 + 2 + 1 + 1 + key.len();
+        assert_eq!(10, transcoded[start - key.len() - 2]);
+        assert_eq!(0x40, transcoded[start] & 0xc0);
+
+        // 24 bit and 8 bit integers and the immediates are 0xf0 to 0xfe
+        let headers = ziplist_headers(&transcoded[start + 2..]);
+        assert_eq!(24, headers.len());
+        assert!(
+            headers
+                .iter()
+                .all(|&header| !(0xf0..=0xfe).contains(&header)),
+            "v{}: {:x?}",
+            version,
+            headers
+        );
+    }
+}
+
+#[test]
+fn test_stream_is_copied() {
+    let data = rdb_with_stream();
+    let expected = format!("raw_object stream 15 {:?} Some(1000)", stream());
+
+    let transcoded = transcode(&data, 11).unwrap();
+    let events = key_events(&transcoded);
+    assert!(events.contains(&expected));
+    assert!(events.contains(&"set key value None".to_string()));
+
+    // Push parsing and inspection can skip streams as well
+    let recorder = Recorder::new();
+    let mut parser = PushParser::new(recorder.clone(), Simple::new());
+    for chunk in transcoded.chunks(5) {
+        parser.feed(chunk).unwrap();
+    }
+    parser.finish().unwrap();
+    assert!(recorder.events().contains(&expected));
+
+    let summary = rdb::inspect(Cursor::new(&transcoded)).unwrap();
+    assert_eq!(2, summary.databases[0].keys);
+    assert_eq!(1, summary.databases[0].expires);
+}
+
+#[test]
+fn test_stream_needs_version_9() {
+    match transcode(&rdb_with_stream(), 8) {
+        Err(RdbError::UnsupportedByVersion {
+            version: 8,
+            required: 9,
+            ..
+        }) => {}
+        result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_functions() {
+    let mut data = b"REDIS0010\xf5".to_vec();
+    data.extend_from_slice(b"\x11#!lua name=mylib\n\xff");
+    data.extend_from_slice(&[0; 8]);
+
+    let events = key_events(&transcode(&data, 11).unwrap());
+    assert!(events.contains(&"function #!lua name=mylib\n".to_string()));
+
+    match transcode(&data, 9) {
+        Err(RdbError::UnsupportedByVersion { required: 10, .. }) => {}
+        result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+    }
+}
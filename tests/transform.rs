@@ -0,0 +1,314 @@
+mod common;
+
+use common::{encodings_of, key_events, read_dump, Recorder};
+use rdb::filter::Simple;
+use rdb::formatter::Rdb;
+use rdb::transform::expiry::{Adjust, Reference};
+use rdb::transform::{Anonymize, Expiry, Rename, Transformed};
+use regex::bytes::Regex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn events_with<T: rdb::transform::Transform>(data: &[u8], transform: T) -> Vec<String> {
+    let recorder = Recorder::new();
+    rdb::parse(
+        data,
+        Transformed::new(recorder.clone(), transform),
+        Simple::new(),
+    )
+    .unwrap();
+    recorder.events()
+}
+
+#[test]
+fn test_rename_rules() {
+    let mut rename = Rename::new();
+    rename.strip_prefix(b"prod:");
+    rename.replace(Regex::new(r"^user:(\d+)").unwrap(), b"u:$1");
+    rename.hash_tag(Regex::new(r"^u:\d+").unwrap());
+    rename.add_prefix(b"stg:");
+
+    assert_eq!(
+        b"stg:{u:42}:name".to_vec(),
+        rename.apply(b"prod:user:42:name")
+    );
+    assert_eq!(b"stg:session:1".to_vec(), rename.apply(b"session:1"));
+    // Keys with a hash tag keep it
+    assert_eq!(b"stg:{u:7}".to_vec(), rename.apply(b"prod:{u:7}"));
+}
+
+#[test]
+fn test_rename_all_callbacks() {
+    let data = read_dump("parser_filters.rdb");
+    let recorder = Recorder::new();
+    rdb::parse(&data[..], recorder.clone(), Simple::new()).unwrap();
+
+    // Without rules, everything is passed on unchanged
+    assert_eq!(recorder.events(), events_with(&data, Rename::new()));
+
+    let expected: Vec<String> = recorder
+        .events()
+        .into_iter()
+        .map(|event| {
+            let is_key_event = !["aux", "resizedb", "checksum", "start_rdb", "end_rdb"]
+                .iter()
+                .any(|prefix| event.starts_with(prefix))
+                && !event.contains("_database");
+            if is_key_event {
+                event.replacen(' ', " new:", 1)
+            } else {
+                event
+            }
+        })
+        .collect();
+
+    let mut rename = Rename::new();
+    rename.add_prefix(b"new:");
+    assert_eq!(expected, events_with(&data, rename));
+}
+
+fn rdb_with_keys(keys: &[&str]) -> Vec<u8> {
+    let mut writer = rdb::RdbWriter::new(Vec::new(), 9).unwrap();
+    writer.write_header().unwrap();
+    for key in keys {
+        writer
+            .write_object(key.as_bytes(), &rdb::Value::String(b"x".to_vec()), None)
+            .unwrap();
+    }
+    writer.write_eof().unwrap();
+    writer.into_inner()
+}
+
+#[test]
+fn test_rename_keeps_hash_tags_together() {
+    let data = rdb_with_keys(&["{l}1", "{l}2"]);
+    let parse = |rename: Rename| {
+        rdb::parse(
+            &data[..],
+            Transformed::new(Recorder::new(), rename),
+            Simple::new(),
+        )
+    };
+
+    let mut rename = Rename::new();
+    rename.replace(Regex::new(r"^\{l\}").unwrap(), b"{m}");
+    assert!(parse(rename).is_ok());
+
+    // Only one of the keys would get the new hash tag
+    let mut rename = Rename::new();
+    rename.replace(Regex::new(r"^\{l\}1").unwrap(), b"{m}1");
+    assert!(parse(rename).is_err());
+}
+
+#[test]
+fn test_rename_rdb_output() {
+    let data = read_dump("multiple_databases.rdb");
+    let mut rename = Rename::new();
+    rename.replace(Regex::new("^key_in_").unwrap(), b"");
+
+    let mut out = Vec::new();
+    rdb::parse(
+        &data[..],
+        Transformed::new(Rdb::new(&mut out), rename),
+        Simple::new(),
+    )
+    .unwrap();
+
+    let events = key_events(&out);
+    assert!(events.contains(&"set zeroth_database zero None".to_string()));
+    assert!(events.contains(&"set second_database second None".to_string()));
+}
+
+/// A file written at second 1000, with keys expiring at 900s, 1500s and never
+fn rdb_with_expiries() -> Vec<u8> {
+    let mut writer = rdb::RdbWriter::new(Vec::new(), 9).unwrap();
+    writer.write_header().unwrap();
+    writer.write_aux(b"ctime", b"1000").unwrap();
+    writer.write_select_db(0).unwrap();
+    for (key, expiry) in [
+        ("old", Some(900_000)),
+        ("new", Some(1_500_000)),
+        ("kept", None),
+    ] {
+        writer
+            .write_object(key.as_bytes(), &rdb::Value::String(b"x".to_vec()), expiry)
+            .unwrap();
+    }
+    writer.write_eof().unwrap();
+    writer.into_inner()
+}
+
+fn sets(events: Vec<String>) -> Vec<String> {
+    events
+        .into_iter()
+        .filter(|event| event.starts_with("set "))
+        .collect()
+}
+
+#[test]
+fn test_expiry_drop_and_ttl() {
+    let data = rdb_with_expiries();
+
+    let mut expiry = Expiry::new(Reference::Ctime);
+    expiry.set_drop_expired(true);
+    assert_eq!(
+        vec!["set new x Some(1500000)", "set kept x None"],
+        sets(events_with(&data, expiry))
+    );
+
+    let mut expiry = Expiry::new(Reference::At(1_200_000));
+    expiry.set_drop_expired(true);
+    expiry.set_adjust(Adjust::Ttl);
+    assert_eq!(
+        vec!["set new x Some(300000)", "set kept x None"],
+        sets(events_with(&data, expiry))
+    );
+
+    // Everything expired by now
+    let mut expiry = Expiry::new(Reference::Now);
+    expiry.set_drop_expired(true);
+    assert_eq!(vec!["set kept x None"], sets(events_with(&data, expiry)));
+}
+
+#[test]
+fn test_expiry_shift_by_snapshot_age() {
+    let data = rdb_with_expiries();
+    let before = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let mut expiry = Expiry::new(Reference::Ctime);
+    expiry.set_adjust(Adjust::Shift);
+
+    // Together with renaming, keys keep the TTL they had when the file was written
+    let mut rename = Rename::new();
+    rename.add_prefix(b"a:");
+    let events = sets(events_with(&data, (expiry, rename)));
+    let expiries: Vec<u64> = events
+        .iter()
+        .filter_map(|event| event.split("Some(").nth(1))
+        .map(|expiry| expiry.trim_end_matches(')').parse().unwrap())
+        .collect();
+    assert_eq!(2, expiries.len());
+    assert!(events[0].starts_with("set a:old"));
+    assert_eq!(600_000, expiries[1] - expiries[0]);
+    assert!(expiries[1] >= before + 500_000);
+}
+
+#[test]
+fn test_expiry_without_ctime() {
+    let data = rdb_with_keys(&["a"]);
+    let result = rdb::parse(
+        &data[..],
+        Transformed::new(Recorder::new(), Expiry::new(Reference::Ctime)),
+        Simple::new(),
+    );
+    assert!(result.is_err());
+}
+
+/// Events with every value replaced by its length
+fn shape_of(events: &[String]) -> Vec<Vec<usize>> {
+    events
+        .iter()
+        .map(|event| event.split(' ').map(str::len).collect())
+        .collect()
+}
+
+#[test]
+fn test_anonymize_keeps_shape() {
+    for name in [
+        "parser_filters.rdb",
+        "intset_64.rdb",
+        "ziplist_with_integers.rdb",
+    ] {
+        let data = read_dump(name);
+        let original = events_with(&data, Rename::new());
+        let anonymized = events_with(&data, Anonymize::new(b"secret"));
+
+        assert_eq!(shape_of(&original), shape_of(&anonymized), "{}", name);
+        assert_ne!(original, anonymized, "{}", name);
+        // Deterministic for the same secret only
+        assert_eq!(anonymized, events_with(&data, Anonymize::new(b"secret")));
+        assert_ne!(anonymized, events_with(&data, Anonymize::new(b"other")));
+
+        // Integers stay integers, so the values are written with the same encodings
+        let write = |anonymize: Option<Anonymize>| {
+            let mut out = Vec::new();
+            rdb::parse(
+                &data[..],
+                Transformed::new(Rdb::new(&mut out), anonymize),
+                Simple::new(),
+            )
+            .unwrap();
+            let encodings = encodings_of(&out);
+            encodings
+                .iter()
+                .map(|encoding| encoding.rsplit(' ').next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            write(None),
+            write(Some(Anonymize::new(b"secret"))),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_anonymize_key_segments() {
+    let data = rdb_with_keys(&["user:1234:name", "user:1235:name", "session:1234"]);
+    let mut anonymize = Anonymize::new(b"secret");
+    anonymize.key_segments(Regex::new(r"^user:(\d+)").unwrap());
+    let keys: Vec<String> = sets(events_with(&data, anonymize))
+        .iter()
+        .map(|event| event.split(' ').nth(1).unwrap().to_string())
+        .collect();
+
+    assert!(keys[0].starts_with("user:") && keys[0].ends_with(":name"));
+    assert_eq!(keys[0].len(), "user:1234:name".len());
+    assert_ne!("user:1234:name", keys[0]);
+    assert_ne!(keys[0], keys[1]);
+    assert_eq!("session:1234", keys[2]);
+}
+
+#[test]
+fn test_anonymize_random_values() {
+    let data = rdb_with_keys(&["a", "b"]);
+    let mut anonymize = Anonymize::new(b"secret");
+    let events = sets(events_with(&data, Anonymize::new(b"secret")));
+    // The same value gets the same replacement
+    assert_eq!(events[0][5..], events[1][5..]);
+
+    anonymize.set_random_values(true);
+    let events = sets(events_with(&data, anonymize));
+    assert_ne!(events[0][5..], events[1][5..]);
+}
+
+#[test]
+fn test_anonymize_keeps_integers_apart_from_other_numbers() {
+    // Numbers close to the limits of 64 bits, on both sides of them
+    let keys: Vec<String> = (0..20u64)
+        .flat_map(|i| {
+            let above = 9_223_372_036_854_775_800 + i * 7;
+            vec![above.to_string(), format!("-{}", above)]
+        })
+        .collect();
+    let data = rdb_with_keys(&keys.iter().map(String::as_str).collect::<Vec<_>>());
+    let mut anonymize = Anonymize::new(b"secret");
+    anonymize.key_segments(Regex::new(".+").unwrap());
+    let anonymized: Vec<String> = sets(events_with(&data, anonymize))
+        .iter()
+        .map(|event| event.split(' ').nth(1).unwrap().to_string())
+        .collect();
+
+    for (key, anonymized) in keys.iter().zip(&anonymized) {
+        assert_eq!(key.len(), anonymized.len());
+        assert_eq!(
+            key.parse::<i64>().is_ok(),
+            anonymized.parse::<i64>().is_ok(),
+            "{} -> {}",
+            key,
+            anonymized
+        );
+    }
+}
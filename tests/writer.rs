@@ -0,0 +1,297 @@
+mod common;
+
+use common::{
+    dump_names, encodings_of, key_events, rdb_with_eviction_info, read_dump, ziplist_headers,
+    Recorder,
+};
+use rdb::filter::Simple;
+use rdb::formatter::Rdb;
+use rdb::{RdbWriter, Value};
+
+fn rewrite(data: &[u8], version: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    rdb::parse(
+        data,
+        Rdb::with_version(&mut out, version).unwrap(),
+        Simple::new(),
+    )
+    .unwrap();
+    out
+}
+
+#[test]
+fn test_round_trip() {
+    for name in dump_names() {
+        let data = read_dump(&name);
+        let expected = key_events(&data);
+
+        for &version in &[3, 5, 7, 8] {
+            let written = rewrite(&data, version);
+            assert_eq!(format!("REDIS{:04}", version).as_bytes(), &written[..9]);
+            assert_eq!(expected, key_events(&written), "{} v{}", name, version);
+        }
+    }
+}
+
+#[test]
+fn test_identical_output_and_checksum() {
+    // This file only contains plain strings, so it is written exactly the same way.
+    let data = read_dump("rdb_version_5_with_checksum.rdb");
+    assert_eq!(data, rewrite(&data, 5));
+}
+
+#[test]
+fn test_lengths_and_special_scores() {
+    let big = vec![b'x'; 20000];
+    let mut writer = RdbWriter::new(Vec::new(), 7).unwrap();
+    writer.write_header().unwrap();
+    writer.write_select_db(300).unwrap();
+    writer
+        .write_object(b"big", &Value::String(big.clone()), Some(1_500_000_000_000))
+        .unwrap();
+    writer
+        .write_object(
+            b"zset",
+            &Value::SortedSet(vec![
+                (f64::INFINITY, b"a".to_vec()),
+                (f64::NEG_INFINITY, b"b".to_vec()),
+                (1e300, b"c".to_vec()),
+                (-0.125, b"d".to_vec()),
+            ]),
+            None,
+        )
+        .unwrap();
+    writer.write_eof().unwrap();
+    let written = writer.into_inner();
+
+    let recorder = Recorder::new();
+    rdb::parse(&written[..], recorder.clone(), Simple::new()).unwrap();
+    let events = recorder.events();
+    assert!(events.contains(&"start_database 300".to_string()));
+    assert!(events.contains(&format!(
+        "set big {} Some(1500000000000)",
+        String::from_utf8(big).unwrap()
+    )));
+    assert!(events.contains(&"sorted_set_element zset inf a".to_string()));
+    assert!(events.contains(&"sorted_set_element zset -inf b".to_string()));
+    assert!(events.contains(&format!("sorted_set_element zset {} c", 1e300)));
+    assert!(events.contains(&"sorted_set_element zset -0.125 d".to_string()));
+}
+
+#[test]
+fn test_unsupported_version() {
+    assert!(RdbWriter::new(Vec::new(), 0).is_err());
+    assert!(Rdb::with_version(Vec::new(), 99).is_err());
+}
+
+#[test]
+fn test_expiry_in_seconds_before_version_3() {
+    let mut writer = RdbWriter::new(Vec::new(), 2).unwrap();
+    writer.write_header().unwrap();
+    writer.write_select_db(0).unwrap();
+    writer
+        .write_object(
+            b"key",
+            &Value::String(b"value".to_vec()),
+            Some(1_671_963_072_573),
+        )
+        .unwrap();
+    writer.write_eof().unwrap();
+    let written = writer.into_inner();
+
+    let recorder = Recorder::new();
+    rdb::parse(&written[..], recorder.clone(), Simple::new()).unwrap();
+    assert!(recorder
+        .events()
+        .contains(&"set key value Some(1671963072000)".to_string()));
+}
+
+fn write_values(version: u32, encodings: rdb::Encodings) -> Vec<u8> {
+    let strings = |values: &[&str]| values.iter().map(|v| v.as_bytes().to_vec()).collect();
+    let long = "x".repeat(100);
+
+    let mut writer = RdbWriter::with_encodings(Vec::new(), version, encodings).unwrap();
+    writer.write_header().unwrap();
+    writer.write_select_db(0).unwrap();
+    let values = vec![
+        ("list", Value::List(strings(&["a", "1", "-300", "70000"]))),
+        ("longlist", Value::List(vec![long.as_bytes().to_vec(); 200])),
+        (
+            "intset",
+            Value::Set(strings(&["3", "1", "-2", "5000000000"])),
+        ),
+        ("set", Value::Set(strings(&["1", "a"]))),
+        (
+            "zset",
+            Value::SortedSet(vec![(2.5, b"b".to_vec()), (-1.0, b"a".to_vec())]),
+        ),
+        (
+            "hash",
+            Value::Hash(vec![(b"field".to_vec(), b"value".to_vec())]),
+        ),
+        (
+            "bighash",
+            Value::Hash(vec![(b"field".to_vec(), long.as_bytes().to_vec())]),
+        ),
+    ];
+    for (key, value) in &values {
+        writer.write_object(key.as_bytes(), value, None).unwrap();
+    }
+    writer.write_eof().unwrap();
+    let written = writer.into_inner();
+
+    // The values read back unchanged, apart from the order of compactly encoded sets.
+    let recorder = Recorder::new();
+    rdb::parse(&written[..], recorder.clone(), Simple::new()).unwrap();
+    let events = recorder.events();
+    assert!(events.contains(&"list_element list 70000".to_string()));
+    assert!(events.contains(&"set_element intset 5000000000".to_string()));
+    assert!(events.contains(&"sorted_set_element zset 2.5 b".to_string()));
+    assert_eq!(
+        200,
+        events
+            .iter()
+            .filter(|event| event.starts_with("list_element longlist"))
+            .count()
+    );
+
+    written
+}
+
+#[test]
+fn test_compact_encodings() {
+    let expected = [
+        (
+            8,
+            [
+                "list Quicklist",
+                "longlist Quicklist",
+                "intset Intset",
+                "set LinkedList",
+                "zset Ziplist",
+                "hash Ziplist",
+                "bighash Hashtable",
+            ],
+        ),
+        (
+            4,
+            [
+                "list Ziplist",
+                "longlist LinkedList",
+                "intset Intset",
+                "set LinkedList",
+                "zset Ziplist",
+                "hash Ziplist",
+                "bighash Hashtable",
+            ],
+        ),
+        (
+            2,
+            [
+                "list Ziplist",
+                "longlist LinkedList",
+                "intset Intset",
+                "set LinkedList",
+                "zset Ziplist",
+                "hash Zipmap",
+                "bighash Hashtable",
+            ],
+        ),
+    ];
+
+    for (version, expected) in &expected {
+        let written = write_values(*version, rdb::Encodings::default());
+        assert_eq!(expected.to_vec(), encodings_of(&written), "v{}", version);
+    }
+
+    let plain = write_values(8, rdb::Encodings::plain());
+    assert!(encodings_of(&plain)
+        .iter()
+        .all(|encoding| !encoding.contains("Ziplist") && !encoding.contains("Intset")));
+}
+
+#[test]
+fn test_compressed_and_integer_strings() {
+    let write = |value: &[u8], encodings: rdb::Encodings| {
+        let mut writer = RdbWriter::with_encodings(Vec::new(), 3, encodings).unwrap();
+        writer.write_header().unwrap();
+        writer
+            .write_object(b"k", &Value::String(value.to_vec()), None)
+            .unwrap();
+        writer.write_eof().unwrap();
+        writer.into_inner()
+    };
+
+    // INT16
+    assert_eq!(
+        b"REDIS0003\x00\x01k\xc1\x39\x30\xff".to_vec(),
+        write(b"12345", rdb::Encodings::default())
+    );
+    // Not the canonical form, so it stays a string
+    assert_eq!(
+        b"REDIS0003\x00\x01k\x03012\xff".to_vec(),
+        write(b"012", rdb::Encodings::default())
+    );
+
+    let value = vec![b'a'; 1000];
+    let compressed = write(&value, rdb::Encodings::default());
+    assert!(compressed.len() < 100);
+    assert!(write(&value, rdb::Encodings::plain()).len() > 1000);
+
+    let recorder = Recorder::new();
+    rdb::parse(&compressed[..], recorder.clone(), Simple::new()).unwrap();
+    assert!(recorder
+        .events()
+        .contains(&format!("set k {} None", "a".repeat(1000))));
+}
+
+#[test]
+fn test_dump_payload() {
+    // The example of the DUMP command in the Redis documentation
+    assert_eq!(
+        b"\x00\xc0\n\t\x00\xbem\x06\x89Z(\x00\n".to_vec(),
+        rdb::writer::dump_payload(&Value::String(b"10".to_vec()), 9).unwrap()
+    );
+
+    // A set listpack, followed by the version
+    let payload = rdb::writer::dump_payload(&Value::Set(vec![b"a".to_vec()]), 11).unwrap();
+    assert_eq!(20, payload[0]);
+    assert_eq!(&[11, 0], &payload[payload.len() - 10..payload.len() - 8]);
+}
+
+#[test]
+fn test_ziplist_integers_before_version_6() {
+    let list = Value::List(vec![b"7".to_vec(), b"-100".to_vec(), b"1000000".to_vec()]);
+
+    // Type, length of the blob, then the ziplist
+    let payload = rdb::writer::dump_payload(&list, 5).unwrap();
+    assert_eq!(10, payload[0]);
+    assert_eq!(vec![0xc0, 0xc0, 0xd0], ziplist_headers(&payload[2..]));
+
+    let payload = rdb::writer::dump_payload(&list, 6).unwrap();
+    assert_eq!(vec![0xf8, 0xfe, 0xf0], ziplist_headers(&payload[2..]));
+}
+
+#[test]
+fn test_eviction_info() {
+    let data = rdb_with_eviction_info();
+    let eviction_events = |data: &[u8]| -> Vec<String> {
+        let recorder = Recorder::new();
+        rdb::parse(data, recorder.clone(), Simple::new()).unwrap();
+        recorder
+            .events()
+            .into_iter()
+            .filter(|event| event.starts_with("eviction_info") || event.starts_with("set"))
+            .collect()
+    };
+
+    let expected = eviction_events(&data);
+    assert_eq!(expected, eviction_events(&rewrite(&data, 9)));
+    assert_eq!(expected, eviction_events(&rewrite(&data, 11)));
+
+    // Older versions have no eviction info
+    assert_eq!(
+        vec!["set a x None", "set b y None", "set c z None"],
+        eviction_events(&rewrite(&data, 8))
+    );
+}